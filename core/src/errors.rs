@@ -16,6 +16,57 @@ pub enum OmniverlayError {
     #[error("Extension load failed: {0}")]
     ExtensionLoadFailed(String),
 
+    #[error("Extension runtime error: {0}")]
+    ExtensionRuntime(String),
+
+    #[error("Extension panicked: {0}")]
+    ExtensionPanicked(String),
+
+    #[error("Extension {0} is not allowed {1}")]
+    PermissionDenied(String, String),
+
+    #[error("Extension {0} went over its budget: {1}")]
+    OverBudget(String, String),
+
+    #[error("Extension {0} is not running")]
+    ExtensionNotRunning(String),
+
+    #[error("Extension {0} does not handle {1}")]
+    RequestHandlerNotFound(String, String),
+
+    #[error("Extension instance error: {0}")]
+    ExtensionInstance(String),
+
+    #[error("Invalid extension manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error("Extension {0} depends on {1} {2}, which is not installed")]
+    MissingDependency(String, String, String),
+
+    #[error("Extension {0} depends on {1} {2}, but {3} is installed")]
+    IncompatibleDependency(String, String, String, String),
+
+    #[error("Extension {0} cannot start because its dependency {1} cannot start")]
+    DependencyFailed(String, String),
+
+    #[error("Extension {0} requires {1} to be enabled first")]
+    DependencyNotEnabled(String, String),
+
+    #[error("Dependency cycle between extensions: {0}")]
+    DependencyCycle(String),
+
+    #[error("Invalid extension package: {0}")]
+    InvalidPackage(String),
+
+    #[error("Refusing to load {0}: {1}")]
+    UntrustedExtension(String, String),
+
+    #[error("Extension {0} {1} is installed, installing {2} would downgrade it")]
+    DowngradeRefused(String, String, String),
+
+    #[error("Extension catalog error: {0}")]
+    CatalogError(String),
+
     #[error("Serialization error: {0}")]
     SerdeJson(#[from] serde_json::Error),
 
@@ -25,6 +76,9 @@ pub enum OmniverlayError {
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
+    #[error("Invalid config value: {0}")]
+    InvalidConfigValue(String),
+
     #[error("Config not found: {0}")]
     ConfigNotFound(String),
 
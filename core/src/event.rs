@@ -7,6 +7,23 @@ use tokio::sync::RwLock;
 #[derive(Debug)]
 pub enum OmniverlayEventType {
     UpdateExtensionData,
+    PublishExtensionData(String, serde_json::Value),
+    /// The supervisor stopped restarting an extension, with the last error
+    ExtensionGaveUp(String, String),
+    /// An extension was disabled for going over its `ResourceBudget`, with what it went over
+    ExtensionOverBudget(String, String),
+}
+
+impl OmniverlayEventType {
+    /// Name of the event, without its payload which can be large or private to an extension
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UpdateExtensionData => "UpdateExtensionData",
+            Self::PublishExtensionData(..) => "PublishExtensionData",
+            Self::ExtensionGaveUp(..) => "ExtensionGaveUp",
+            Self::ExtensionOverBudget(..) => "ExtensionOverBudget",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -30,11 +47,13 @@ macro_rules! invoke_event {
         {
             use $crate::event::{OmniverlayEvent, EVENT_HANDLER};
 
-            // Log the event type
-            log::info!("Invoking event: {:?}", $event_type);
+            let event_type = $event_type;
+
+            // Log the event type, published data is invoked on every payload
+            log::trace!("Invoking event: {}", event_type.name());
 
             // Create the Event
-            let event = OmniverlayEvent { event_type: $event_type };
+            let event = OmniverlayEvent { event_type };
 
             // Get the event handler from the OnceCell
             if let Some(handler) = EVENT_HANDLER.get() {
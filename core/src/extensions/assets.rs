@@ -0,0 +1,221 @@
+//! Frontend assets bundled with extensions.
+//!
+//! An extension directory may hold an `assets/` directory with an `index.html`, see `package`. The
+//! app serves it through the `omniverlay-ext` protocol and shows it in place of a component of the
+//! ui package:
+//!
+//! ```text
+//! omniverlay-ext://localhost/<extension type>/index.html?instance=<instance name>
+//! ```
+//!
+//! On Windows the webview reaches it as `https://omniverlay-ext.localhost/<extension type>/...`.
+//! Relative links of the page resolve inside the directory of its extension, nothing outside of it
+//! is ever served.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use super::{manifest::MANIFEST_FILE_NAME, package::ASSETS_DIR_NAME};
+
+pub const ASSETS_SCHEME: &str = "omniverlay-ext";
+
+/// Page of the assets shown in the overlay
+pub const ASSETS_ENTRY: &str = "index.html";
+
+/// Assets directory of an extension loaded from `source`, `None` if it has no `index.html`
+pub fn assets_dir_of(source: &Path) -> Option<PathBuf> {
+    source
+        .parent()
+        .filter(|dir| dir.join(MANIFEST_FILE_NAME).is_file())
+        .map(|dir| dir.join(ASSETS_DIR_NAME))
+        .filter(|dir| dir.join(ASSETS_ENTRY).is_file())
+}
+
+pub struct Asset {
+    pub contents: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+/// Assets directories by extension type, shared with the protocol handler so it does not wait on
+/// the `ExtensionManager`
+#[derive(Clone, Default)]
+pub struct AssetRegistry {
+    dirs: Arc<RwLock<HashMap<String, PathBuf>>>,
+}
+
+impl AssetRegistry {
+    pub(super) fn insert(&self, extension_type: &str, dir: Option<PathBuf>) {
+        let mut dirs = self.dirs.write().unwrap();
+
+        match dir {
+            Some(dir) => dirs.insert(extension_type.to_string(), dir),
+            None => dirs.remove(extension_type),
+        };
+    }
+
+    pub(super) fn remove(&self, extension_type: &str) {
+        self.dirs.write().unwrap().remove(extension_type);
+    }
+
+    pub fn has_assets(&self, extension_type: &str) -> bool {
+        self.dirs.read().unwrap().contains_key(extension_type)
+    }
+
+    /// File requested by a url of the protocol, `None` if it does not exist or is outside of the
+    /// assets of the extension
+    pub fn resolve(&self, url: &str) -> Option<PathBuf> {
+        let url = Url::parse(url).ok()?;
+
+        let mut segments = url
+            .path_segments()?
+            .map(|segment| percent_decode_str(segment).decode_utf8().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        if segments.is_empty() {
+            return None;
+        }
+
+        let extension_type = segments.remove(0);
+        let dir = self.dirs.read().unwrap().get(extension_type.as_ref())?.clone();
+
+        // `<extension type>/` serves the entry page
+        if segments.last().is_none_or(|segment| segment.is_empty()) {
+            segments.push(ASSETS_ENTRY.into());
+        }
+
+        let mut path = dir.clone();
+
+        for segment in segments.iter().filter(|segment| !segment.is_empty()) {
+            // Decoded segments may hide separators or parent directories
+            if segment == "." || segment == ".." || segment.contains(['/', '\\']) {
+                return None;
+            }
+
+            path.push(segment.as_ref());
+        }
+
+        // Symbolic links must not lead out of the directory either
+        let path = path.canonicalize().ok()?;
+
+        (path.starts_with(dir.canonicalize().ok()?) && path.is_file()).then_some(path)
+    }
+
+    pub fn load(&self, url: &str) -> Option<Asset> {
+        let path = self.resolve(url)?;
+
+        Some(Asset {
+            contents: std::fs::read(&path).ok()?,
+            mime_type: mime_type(&path),
+        })
+    }
+}
+
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html",
+        Some("js" | "mjs") => "text/javascript",
+        Some("css") => "text/css",
+        Some("json") => "application/json",
+        Some("wasm") => "application/wasm",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry_with_assets() -> (tempfile::TempDir, AssetRegistry) {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("clock").join(ASSETS_DIR_NAME);
+
+        std::fs::create_dir_all(dir.join("img")).unwrap();
+        std::fs::write(dir.join(ASSETS_ENTRY), "<html></html>").unwrap();
+        std::fs::write(dir.join("img/icon.svg"), "<svg/>").unwrap();
+        std::fs::write(root.path().join("secret.txt"), "secret").unwrap();
+
+        let registry = AssetRegistry::default();
+        registry.insert("Clock", Some(dir));
+
+        (root, registry)
+    }
+
+    #[test]
+    fn resolves_files_of_the_extension() {
+        let (_root, registry) = registry_with_assets();
+
+        let entry = registry.resolve("omniverlay-ext://localhost/Clock/").unwrap();
+        assert!(entry.ends_with(ASSETS_ENTRY));
+
+        let icon = registry.resolve("omniverlay-ext://localhost/Clock/img/icon.svg?instance=Clock").unwrap();
+        assert!(icon.ends_with("img/icon.svg"));
+
+        assert!(registry.resolve("https://omniverlay-ext.localhost/Clock/img/icon.svg").is_some());
+        assert!(registry.resolve("omniverlay-ext://localhost/Clock/missing.js").is_none());
+        assert!(registry.resolve("omniverlay-ext://localhost/Other/index.html").is_none());
+    }
+
+    #[test]
+    fn refuses_to_leave_the_assets_directory() {
+        let (_root, registry) = registry_with_assets();
+
+        for url in [
+            "omniverlay-ext://localhost/Clock/../../secret.txt",
+            "omniverlay-ext://localhost/Clock/%2E%2E/%2E%2E/secret.txt",
+            "omniverlay-ext://localhost/Clock/img/..%2F..%2F..%2Fsecret.txt",
+            "omniverlay-ext://localhost/Clock/img/..%5C..%5C..%5Csecret.txt",
+            "omniverlay-ext://localhost/Clock/%2Fetc%2Fpasswd",
+        ] {
+            assert_eq!(registry.resolve(url), None, "{url}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symbolic_links_leading_out() {
+        let (root, registry) = registry_with_assets();
+
+        let link = root.path().join("clock").join(ASSETS_DIR_NAME).join("link.txt");
+        std::os::unix::fs::symlink(root.path().join("secret.txt"), link).unwrap();
+
+        assert_eq!(registry.resolve("omniverlay-ext://localhost/Clock/link.txt"), None);
+    }
+
+    #[test]
+    fn removed_extension_is_not_served() {
+        let (_root, registry) = registry_with_assets();
+
+        registry.remove("Clock");
+
+        assert!(!registry.has_assets("Clock"));
+        assert!(registry.resolve("omniverlay-ext://localhost/Clock/").is_none());
+    }
+
+    #[test]
+    fn mime_types() {
+        assert_eq!(mime_type(Path::new("index.HTML")), "text/html");
+        assert_eq!(mime_type(Path::new("app.mjs")), "text/javascript");
+        assert_eq!(mime_type(Path::new("data")), "application/octet-stream");
+    }
+}
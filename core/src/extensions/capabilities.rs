@@ -0,0 +1,196 @@
+//! What an extension is allowed to touch.
+//!
+//! Extensions declare the capabilities they need in `ExtensionInfo::capabilities` (or in the
+//! `capabilities` of their manifest), and the user grants them per profile in
+//! `ExtensionState::granted_capabilities`, extensions compiled into the app start with what they
+//! declare. Host APIs check the grants through `Permissions`; native extensions call
+//! `ExtensionHost::require` before using a resource directly, or `ExtensionHost::require_path`
+//! before opening a file.
+//!
+//! `Filesystem` grants only cover absolute paths. The host checks a file access where it leads on
+//! disk, after following `..` and symbolic links, so a link inside a granted directory cannot
+//! lead out of it.
+
+use std::{
+    fmt, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Capability {
+    /// Read the files under `path`, and write them too if `write` is set. Relative paths, which
+    /// depend on the working directory of the app, cover nothing.
+    Filesystem {
+        path: PathBuf,
+        #[serde(default)]
+        write: bool,
+    },
+    /// List running processes and read their usage
+    ProcessList,
+    /// Read system wide usage: CPU, memory, disks
+    SystemInfo,
+    /// Connect to other machines
+    Network,
+    /// Start other programs
+    SpawnProcess,
+}
+
+impl Capability {
+    /// Whether granting `self` allows what `requested` asks for
+    pub fn covers(&self, requested: &Capability) -> bool {
+        match (self, requested) {
+            (
+                Self::Filesystem { path, write },
+                Self::Filesystem {
+                    path: requested_path,
+                    write: requested_write,
+                },
+            ) => {
+                path.has_root()
+                    && requested_path.has_root()
+                    && (*write || !requested_write)
+                    && normalize(requested_path).starts_with(normalize(path))
+            }
+            _ => self == requested,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Filesystem { path, write: false } => write!(f, "read access to {}", path.display()),
+            Self::Filesystem { path, write: true } => write!(f, "write access to {}", path.display()),
+            Self::ProcessList => write!(f, "process listing"),
+            Self::SystemInfo => write!(f, "system information"),
+            Self::Network => write!(f, "network access"),
+            Self::SpawnProcess => write!(f, "starting programs"),
+        }
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem, so `/granted/../etc` is not under `/granted`
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Where an access to `path` leads on disk. A written file may not exist yet, its directory must,
+/// and when it is a symbolic link the write goes where it points.
+fn resolve(path: &Path, write: bool) -> io::Result<PathBuf> {
+    if !write {
+        return std::fs::canonicalize(path);
+    }
+
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a file: {}", path.display()),
+        ));
+    };
+
+    let file = std::fs::canonicalize(parent)?.join(file_name);
+
+    match std::fs::symlink_metadata(&file) {
+        // Fails for a link to nothing, which would create a file wherever it points
+        Ok(metadata) if metadata.is_symlink() => std::fs::canonicalize(&file),
+        _ => Ok(file),
+    }
+}
+
+/// Capabilities granted to an extension instance, shared between the manager and its host
+#[derive(Clone)]
+pub struct Permissions {
+    extension_name: String,
+    granted: Arc<RwLock<Vec<Capability>>>,
+}
+
+impl Permissions {
+    pub(super) fn new(extension_name: String) -> Self {
+        Self {
+            extension_name,
+            granted: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Replace the grants, only what the extension declared can be granted
+    pub(super) fn grant(&self, declared: &[Capability], granted: &[Capability]) {
+        let granted = granted
+            .iter()
+            .filter(|capability| declared.iter().any(|declared| declared.covers(capability)))
+            .cloned()
+            .collect();
+
+        *self.granted.write().unwrap() = granted;
+    }
+
+    pub fn is_granted(&self, capability: &Capability) -> bool {
+        self.granted
+            .read()
+            .unwrap()
+            .iter()
+            .any(|granted| granted.covers(capability))
+    }
+
+    /// Check a file access where it leads on disk, see `resolve`, and return the path to access.
+    /// Granted directories are resolved too, so both sides are compared in the same form. Only
+    /// uses `std`, extension libraries have no runtime to run `tokio::fs` on.
+    pub fn require_path(&self, path: &Path, write: bool) -> OmniverlayResult<PathBuf> {
+        let requested = Capability::Filesystem {
+            path: path.to_path_buf(),
+            write,
+        };
+
+        // Checked as written first, so the error does not tell whether an ungranted path exists
+        self.require(&requested)?;
+
+        let resolved = Capability::Filesystem {
+            path: resolve(path, write)?,
+            write,
+        };
+
+        let covered = self.granted.read().unwrap().iter().any(|granted| match granted {
+            Capability::Filesystem { path, write } => Capability::Filesystem {
+                path: std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+                write: *write,
+            }
+            .covers(&resolved),
+            _ => false,
+        });
+
+        match resolved {
+            Capability::Filesystem { path, .. } if covered => Ok(path),
+            _ => Err(OmniverlayError::PermissionDenied(
+                self.extension_name.clone(),
+                requested.to_string(),
+            )),
+        }
+    }
+
+    pub fn require(&self, capability: &Capability) -> OmniverlayResult<()> {
+        match self.is_granted(capability) {
+            true => Ok(()),
+            false => Err(OmniverlayError::PermissionDenied(
+                self.extension_name.clone(),
+                capability.to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Catalogs of extension packages.
+//!
+//! A catalog is a JSON file listing packages, read from a directory shared by a team (`file://`)
+//! or from a local HTTP server (`http://`):
+//!
+//! ```json
+//! {
+//!     "name": "Team extensions",
+//!     "extensions": [
+//!         {
+//!             "id": "dev.team.clock",
+//!             "name": "Clock",
+//!             "version": "1.2.0",
+//!             "description": "Shows the time",
+//!             "tags": ["time"],
+//!             "url": "clock-1.2.0.omnipkg",
+//!             "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! `url` is relative to the catalog unless absolute, in which case it must use the scheme of the
+//! catalog. `sha256` is the one of the `.omnipkg` file.
+//! Several versions of an extension can be listed, the newest one this host can run is offered.
+//! Catalogs are set in `OmniverlaySettings::catalogs`.
+
+use std::{collections::HashMap, io::Read, path::PathBuf, time::Duration};
+
+use log::warn;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    settings::OmniverlaySettings,
+    utils::fs::get_downloads_dir,
+};
+
+use super::{
+    installer::PackageInstaller,
+    loader::HOST_VERSION,
+    package::{ExtensionPackage, MAX_UNPACKED_SIZE, PACKAGE_EXTENSION},
+};
+
+/// Largest catalog accepted
+const MAX_CATALOG_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Time given to an `http://` server to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to an `http://` server to send the next part of its answer
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn http_error(e: std::io::Error) -> String {
+    match e.kind() {
+        // `WouldBlock` on Unix
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+            format!("timed out, no connection within {CONNECT_TIMEOUT:?} or no data for {READ_TIMEOUT:?}")
+        }
+        _ => e.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub extensions: Vec<CatalogEntry>,
+}
+
+/// A package listed in a catalog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub id: String,
+    pub name: String,
+    pub version: Version,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub min_host_version: Option<Version>,
+    /// Location of the package, absolute once the catalog is fetched
+    pub url: String,
+    pub sha256: String,
+}
+
+impl CatalogEntry {
+    fn is_compatible(&self, host_version: &Version) -> bool {
+        self.min_host_version.as_ref().is_none_or(|min| min <= host_version)
+    }
+
+    /// Fails unless `package` is the extension and version listed, the catalog could point at any package
+    pub fn check_package(&self, package: &ExtensionPackage) -> OmniverlayResult<()> {
+        let manifest = &package.manifest;
+
+        if manifest.id != self.id || manifest.version != self.version {
+            return Err(OmniverlayError::CatalogError(format!(
+                "{}: the catalog lists {} {} but the package is {} {}",
+                self.url, self.id, self.version, manifest.id, manifest.version
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        [&self.id, &self.name]
+            .into_iter()
+            .chain(self.description.as_ref())
+            .chain(&self.tags)
+            .any(|text| text.to_lowercase().contains(&query))
+    }
+}
+
+/// The newest version of an extension offered by the catalogs, compared with the installed one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailableExtension {
+    pub entry: CatalogEntry,
+    /// Url of the catalog listing it
+    pub catalog: String,
+    pub installed: Option<Version>,
+    pub update_available: bool,
+}
+
+fn catalog_error(url: &Url, message: impl std::fmt::Display) -> OmniverlayError {
+    OmniverlayError::CatalogError(format!("{url}: {message}"))
+}
+
+impl Catalog {
+    /// Read the catalog at `url`, resolving the urls of its packages
+    pub async fn fetch(url: &Url) -> OmniverlayResult<Self> {
+        let contents = fetch(url, MAX_CATALOG_SIZE).await?;
+        let mut catalog: Self = serde_json::from_slice(&contents).map_err(|e| catalog_error(url, e))?;
+
+        for entry in &mut catalog.extensions {
+            let package_url = url.join(&entry.url).map_err(|e| catalog_error(url, e))?;
+
+            // An `http://` catalog must not point at the files of the user
+            if package_url.scheme() != url.scheme() {
+                return Err(catalog_error(
+                    url,
+                    format!("{package_url} does not use the scheme of the catalog"),
+                ));
+            }
+
+            entry.url = package_url.to_string();
+        }
+
+        Ok(catalog)
+    }
+}
+
+/// Contents of a `file://` or `http://` url, at most `max_size` bytes
+async fn fetch(url: &Url, max_size: u64) -> OmniverlayResult<Vec<u8>> {
+    let too_large = || catalog_error(url, format!("larger than {max_size} bytes"));
+
+    match url.scheme() {
+        "file" => {
+            let path = url.to_file_path().map_err(|_| catalog_error(url, "not a file path"))?;
+
+            if tokio::fs::metadata(&path).await?.len() > max_size {
+                return Err(too_large());
+            }
+
+            Ok(tokio::fs::read(&path).await?)
+        }
+        "http" => {
+            let request_url = url.clone();
+
+            let response = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
+                let response = attohttpc::get(request_url.as_str())
+                    .connect_timeout(CONNECT_TIMEOUT)
+                    .read_timeout(READ_TIMEOUT)
+                    .send()
+                    .map_err(|e| match e.into_kind() {
+                        attohttpc::ErrorKind::Io(e) => http_error(e),
+                        kind => attohttpc::Error::from(kind).to_string(),
+                    })?;
+
+                if !response.is_success() {
+                    return Err(format!("HTTP {}", response.status()));
+                }
+
+                let mut contents = Vec::new();
+                response
+                    .take(max_size + 1)
+                    .read_to_end(&mut contents)
+                    .map_err(http_error)?;
+
+                Ok(contents)
+            })
+            .await
+            .map_err(|e| OmniverlayError::Other(e.into()))?
+            .map_err(|e| catalog_error(url, e))?;
+
+            if response.len() as u64 > max_size {
+                return Err(too_large());
+            }
+
+            Ok(response)
+        }
+        scheme => Err(catalog_error(url, format!("unsupported scheme {scheme}, use file:// or http://"))),
+    }
+}
+
+/// Url of a catalog given in the settings, plain paths are accepted
+pub fn parse_catalog_url(location: &str) -> OmniverlayResult<Url> {
+    match Url::parse(location) {
+        Ok(url) => Ok(url),
+        Err(_) => Url::from_file_path(PathBuf::from(location))
+            .map_err(|_| OmniverlayError::CatalogError(format!("{location}: not a url nor an absolute path"))),
+    }
+}
+
+/// The catalogs of the user
+pub struct ExtensionCatalog {
+    sources: Vec<Url>,
+}
+
+impl ExtensionCatalog {
+    /// Catalogs of the settings
+    pub fn load() -> OmniverlayResult<Self> {
+        let sources = OmniverlaySettings::load()?
+            .catalogs
+            .iter()
+            .map(|location| parse_catalog_url(location))
+            .collect::<OmniverlayResult<_>>()?;
+
+        Ok(Self::new(sources))
+    }
+
+    pub fn new(sources: Vec<Url>) -> Self {
+        Self { sources }
+    }
+
+    /// Newest compatible version of every extension, from every catalog that could be read. A
+    /// catalog failing to load is skipped so the others stay usable.
+    pub async fn available(&self) -> OmniverlayResult<Vec<AvailableExtension>> {
+        let host_version = Version::parse(HOST_VERSION).map_err(|e| OmniverlayError::Other(e.into()))?;
+        let mut newest: HashMap<String, (CatalogEntry, &Url)> = HashMap::new();
+
+        for source in &self.sources {
+            let catalog = match Catalog::fetch(source).await {
+                Ok(catalog) => catalog,
+                Err(e) => {
+                    warn!("Skipping extension catalog {}: {}", source, e);
+                    continue;
+                }
+            };
+
+            for entry in catalog.extensions.into_iter().filter(|e| e.is_compatible(&host_version)) {
+                match newest.get(&entry.id) {
+                    Some((current, _)) if current.version >= entry.version => {}
+                    _ => {
+                        newest.insert(entry.id.clone(), (entry, source));
+                    }
+                }
+            }
+        }
+
+        let installed: HashMap<String, Version> = PackageInstaller::new()?
+            .list_installed()?
+            .into_iter()
+            .map(|package| (package.manifest.id, package.manifest.version))
+            .collect();
+
+        let mut available: Vec<AvailableExtension> = newest
+            .into_values()
+            .map(|(entry, catalog)| {
+                let installed = installed.get(&entry.id).cloned();
+
+                AvailableExtension {
+                    update_available: installed.as_ref().is_some_and(|installed| *installed < entry.version),
+                    installed,
+                    entry,
+                    catalog: catalog.to_string(),
+                }
+            })
+            .collect();
+
+        available.sort_by(|a, b| a.entry.name.cmp(&b.entry.name));
+
+        Ok(available)
+    }
+
+    /// Available extensions whose id, name, description or tags contain `query`, ignoring case
+    pub async fn search(&self, query: &str) -> OmniverlayResult<Vec<AvailableExtension>> {
+        let mut available = self.available().await?;
+
+        available.retain(|extension| extension.entry.matches(query.trim()));
+
+        Ok(available)
+    }
+
+    /// Installed extensions with a newer version in a catalog
+    pub async fn updates(&self) -> OmniverlayResult<Vec<AvailableExtension>> {
+        let mut available = self.available().await?;
+
+        available.retain(|extension| extension.update_available);
+
+        Ok(available)
+    }
+
+    /// Newest available version of the extension `id`
+    pub async fn find(&self, id: &str) -> OmniverlayResult<AvailableExtension> {
+        self.available()
+            .await?
+            .into_iter()
+            .find(|extension| extension.entry.id == id)
+            .ok_or_else(|| OmniverlayError::CatalogError(format!("no catalog offers {id}")))
+    }
+}
+
+/// Download the package of `entry` into `~/.omniverlay/downloads`, checking its checksum
+pub async fn download(entry: &CatalogEntry) -> OmniverlayResult<PathBuf> {
+    let url = Url::parse(&entry.url).map_err(|e| OmniverlayError::CatalogError(format!("{}: {e}", entry.url)))?;
+    let contents = fetch(&url, MAX_UNPACKED_SIZE).await?;
+
+    let sha256 = hex::encode(Sha256::digest(&contents));
+
+    if sha256 != entry.sha256.to_ascii_lowercase() {
+        return Err(catalog_error(&url, "checksum does not match the catalog"));
+    }
+
+    let dir = get_downloads_dir()?;
+    tokio::fs::create_dir_all(&dir).await?;
+
+    // Named after the checksum, the id comes from the catalog and could hold anything
+    let path = dir.join(format!("{sha256}.{PACKAGE_EXTENSION}"));
+    tokio::fs::write(&path, contents).await?;
+
+    Ok(path)
+}
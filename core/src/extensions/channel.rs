@@ -0,0 +1,219 @@
+//! Data published by extensions for the overlay.
+//!
+//! Every extension has its own topic. Publishing faster than `MIN_PUBLISH_INTERVAL` only keeps
+//! the latest payload, which is sent once the interval is over, and the last payload of every
+//! topic is cached so a freshly opened overlay can show it right away.
+//!
+//! Other extensions can subscribe to a topic, they receive every payload without rate limiting.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::{errors::OmniverlayResult, event::OmniverlayEventType, invoke_event, utils::runtime::RuntimeHandle};
+
+/// Shortest delay between two payloads of the same extension reaching the overlay
+pub const MIN_PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Payloads kept for a subscriber that does not keep up, older ones are dropped
+const SUBSCRIPTION_CAPACITY: usize = 16;
+
+/// Name of the Tauri event carrying the payloads of `extension_name` to the overlay.
+///
+/// Tauri only accepts alphanumerics and `-/:_` in event names, every other byte of the name is
+/// written as `:` and two hex digits, so `Clock#2` publishes on `Extension://Clock:232/data`.
+pub fn data_event_name(extension_name: &str) -> String {
+    let mut encoded = String::with_capacity(extension_name.len());
+
+    for byte in extension_name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!(":{byte:02X}")),
+        }
+    }
+
+    format!("Extension://{encoded}/data")
+}
+
+#[derive(Default)]
+struct Topic {
+    last: Option<Value>,
+    last_sent: Option<Instant>,
+    flush_scheduled: bool,
+    subscribers: Option<broadcast::Sender<Value>>,
+}
+
+/// Payloads published by another extension, see `ExtensionHost::subscribe`
+pub struct DataSubscription {
+    extension_name: String,
+    receiver: broadcast::Receiver<Value>,
+}
+
+impl DataSubscription {
+    /// Wait for the next payload, `None` once the channel is gone
+    pub async fn recv_value(&mut self) -> Option<Value> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(payload) => return Some(payload),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber of extension {} skipped {} payloads", self.extension_name, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Next payload if one is already waiting
+    pub fn try_recv_value(&mut self) -> Option<Value> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(payload) => return Some(payload),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    warn!("Subscriber of extension {} skipped {} payloads", self.extension_name, skipped);
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    pub async fn recv<T: DeserializeOwned>(&mut self) -> OmniverlayResult<Option<T>> {
+        match self.recv_value().await {
+            Some(payload) => Ok(Some(serde_json::from_value(payload)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct DataChannel {
+    topics: Arc<Mutex<HashMap<String, Topic>>>,
+    // Extensions may publish from threads outside of tokio
+    runtime: RuntimeHandle,
+}
+
+impl DataChannel {
+    pub fn new() -> Self {
+        Self {
+            runtime: RuntimeHandle::current(),
+            ..Default::default()
+        }
+    }
+
+    pub fn publish(&self, extension_name: &str, payload: Value) {
+        let mut topics = self.topics.lock().unwrap();
+        let topic = topics.entry(extension_name.to_string()).or_default();
+
+        topic.last = Some(payload.clone());
+
+        if let Some(subscribers) = &topic.subscribers {
+            // Fails when nobody is subscribed anymore, which is fine
+            let _ = subscribers.send(payload.clone());
+        }
+
+        let now = Instant::now();
+        let ready_at = topic.last_sent.map(|sent| sent + MIN_PUBLISH_INTERVAL).unwrap_or(now);
+
+        if ready_at <= now {
+            topic.last_sent = Some(now);
+
+            let name = extension_name.to_string();
+            self.runtime.spawn(async move {
+                invoke_event!(OmniverlayEventType::PublishExtensionData(name, payload));
+            });
+
+            return;
+        }
+
+        // A flush is already waiting, it will send this payload since it is now the latest
+        if topic.flush_scheduled {
+            return;
+        }
+
+        topic.flush_scheduled = true;
+
+        let channel = self.clone();
+        let name = extension_name.to_string();
+
+        self.runtime.spawn(async move {
+            tokio::time::sleep(ready_at - now).await;
+            channel.flush(name).await;
+        });
+    }
+
+    async fn flush(&self, extension_name: String) {
+        let payload = {
+            let mut topics = self.topics.lock().unwrap();
+
+            let Some(topic) = topics.get_mut(&extension_name) else {
+                return;
+            };
+
+            topic.flush_scheduled = false;
+            topic.last_sent = Some(Instant::now());
+
+            topic.last.clone()
+        };
+
+        // Cleared in the meantime, the extension was disabled
+        if let Some(payload) = payload {
+            invoke_event!(OmniverlayEventType::PublishExtensionData(extension_name, payload));
+        }
+    }
+
+    pub fn subscribe(&self, extension_name: &str) -> DataSubscription {
+        let mut topics = self.topics.lock().unwrap();
+        let topic = topics.entry(extension_name.to_string()).or_default();
+
+        let receiver = match &topic.subscribers {
+            Some(subscribers) => subscribers.subscribe(),
+            None => {
+                let (sender, receiver) = broadcast::channel(SUBSCRIPTION_CAPACITY);
+                topic.subscribers = Some(sender);
+                receiver
+            }
+        };
+
+        DataSubscription {
+            extension_name: extension_name.to_string(),
+            receiver,
+        }
+    }
+
+    /// Last payload published by an extension
+    pub fn last(&self, extension_name: &str) -> Option<Value> {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(extension_name)
+            .and_then(|topic| topic.last.clone())
+    }
+
+    /// Last payload of every extension that published something
+    pub fn all(&self) -> HashMap<String, Value> {
+        self.topics
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, topic)| Some((name.clone(), topic.last.clone()?)))
+            .collect()
+    }
+
+    /// Forget the cached payload, so a stopped extension does not show stale data
+    pub fn clear(&self, extension_name: &str) {
+        if let Some(topic) = self.topics.lock().unwrap().get_mut(extension_name) {
+            topic.last = None;
+        }
+    }
+
+    /// Forget a topic, its subscribers are told the channel is gone
+    pub fn remove(&self, extension_name: &str) {
+        self.topics.lock().unwrap().remove(extension_name);
+    }
+}
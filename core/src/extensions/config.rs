@@ -2,11 +2,15 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::{
     collections::{HashMap, HashSet},
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
 use crate::errors::{OmniverlayError, OmniverlayResult}; // Ensure serde_json is used for JSON serialization
 
+/// Derives `OmniverlayConfig`, see the `omniverlay_macros` crate for the attributes
+pub use omniverlay_macros::OmniverlayConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigEnum {
     pub name: String,
@@ -63,6 +67,7 @@ impl ConfigCategory {
     }
 }
 
+#[derive(Clone)]
 pub struct ConfigCategoryBuilder {
     name: String,
     values: HashMap<String, ConfigValue>,
@@ -108,6 +113,18 @@ impl ExtensionConfig {
         self.categories.iter().find(|c| c.name == name)
     }
 
+    /// Insert a value, the category is created if needed
+    pub fn set_value(&mut self, category: &str, name: &str, value: ConfigValue) {
+        match self.categories.iter_mut().find(|c| c.name == category) {
+            Some(category) => category.add_value(name.to_string(), value),
+            None => {
+                let mut new_category = ConfigCategory::new(category.to_string());
+                new_category.add_value(name.to_string(), value);
+                self.categories.push(new_category);
+            }
+        }
+    }
+
     pub fn to_json(&self) -> OmniverlayResult<String> {
         let json = serde_json::to_string_pretty(self)?;
 
@@ -197,6 +214,129 @@ impl ExtensionConfig {
     }
 }
 
+/// Typed view of an `ExtensionConfig`, usually derived with `#[derive(OmniverlayConfig)]`
+pub trait OmniverlayConfig: Sized {
+    fn default_values() -> Self;
+
+    /// Fails when a value cannot be represented, like an integer above `i64::MAX`
+    fn to_config(&self) -> OmniverlayResult<ExtensionConfig>;
+
+    /// Values missing from `config` keep their default, values of the wrong type are an error
+    fn from_config(config: &ExtensionConfig) -> OmniverlayResult<Self>;
+
+    /// Config shown in the studio before the user changes anything
+    fn default_config() -> OmniverlayResult<ExtensionConfig> {
+        Self::default_values().to_config()
+    }
+}
+
+/// Types that can be stored in a `ConfigValueType`
+pub trait ConfigField: Sized {
+    fn to_config_value(&self) -> OmniverlayResult<ConfigValueType>;
+
+    fn from_config_value(value: &ConfigValueType) -> Option<Self>;
+}
+
+impl ConfigField for String {
+    fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+        Ok(ConfigValueType::String(self.clone()))
+    }
+
+    fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+        match value {
+            ConfigValueType::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl ConfigField for bool {
+    fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+        Ok(ConfigValueType::Bool(*self))
+    }
+
+    fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+        match value {
+            ConfigValueType::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl ConfigField for PathBuf {
+    fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+        Ok(ConfigValueType::Path(self.to_string_lossy().into_owned()))
+    }
+
+    fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+        match value {
+            ConfigValueType::Path(value) => Some(PathBuf::from(value)),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_int_config_field {
+    ($($ty:ty),*) => {
+        $(
+            impl ConfigField for $ty {
+                fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+                    // u64 and usize values above i64::MAX would wrap to negative numbers
+                    i64::try_from(*self).map(ConfigValueType::Int).map_err(|_| {
+                        OmniverlayError::InvalidConfigValue(format!("{} does not fit in a config integer", self))
+                    })
+                }
+
+                fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+                    match value {
+                        ConfigValueType::Int(value) => (*value).try_into().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_int_config_field!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+macro_rules! impl_float_config_field {
+    ($($ty:ty),*) => {
+        $(
+            impl ConfigField for $ty {
+                fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+                    Ok(ConfigValueType::Float(*self as f64))
+                }
+
+                fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+                    match value {
+                        ConfigValueType::Float(value) => Some(*value as $ty),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_float_config_field!(f32, f64);
+
+impl<T: ConfigField> ConfigField for Vec<T> {
+    fn to_config_value(&self) -> OmniverlayResult<ConfigValueType> {
+        Ok(ConfigValueType::List(
+            self.iter().map(ConfigField::to_config_value).collect::<OmniverlayResult<_>>()?,
+        ))
+    }
+
+    fn from_config_value(value: &ConfigValueType) -> Option<Self> {
+        match value {
+            ConfigValueType::List(values) => values.iter().map(T::from_config_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ExtensionConfigBuilder {
     pub categories: Vec<ConfigCategory>,
 }
@@ -2,7 +2,7 @@ use crate::{
     errors::{OmniverlayError, OmniverlayResult}, event::OmniverlayEventType, get_omniverlay, invoke_event, utils::fs::get_omniverlay_dir
 };
 use async_trait::async_trait;
-use log::info;
+use log::error;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
@@ -33,6 +33,16 @@ pub trait OmniverlayData: Serialize + for<'de> Deserialize<'de> {
     fn dir_path() -> OmniverlayResult<PathBuf>;
 }
 
+/// What happens to the entries of an unregistered extension in the current profile and layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataRetention {
+    /// Remove them, the extension starts from its defaults if it comes back
+    Prune,
+    /// Keep them, the extension gets its state and layout back if it is registered again
+    Keep,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OmniverlayProfile {
     pub name: String,
@@ -57,14 +67,16 @@ impl OmniverlayData for OmniverlayProfile {
             let omniverlay_guard = omniverlay.read().await;
 
             let extension_manager = omniverlay_guard.get_extension_manager().await;
-            let mut extension_manager_guard = extension_manager.write().await;
 
-            // Update with new extensions
-            for (extension_name, extension_state) in &self.extensions {
-                extension_manager_guard
-                    .update_extension_state(extension_name, extension_state.clone())
-                    .await?;
-            }
+            extension_manager
+                .write()
+                .await
+                .create_missing_instances(self.extensions.keys())
+                .await;
+
+            let extension_manager_guard = extension_manager.read().await;
+
+            extension_manager_guard.apply_states(&self.extensions).await?;
         }
 
         Ok(())
@@ -142,11 +154,17 @@ impl OmniverlayData for OmniverlayLayout {
             let extension_manager = omniverlay_guard.get_extension_manager().await;
             let mut extension_manager_guard = extension_manager.write().await;
 
+            extension_manager_guard.create_missing_instances(self.extensions.keys()).await;
+
             // Update with new extensions
             for (extension_name, extension_layout) in &self.extensions {
-                extension_manager_guard
+                // One failing extension must not prevent the others from being updated
+                if let Err(e) = extension_manager_guard
                     .update_extension_layout(extension_name, extension_layout.clone())
-                    .await?;
+                    .await
+                {
+                    error!("Failed to update layout of extension {}: {}", extension_name, e);
+                }
             }
         }
 
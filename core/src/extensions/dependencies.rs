@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use semver::{Version, VersionReq};
+
+use crate::errors::OmniverlayError;
+
+use super::ExtensionInfo;
+
+/// What the resolver needs to know about an extension
+#[derive(Debug, Clone)]
+pub struct DependencyNode {
+    /// Key of the extension in the `ExtensionManager`
+    pub name: String,
+    /// Manifest id, or the name for extensions without a manifest
+    pub id: String,
+    pub version: Option<Version>,
+    pub dependencies: HashMap<String, VersionReq>,
+}
+
+impl DependencyNode {
+    pub fn from_info(info: &ExtensionInfo) -> Self {
+        match &info.manifest {
+            Some(manifest) => Self {
+                name: info.name.clone(),
+                id: manifest.id.clone(),
+                version: Some(manifest.version.clone()),
+                dependencies: manifest.dependencies.clone(),
+            },
+            None => Self {
+                name: info.name.clone(),
+                id: info.name.clone(),
+                version: None,
+                dependencies: HashMap::new(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StartupOrder {
+    /// Extension names, every extension comes after its dependencies
+    pub order: Vec<String>,
+    /// Extensions that cannot be started and why
+    pub errors: Vec<(String, OmniverlayError)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+    Failed,
+}
+
+struct Resolver<'a> {
+    nodes: HashMap<&'a str, &'a DependencyNode>,
+    marks: HashMap<&'a str, Mark>,
+    stack: Vec<&'a str>,
+    result: StartupOrder,
+}
+
+impl<'a> Resolver<'a> {
+    fn fail(&mut self, id: &'a str, error: OmniverlayError) {
+        self.marks.insert(id, Mark::Failed);
+        self.result.errors.push((self.nodes[id].name.clone(), error));
+    }
+
+    // Returns false when the extension cannot be started
+    fn visit(&mut self, id: &'a str) -> bool {
+        match self.marks.get(id) {
+            Some(Mark::Done) => return true,
+            Some(Mark::Failed) => return false,
+            Some(Mark::Visiting) => {
+                let start = self.stack.iter().position(|s| *s == id).unwrap_or(0);
+                let mut cycle: Vec<&str> = self.stack[start..].to_vec();
+                cycle.push(id);
+
+                let error = OmniverlayError::DependencyCycle(cycle.join(" -> "));
+                self.fail(id, error);
+
+                return false;
+            }
+            None => {}
+        }
+
+        let node = self.nodes[id];
+
+        self.marks.insert(id, Mark::Visiting);
+        self.stack.push(id);
+
+        // Sorted so the resulting order does not depend on HashMap iteration
+        let mut dependencies: Vec<_> = node.dependencies.iter().collect();
+        dependencies.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut ok = true;
+
+        for (dependency_id, requirement) in dependencies {
+            let Some(dependency) = self.nodes.get(dependency_id.as_str()).copied() else {
+                self.fail(
+                    id,
+                    OmniverlayError::MissingDependency(node.name.clone(), dependency_id.clone(), requirement.to_string()),
+                );
+                ok = false;
+                break;
+            };
+
+            let compatible = match &dependency.version {
+                Some(version) => requirement.matches(version),
+                None => *requirement == VersionReq::STAR,
+            };
+
+            if !compatible {
+                let installed = dependency
+                    .version
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "an unversioned extension".to_string());
+
+                self.fail(
+                    id,
+                    OmniverlayError::IncompatibleDependency(
+                        node.name.clone(),
+                        dependency_id.clone(),
+                        requirement.to_string(),
+                        installed,
+                    ),
+                );
+                ok = false;
+                break;
+            }
+
+            if !self.visit(dependency.id.as_str()) {
+                if self.marks.get(id) != Some(&Mark::Failed) {
+                    let error = OmniverlayError::DependencyFailed(node.name.clone(), dependency.name.clone());
+                    self.fail(id, error);
+                }
+                ok = false;
+                break;
+            }
+        }
+
+        self.stack.pop();
+
+        if ok {
+            self.marks.insert(id, Mark::Done);
+            self.result.order.push(node.name.clone());
+        }
+
+        ok
+    }
+}
+
+/// Order extensions so every extension starts after its dependencies.
+///
+/// Extensions with a missing, incompatible or cyclic dependency are left out of the order and
+/// reported in `errors`, together with everything that depends on them.
+pub fn resolve_startup_order(nodes: &[DependencyNode]) -> StartupOrder {
+    let mut resolver = Resolver {
+        nodes: nodes.iter().map(|node| (node.id.as_str(), node)).collect(),
+        marks: HashMap::new(),
+        stack: Vec::new(),
+        result: StartupOrder::default(),
+    };
+
+    let mut ids: Vec<&str> = nodes.iter().map(|node| node.id.as_str()).collect::<HashSet<_>>().into_iter().collect();
+    ids.sort();
+
+    for id in ids {
+        resolver.visit(id);
+    }
+
+    resolver.result
+}
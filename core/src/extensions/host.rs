@@ -0,0 +1,692 @@
+use std::{
+    any::Any,
+    ffi::c_void,
+    future::Future,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use log::{error, warn, Level, Record};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{oneshot, Mutex};
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    utils::runtime::RuntimeHandle,
+};
+
+use super::{
+    capabilities::{Capability, Permissions},
+    channel::{DataChannel, DataSubscription},
+    messaging::{MessageBus, RequestHandler, REQUEST_TIMEOUT},
+    storage::ExtensionStorage,
+    supervisor::{self, TimedOutCalls},
+    usage::ExtensionUsage,
+    ExtensionInfo, Registration, RegisteredExtension,
+};
+
+/// Functions of the host, reached through plain C calls.
+///
+/// An extension library links its own copy of the core, with its own statics: code of the library
+/// would publish to an event handler that was never set, or log to a logger that was never
+/// installed. `ExtensionHost` calls these instead, which always point into the host, and the
+/// `ExtensionRegistrar` hands them to the library for its logger. Only the host reads the
+/// `ExtensionHost` behind a `host` pointer, the library was built by the same compiler against the
+/// same core for it to be loaded.
+///
+/// Calls that need the tokio runtime of the host answer through a `Completion` once they are done,
+/// the library only waits on a channel, which works without a runtime.
+#[repr(C)]
+pub struct HostVTable {
+    /// Publish `len` bytes of JSON for the `ExtensionHost` at `host`, false when they are not JSON
+    pub publish: unsafe extern "C" fn(host: *const c_void, json: *const u8, len: usize) -> bool,
+    /// Report that the extension of the `ExtensionHost` at `host` failed with a message of `len` bytes
+    pub report_failure: unsafe extern "C" fn(host: *const c_void, message: *const u8, len: usize),
+    /// Call `run(task)` on a blocking thread of the host runtime
+    pub schedule: unsafe extern "C" fn(host: *const c_void, task: *mut c_void, run: unsafe extern "C" fn(*mut c_void)),
+    /// Log a message with a `log::Level` given as a number, 1 for errors to 5 for traces
+    pub log: unsafe extern "C" fn(level: usize, target: *const u8, target_len: usize, message: *const u8, message_len: usize),
+    /// Send the request of `len` bytes of `HostRequest` JSON, completed with the JSON of the answer
+    pub request: unsafe extern "C" fn(host: *const c_void, request: *const u8, len: usize, context: *mut c_void, complete: Completion),
+    /// Read the file at a UTF-8 path of `len` bytes, completed with its contents
+    pub read_file: unsafe extern "C" fn(host: *const c_void, path: *const u8, len: usize, context: *mut c_void, complete: Completion),
+    /// List the directory at a UTF-8 path of `len` bytes, completed with the JSON array of its entries
+    pub read_dir: unsafe extern "C" fn(host: *const c_void, path: *const u8, len: usize, context: *mut c_void, complete: Completion),
+    /// Write `contents_len` bytes to the file at a UTF-8 path of `path_len` bytes, completed with nothing
+    pub write_file: unsafe extern "C" fn(
+        host: *const c_void,
+        path: *const u8,
+        path_len: usize,
+        contents: *const u8,
+        contents_len: usize,
+        context: *mut c_void,
+        complete: Completion,
+    ),
+}
+
+/// Called once by the host with the outcome of a call: `len` bytes of its result when `ok`, of the
+/// message of its error otherwise
+pub type Completion = unsafe extern "C" fn(context: *mut c_void, ok: bool, bytes: *const u8, len: usize);
+
+/// Of the copy of the core this is compiled in, only the host's one is ever called
+pub static HOST_VTABLE: HostVTable = HostVTable {
+    publish: host_publish,
+    report_failure: host_report_failure,
+    schedule: host_schedule,
+    log: host_log,
+    request: host_request,
+    read_file: host_read_file,
+    read_dir: host_read_dir,
+    write_file: host_write_file,
+};
+
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes
+unsafe fn slice<'a>(bytes: *const u8, len: usize) -> &'a [u8] {
+    match len {
+        0 => &[],
+        _ => unsafe { std::slice::from_raw_parts(bytes, len) },
+    }
+}
+
+unsafe extern "C" fn host_publish(host: *const c_void, json: *const u8, len: usize) -> bool {
+    // SAFETY: Only called by `ExtensionHost` with itself and its serialized payload
+    let (host, json) = unsafe { (&*(host as *const ExtensionHost), slice(json, len)) };
+
+    match serde_json::from_slice(json) {
+        Ok(payload) => {
+            host.usage.record_publish();
+            host.channel.publish(&host.extension_name, payload);
+
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+unsafe extern "C" fn host_report_failure(host: *const c_void, message: *const u8, len: usize) {
+    // SAFETY: Only called by `ExtensionHost` with itself and the error message
+    let (host, message) = unsafe { (&*(host as *const ExtensionHost), slice(message, len)) };
+
+    // Already the whole message of the error
+    let error = OmniverlayError::Other(anyhow::anyhow!("{}", String::from_utf8_lossy(message)));
+
+    let Some(registration) = host.registration.upgrade() else {
+        error!("Extension {} failed without being registered: {}", host.extension_name, error);
+        return;
+    };
+
+    let extension_name = host.extension_name.clone();
+
+    host.runtime.spawn(async move {
+        supervisor::fail_running(extension_name, RegisteredExtension(registration), &error).await;
+    });
+}
+
+struct ScheduledTask(*mut c_void, unsafe extern "C" fn(*mut c_void));
+
+// SAFETY: `ExtensionHost::schedule` only schedules tasks that are `Send`
+unsafe impl Send for ScheduledTask {}
+
+impl ScheduledTask {
+    fn run(self) {
+        // SAFETY: `run` takes ownership of the task it was scheduled with
+        unsafe { (self.1)(self.0) }
+    }
+}
+
+unsafe extern "C" fn host_schedule(host: *const c_void, task: *mut c_void, run: unsafe extern "C" fn(*mut c_void)) {
+    // SAFETY: Only called by `ExtensionHost` with itself
+    let host = unsafe { &*(host as *const ExtensionHost) };
+    let task = ScheduledTask(task, run);
+
+    host.runtime.spawn_blocking(move || task.run());
+}
+
+unsafe extern "C" fn host_log(level: usize, target: *const u8, target_len: usize, message: *const u8, message_len: usize) {
+    let level = match level {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    };
+
+    // SAFETY: Only called by `HostLogger` with the target and message of a record
+    let (target, message) = unsafe { (slice(target, target_len), slice(message, message_len)) };
+    let target = String::from_utf8_lossy(target);
+
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .target(&target)
+            .args(format_args!("{}", String::from_utf8_lossy(message)))
+            .build(),
+    );
+}
+
+/// Arguments of `HostVTable::request`
+#[derive(Serialize, Deserialize)]
+struct HostRequest {
+    extension: String,
+    method: String,
+    params: Value,
+    timeout_ms: u64,
+}
+
+/// Call of the vtable the host runs, the extension waits until it is completed
+struct PendingCall {
+    context: *mut c_void,
+    complete: Option<Completion>,
+}
+
+// SAFETY: The context is the sender of `start_call`, which is `Send`
+unsafe impl Send for PendingCall {}
+
+impl PendingCall {
+    fn new(context: *mut c_void, complete: Completion) -> Self {
+        Self {
+            context,
+            complete: Some(complete),
+        }
+    }
+
+    fn complete(mut self, outcome: OmniverlayResult<Vec<u8>>) {
+        self.finish(outcome);
+    }
+
+    fn finish(&mut self, outcome: OmniverlayResult<Vec<u8>>) {
+        let Some(complete) = self.complete.take() else {
+            return;
+        };
+
+        let (ok, bytes) = match outcome {
+            Ok(bytes) => (true, bytes),
+            Err(e) => (false, e.to_string().into_bytes()),
+        };
+
+        // SAFETY: The context goes back once to the completion it came with
+        unsafe { complete(self.context, ok, bytes.as_ptr(), bytes.len()) }
+    }
+
+    /// Run `future` on the runtime of `host` and complete the call with its outcome
+    fn run<F>(self, host: &ExtensionHost, future: F)
+    where
+        F: Future<Output = OmniverlayResult<Vec<u8>>> + Send + 'static,
+    {
+        host.runtime.spawn(async move { self.complete(future.await) });
+    }
+}
+
+impl Drop for PendingCall {
+    // A call the runtime dropped still completes, the extension would wait for it forever otherwise
+    fn drop(&mut self) {
+        self.finish(Err(OmniverlayError::ExtensionRuntime("the host could not run the call".to_string())));
+    }
+}
+
+/// # Safety
+///
+/// `path` must point to `len` readable bytes
+unsafe fn path_of(path: *const u8, len: usize) -> OmniverlayResult<PathBuf> {
+    // SAFETY: Guaranteed by the caller
+    let path = unsafe { slice(path, len) };
+
+    std::str::from_utf8(path)
+        .map(PathBuf::from)
+        .map_err(|_| OmniverlayError::IoError(std::io::Error::other("invalid file path: not UTF-8")))
+}
+
+unsafe extern "C" fn host_request(host: *const c_void, request: *const u8, len: usize, context: *mut c_void, complete: Completion) {
+    // SAFETY: Only called by `ExtensionHost` with itself and its serialized request
+    let (host, request) = unsafe { (&*(host as *const ExtensionHost), slice(request, len)) };
+    let call = PendingCall::new(context, complete);
+
+    match serde_json::from_slice(request) {
+        Ok(request) => call.run(host, answer_request(host.bus.clone(), host.extension_name.clone(), request)),
+        Err(e) => call.complete(Err(e.into())),
+    }
+}
+
+async fn answer_request(bus: MessageBus, from: String, request: HostRequest) -> OmniverlayResult<Vec<u8>> {
+    let timeout = Duration::from_millis(request.timeout_ms);
+    let response = bus
+        .request(&from, &request.extension, &request.method, request.params, timeout)
+        .await?;
+
+    Ok(serde_json::to_vec(&response)?)
+}
+
+unsafe extern "C" fn host_read_file(host: *const c_void, path: *const u8, len: usize, context: *mut c_void, complete: Completion) {
+    // SAFETY: Only called by `ExtensionHost` with itself and the path to read
+    let (host, path) = unsafe { (&*(host as *const ExtensionHost), path_of(path, len)) };
+
+    PendingCall::new(context, complete).run(host, read_file_at(host.permissions.clone(), path));
+}
+
+// The permissions are checked again on the host, whatever the extension library checked itself
+async fn read_file_at(permissions: Permissions, path: OmniverlayResult<PathBuf>) -> OmniverlayResult<Vec<u8>> {
+    let path = permissions.require_path(&path?, false)?;
+
+    Ok(tokio::fs::read(path).await?)
+}
+
+unsafe extern "C" fn host_read_dir(host: *const c_void, path: *const u8, len: usize, context: *mut c_void, complete: Completion) {
+    // SAFETY: Only called by `ExtensionHost` with itself and the path to list
+    let (host, path) = unsafe { (&*(host as *const ExtensionHost), path_of(path, len)) };
+
+    PendingCall::new(context, complete).run(host, read_dir_at(host.permissions.clone(), path));
+}
+
+async fn read_dir_at(permissions: Permissions, path: OmniverlayResult<PathBuf>) -> OmniverlayResult<Vec<u8>> {
+    let path = permissions.require_path(&path?, false)?;
+    let mut entries = tokio::fs::read_dir(path).await?;
+    let mut paths = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        paths.push(entry.path());
+    }
+
+    Ok(serde_json::to_vec(&paths)?)
+}
+
+unsafe extern "C" fn host_write_file(
+    host: *const c_void,
+    path: *const u8,
+    path_len: usize,
+    contents: *const u8,
+    contents_len: usize,
+    context: *mut c_void,
+    complete: Completion,
+) {
+    // SAFETY: Only called by `ExtensionHost` with itself, the path to write and the contents, which
+    // are copied before returning
+    let (host, path, contents) = unsafe {
+        (
+            &*(host as *const ExtensionHost),
+            path_of(path, path_len),
+            slice(contents, contents_len).to_vec(),
+        )
+    };
+
+    PendingCall::new(context, complete).run(host, write_file_at(host.permissions.clone(), path, contents));
+}
+
+async fn write_file_at(
+    permissions: Permissions,
+    path: OmniverlayResult<PathBuf>,
+    contents: Vec<u8>,
+) -> OmniverlayResult<Vec<u8>> {
+    let path = permissions.require_path(&path?, true)?;
+    tokio::fs::write(path, contents).await?;
+
+    Ok(Vec::new())
+}
+
+type CallOutcome = Result<Vec<u8>, String>;
+
+unsafe extern "C" fn complete_call(context: *mut c_void, ok: bool, bytes: *const u8, len: usize) {
+    // SAFETY: Boxed by `start_call`, the host completes a call only once
+    let sender = unsafe { Box::from_raw(context as *mut oneshot::Sender<CallOutcome>) };
+    // SAFETY: The host passes `len` readable bytes
+    let bytes = unsafe { slice(bytes, len) };
+
+    let outcome = match ok {
+        true => Ok(bytes.to_vec()),
+        false => Err(String::from_utf8_lossy(bytes).into_owned()),
+    };
+
+    // The extension may have stopped waiting
+    let _ = sender.send(outcome);
+}
+
+/// Start a call of the vtable, `start` gets the context and the completion to pass on
+fn start_call(start: impl FnOnce(*mut c_void, Completion)) -> oneshot::Receiver<CallOutcome> {
+    let (sender, receiver) = oneshot::channel();
+
+    start(Box::into_raw(Box::new(sender)) as *mut c_void, complete_call);
+
+    receiver
+}
+
+/// Outcome of a call started with `start_call`
+async fn finish_call(receiver: oneshot::Receiver<CallOutcome>) -> OmniverlayResult<Vec<u8>> {
+    match receiver.await {
+        Ok(Ok(bytes)) => Ok(bytes),
+        // Already the whole message of the error
+        Ok(Err(message)) => Err(OmniverlayError::Other(anyhow::anyhow!("{}", message))),
+        Err(_) => Err(OmniverlayError::ExtensionRuntime("the host dropped the call".to_string())),
+    }
+}
+
+fn utf8_path(path: &Path) -> OmniverlayResult<&str> {
+    path.to_str()
+        .ok_or_else(|| OmniverlayError::IoError(std::io::Error::other(format!("invalid file path: {}", path.display()))))
+}
+
+/// Logger of an extension library, forwarding to the logger of the host
+pub struct HostLogger(pub &'static HostVTable);
+
+impl log::Log for HostLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let target = record.target();
+        let message = record.args().to_string();
+
+        // SAFETY: Both point to strings which outlive the call
+        unsafe { (self.0.log)(record.level() as usize, target.as_ptr(), target.len(), message.as_ptr(), message.len()) }
+    }
+
+    fn flush(&self) {}
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+/// Grants of a host made by `ExtensionHost::standalone`, kept away from the extension since it
+/// plays the part of the user's profile
+pub struct StandaloneGrants {
+    declared: Vec<Capability>,
+    permissions: Permissions,
+}
+
+impl StandaloneGrants {
+    /// Replace the grants, only what was declared can be granted
+    pub fn grant(&self, granted: &[Capability]) {
+        self.permissions.grant(&self.declared, granted);
+    }
+}
+
+/// Handle given to an extension through `Extension::set_host`, its way to talk to the host.
+///
+/// Publishing, reporting failures, scheduling, requests and file access go through the
+/// `HostVTable`, so they reach the host from extension libraries too.
+#[derive(Clone)]
+pub struct ExtensionHost {
+    extension_name: String,
+    channel: DataChannel,
+    bus: MessageBus,
+    storage: ExtensionStorage,
+    permissions: Permissions,
+    usage: ExtensionUsage,
+    timed_out: TimedOutCalls,
+    // Empty for a standalone host
+    registration: Weak<Registration>,
+    runtime: RuntimeHandle,
+    vtable: &'static HostVTable,
+}
+
+impl ExtensionHost {
+    pub(super) fn new(
+        extension_name: String,
+        channel: DataChannel,
+        bus: MessageBus,
+        storage: ExtensionStorage,
+        registered: &RegisteredExtension,
+    ) -> Self {
+        Self {
+            extension_name,
+            channel,
+            bus,
+            storage,
+            permissions: registered.permissions.clone(),
+            usage: registered.usage.clone(),
+            timed_out: registered.timed_out.clone(),
+            registration: Arc::downgrade(&registered.0),
+            runtime: RuntimeHandle::current(),
+            vtable: &HOST_VTABLE,
+        }
+    }
+
+    /// Host of an extension running without an `ExtensionManager`, with its own data channel and
+    /// message bus and no budget. Everything `declared` is granted, the returned `StandaloneGrants`
+    /// stays with the caller to change that. Used by `omniverlay_test` to run an extension in a test.
+    pub fn standalone(
+        info: Arc<Mutex<ExtensionInfo>>,
+        extension_name: &str,
+        storage: ExtensionStorage,
+        declared: &[Capability],
+    ) -> (Self, StandaloneGrants) {
+        let usage = ExtensionUsage::new(extension_name, None);
+
+        let bus = MessageBus::new();
+        bus.add_extension(extension_name, info, usage.clone());
+
+        let grants = StandaloneGrants {
+            declared: declared.to_vec(),
+            permissions: Permissions::new(extension_name.to_string()),
+        };
+        grants.grant(declared);
+
+        let host = Self {
+            extension_name: extension_name.to_string(),
+            channel: DataChannel::new(),
+            bus,
+            storage,
+            permissions: grants.permissions.clone(),
+            usage,
+            timed_out: TimedOutCalls::default(),
+            registration: Weak::new(),
+            runtime: RuntimeHandle::current(),
+            vtable: &HOST_VTABLE,
+        };
+
+        (host, grants)
+    }
+
+    pub fn extension_name(&self) -> &str {
+        &self.extension_name
+    }
+
+    /// Send a payload to the overlay, where it arrives as the event named by `channel::data_event_name`
+    pub fn publish<T: Serialize>(&self, payload: &T) -> OmniverlayResult<()> {
+        self.publish_value(serde_json::to_value(payload)?);
+
+        Ok(())
+    }
+
+    pub fn publish_value(&self, payload: Value) {
+        let json = payload.to_string();
+
+        // SAFETY: The vtable is the host's, it gets this host and a string which outlives the call
+        let published = unsafe { (self.vtable.publish)(self as *const Self as *const c_void, json.as_ptr(), json.len()) };
+
+        if !published {
+            warn!("Extension {} published a payload the host could not read", self.extension_name);
+        }
+    }
+
+    /// Report that the extension failed while running, outside of its hooks. The extension is then
+    /// handled by the supervisor like after a failed tick, see `supervisor`.
+    pub fn report_failure(&self, error: OmniverlayError) {
+        let message = error.to_string();
+
+        // SAFETY: The vtable is the host's, it gets this host and a string which outlives the call
+        unsafe { (self.vtable.report_failure)(self as *const Self as *const c_void, message.as_ptr(), message.len()) }
+    }
+
+    /// Run `task` on a blocking thread of the host. An error or a panic ending it is reported with
+    /// `report_failure`. Extension libraries cannot use the tokio runtime of the host themselves,
+    /// this is their way to work in the background.
+    pub fn schedule<F>(&self, task: F)
+    where
+        F: FnOnce() -> OmniverlayResult<()> + Send + 'static,
+    {
+        unsafe extern "C" fn run<F>(task: *mut c_void)
+        where
+            F: FnOnce() -> OmniverlayResult<()> + Send + 'static,
+        {
+            // SAFETY: Boxed below, the host runs it only once
+            let (host, task) = *unsafe { Box::from_raw(task as *mut (ExtensionHost, F)) };
+
+            let error = match std::panic::catch_unwind(AssertUnwindSafe(task)) {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e,
+                Err(payload) => OmniverlayError::ExtensionPanicked(panic_message(&*payload)),
+            };
+
+            host.report_failure(error);
+        }
+
+        let task = Box::into_raw(Box::new((self.clone(), task)));
+
+        // SAFETY: The vtable is the host's, `run` takes the task back
+        unsafe { (self.vtable.schedule)(self as *const Self as *const c_void, task as *mut c_void, run::<F>) }
+    }
+
+    /// Persistent key-value storage of this extension
+    pub fn storage(&self) -> &ExtensionStorage {
+        &self.storage
+    }
+
+    /// Receive every payload another extension publishes from now on
+    pub fn subscribe(&self, extension_name: &str) -> DataSubscription {
+        self.channel.subscribe(extension_name)
+    }
+
+    /// Last payload published by another extension
+    pub fn last_data(&self, extension_name: &str) -> Option<Value> {
+        self.channel.last(extension_name)
+    }
+
+    /// Answer `method` requests from other extensions, see `messaging`
+    pub fn handle<H: RequestHandler + 'static>(&self, method: &str, handler: H) {
+        self.bus.register_handler(&self.extension_name, method, Arc::new(handler));
+    }
+
+    pub fn remove_handler(&self, method: &str) {
+        self.bus.remove_handler(&self.extension_name, method);
+    }
+
+    /// Call `method` on another extension, which must be running
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        extension_name: &str,
+        method: &str,
+        params: &P,
+    ) -> OmniverlayResult<R> {
+        self.request_with_timeout(extension_name, method, params, REQUEST_TIMEOUT).await
+    }
+
+    pub async fn request_with_timeout<P: Serialize, R: DeserializeOwned>(
+        &self,
+        extension_name: &str,
+        method: &str,
+        params: &P,
+        timeout: Duration,
+    ) -> OmniverlayResult<R> {
+        let request = serde_json::to_vec(&HostRequest {
+            extension: extension_name.to_string(),
+            method: method.to_string(),
+            params: serde_json::to_value(params)?,
+            timeout_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+        })?;
+
+        let receiver = start_call(|context, complete| {
+            // SAFETY: The vtable is the host's, it gets this host and a request which outlives the call
+            unsafe { (self.vtable.request)(self as *const Self as *const c_void, request.as_ptr(), request.len(), context, complete) }
+        });
+
+        Ok(serde_json::from_slice(&finish_call(receiver).await?)?)
+    }
+
+    /// Fails with `PermissionDenied` unless the user granted `capability`, native extensions must
+    /// call it before using a resource the host does not give access to
+    pub fn require(&self, capability: &Capability) -> OmniverlayResult<()> {
+        self.permissions.require(capability)
+    }
+
+    pub fn is_granted(&self, capability: &Capability) -> bool {
+        self.permissions.is_granted(capability)
+    }
+
+    /// Fails with `PermissionDenied` unless the user granted access to where `path` leads on disk,
+    /// returns that place. Relative paths are refused. Native extensions opening files themselves
+    /// must open the returned path.
+    pub fn require_path(&self, path: &Path, write: bool) -> OmniverlayResult<PathBuf> {
+        self.permissions.require_path(path, write)
+    }
+
+    /// Whether a call into this extension timed out and did not return yet. A hook blocking its
+    /// thread should check it and return, the extension is not called again until it does.
+    pub fn is_cancelled(&self) -> bool {
+        self.timed_out.any()
+    }
+
+    /// What this extension cost so far, see `usage`
+    pub fn usage(&self) -> &ExtensionUsage {
+        &self.usage
+    }
+
+    pub async fn read_file(&self, path: &Path) -> OmniverlayResult<Vec<u8>> {
+        let path = self.require_path(path, false)?;
+        let path = utf8_path(&path)?;
+
+        let receiver = start_call(|context, complete| {
+            // SAFETY: The vtable is the host's, it gets this host and a path which outlives the call
+            unsafe { (self.vtable.read_file)(self as *const Self as *const c_void, path.as_ptr(), path.len(), context, complete) }
+        });
+
+        finish_call(receiver).await
+    }
+
+    pub async fn read_dir(&self, path: &Path) -> OmniverlayResult<Vec<PathBuf>> {
+        let path = self.require_path(path, false)?;
+        let path = utf8_path(&path)?;
+
+        let receiver = start_call(|context, complete| {
+            // SAFETY: The vtable is the host's, it gets this host and a path which outlives the call
+            unsafe { (self.vtable.read_dir)(self as *const Self as *const c_void, path.as_ptr(), path.len(), context, complete) }
+        });
+
+        Ok(serde_json::from_slice(&finish_call(receiver).await?)?)
+    }
+
+    pub async fn write_file(&self, path: &Path, contents: impl AsRef<[u8]>) -> OmniverlayResult<()> {
+        let path = self.require_path(path, true)?;
+        let path = utf8_path(&path)?;
+        let contents = contents.as_ref();
+
+        let receiver = start_call(|context, complete| {
+            // SAFETY: The vtable is the host's, it gets this host, a path and contents which outlive
+            // the call
+            unsafe {
+                (self.vtable.write_file)(
+                    self as *const Self as *const c_void,
+                    path.as_ptr(),
+                    path.len(),
+                    contents.as_ptr(),
+                    contents.len(),
+                    context,
+                    complete,
+                )
+            }
+        });
+
+        finish_call(receiver).await?;
+
+        Ok(())
+    }
+
+    /// Start `command` once the user granted `SpawnProcess`. It is a `std` command, which needs no
+    /// runtime, extension libraries cannot use the one of the host for a `tokio` command. Waiting on
+    /// the child belongs in `schedule`.
+    pub fn spawn_process(&self, command: &mut Command) -> OmniverlayResult<Child> {
+        self.require(&Capability::SpawnProcess)?;
+
+        Ok(command.spawn()?)
+    }
+}
@@ -0,0 +1,213 @@
+//! Installs `.omnipkg` packages, see `package`.
+//!
+//! A package is unpacked into `<extensions dir>/<manifest id>/`, where `ExtensionManager::load_extensions_from_dir`
+//! finds it. It is first unpacked into a hidden staging directory then moved in place, so a failed
+//! install leaves the installed version untouched. `Omniverlay::install_package` also swaps the
+//! extensions of a running app.
+
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    utils::fs::get_extensions_dir,
+};
+
+use super::{
+    manifest::{ExtensionManifest, MANIFEST_FILE_NAME},
+    package::ExtensionPackage,
+};
+
+/// What `PackageInstaller::install` did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InstallOutcome {
+    Installed,
+    Upgraded { from: Version },
+    Downgraded { from: Version },
+    Reinstalled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPackage {
+    pub manifest: ExtensionManifest,
+    pub dir: PathBuf,
+}
+
+pub struct PackageInstaller {
+    extensions_dir: PathBuf,
+}
+
+impl PackageInstaller {
+    /// Installer of `~/.omniverlay/extensions`
+    pub fn new() -> OmniverlayResult<Self> {
+        Ok(Self::in_dir(get_extensions_dir()?))
+    }
+
+    pub fn in_dir(extensions_dir: PathBuf) -> Self {
+        Self { extensions_dir }
+    }
+
+    /// Directory a package with this id is installed into
+    pub fn dir_of(&self, id: &str) -> PathBuf {
+        self.extensions_dir.join(id)
+    }
+
+    pub fn installed(&self, id: &str) -> OmniverlayResult<Option<InstalledPackage>> {
+        let dir = self.dir_of(id);
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+
+        if !manifest_path.is_file() {
+            return Ok(None);
+        }
+
+        Ok(Some(InstalledPackage {
+            manifest: ExtensionManifest::from_file(&manifest_path)?,
+            dir,
+        }))
+    }
+
+    /// Extension directories of the extensions directory, sorted by id. Directories with a broken
+    /// manifest are skipped.
+    pub fn list_installed(&self) -> OmniverlayResult<Vec<InstalledPackage>> {
+        let mut installed = Vec::new();
+
+        if !self.extensions_dir.is_dir() {
+            return Ok(installed);
+        }
+
+        for entry in std::fs::read_dir(&self.extensions_dir)? {
+            let dir = entry?.path();
+            let manifest_path = dir.join(MANIFEST_FILE_NAME);
+
+            if is_hidden(&dir) || !manifest_path.is_file() {
+                continue;
+            }
+
+            match ExtensionManifest::from_file(&manifest_path) {
+                Ok(manifest) => installed.push(InstalledPackage { manifest, dir }),
+                Err(e) => warn!("Skipping installed extension {}: {}", dir.display(), e),
+            }
+        }
+
+        installed.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+
+        Ok(installed)
+    }
+
+    /// What installing `package` would do, fails if it is a downgrade and `allow_downgrade` is not set
+    pub fn plan(&self, package: &ExtensionPackage, allow_downgrade: bool) -> OmniverlayResult<InstallOutcome> {
+        package.manifest.check_host_version()?;
+
+        let Some(installed) = self.installed(&package.manifest.id)? else {
+            return Ok(InstallOutcome::Installed);
+        };
+
+        let from = installed.manifest.version;
+        let to = &package.manifest.version;
+
+        match from.cmp(to) {
+            std::cmp::Ordering::Less => Ok(InstallOutcome::Upgraded { from }),
+            std::cmp::Ordering::Equal => Ok(InstallOutcome::Reinstalled),
+            std::cmp::Ordering::Greater if allow_downgrade => Ok(InstallOutcome::Downgraded { from }),
+            std::cmp::Ordering::Greater => Err(OmniverlayError::DowngradeRefused(
+                package.manifest.id.clone(),
+                from.to_string(),
+                to.to_string(),
+            )),
+        }
+    }
+
+    /// Unpack `package` into the extensions directory, replacing the installed version. The
+    /// extensions of the replaced version must not be running, see `Omniverlay::install_package`.
+    /// The replaced version is kept until `remove_previous` or `restore_previous` is called.
+    pub fn install(
+        &self,
+        package: &ExtensionPackage,
+        allow_downgrade: bool,
+    ) -> OmniverlayResult<(InstalledPackage, InstallOutcome)> {
+        let outcome = self.plan(package, allow_downgrade)?;
+
+        let id = &package.manifest.id;
+        let dir = self.dir_of(id);
+        let staging = self.extensions_dir.join(format!(".{id}.installing"));
+        let previous = self.previous_dir_of(id);
+
+        for leftover in [&staging, &previous] {
+            if leftover.exists() {
+                std::fs::remove_dir_all(leftover)?;
+            }
+        }
+
+        if let Err(e) = package.unpack(&staging) {
+            let _ = std::fs::remove_dir_all(&staging);
+
+            return Err(e);
+        }
+
+        if dir.exists() {
+            std::fs::rename(&dir, &previous)?;
+        }
+
+        if let Err(e) = std::fs::rename(&staging, &dir) {
+            if previous.exists() {
+                std::fs::rename(&previous, &dir)?;
+            }
+
+            return Err(e.into());
+        }
+
+        info!("Installed extension package {} {} ({:?})", id, package.manifest.version, outcome);
+
+        Ok((
+            InstalledPackage {
+                manifest: package.manifest.clone(),
+                dir,
+            },
+            outcome,
+        ))
+    }
+
+    /// Delete the version replaced by `install` once the new one is loaded
+    pub fn remove_previous(&self, id: &str) {
+        let previous = self.previous_dir_of(id);
+
+        if previous.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&previous) {
+                warn!("Failed to remove the previous version of {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Put back the version replaced by `install`, or remove the extension if there was none
+    pub fn restore_previous(&self, id: &str) -> OmniverlayResult<()> {
+        let dir = self.dir_of(id);
+        let previous = self.previous_dir_of(id);
+
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+
+        if previous.exists() {
+            std::fs::rename(&previous, &dir)?;
+        }
+
+        info!("Restored the previous version of extension package {}", id);
+
+        Ok(())
+    }
+
+    fn previous_dir_of(&self, id: &str) -> PathBuf {
+        self.extensions_dir.join(format!(".{id}.previous"))
+    }
+}
+
+/// Staging directories of the installer are hidden, and skipped when loading extensions
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
@@ -0,0 +1,95 @@
+//! Extensions can run several times with their own state, config and layout, for example two
+//! clocks showing different time zones.
+//!
+//! The first instance of an extension is named after the extension itself, the others are named
+//! `<extension>#<n>`, so extension names cannot contain `#`. The instance name is the key of the
+//! extension everywhere else: in the `ExtensionManager`, in profiles, in layouts and for the data
+//! and storage of the extension.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::Extension;
+
+pub const INSTANCE_SEPARATOR: char = '#';
+
+/// Most instances `ExtensionManager::create_extension_instance` creates of an extension
+pub const MAX_INSTANCES: u32 = 256;
+
+/// Creates the instances of an extension
+#[async_trait]
+pub trait ExtensionFactory: Send + Sync {
+    async fn create(&self) -> OmniverlayResult<Arc<Mutex<dyn Extension>>>;
+}
+
+#[async_trait]
+impl<F, T> ExtensionFactory for F
+where
+    F: Fn() -> OmniverlayResult<T> + Send + Sync,
+    T: Extension + 'static,
+{
+    async fn create(&self) -> OmniverlayResult<Arc<Mutex<dyn Extension>>> {
+        Ok(Arc::new(Mutex::new(self()?)))
+    }
+}
+
+/// Factory of an extension registered as an already built value, it only has one instance
+pub struct SingleInstance(std::sync::Mutex<Option<Arc<Mutex<dyn Extension>>>>);
+
+impl SingleInstance {
+    pub fn new<T: Extension + 'static>(extension: T) -> Self {
+        Self(std::sync::Mutex::new(Some(Arc::new(Mutex::new(extension)))))
+    }
+}
+
+#[async_trait]
+impl ExtensionFactory for SingleInstance {
+    async fn create(&self) -> OmniverlayResult<Arc<Mutex<dyn Extension>>> {
+        self.0
+            .lock()
+            .map_err(|e| OmniverlayError::LockError(e.to_string()))?
+            .take()
+            .ok_or_else(|| OmniverlayError::ExtensionInstance("extension does not support multiple instances".to_string()))
+    }
+}
+
+/// Name of instance `number` of an extension, the first instance is number 1
+pub fn instance_name(extension_type: &str, number: u32) -> String {
+    match number {
+        1 => extension_type.to_string(),
+        _ => format!("{extension_type}{INSTANCE_SEPARATOR}{number}"),
+    }
+}
+
+/// Fails for empty names, names with control characters and names that could be mistaken for the
+/// name of another instance, like `clock#2`
+pub fn check_extension_type(extension_type: &str) -> OmniverlayResult<()> {
+    if extension_type.trim().is_empty() {
+        return Err(OmniverlayError::ExtensionLoadFailed("extension name is empty".to_string()));
+    }
+
+    if extension_type.chars().any(char::is_control) {
+        return Err(OmniverlayError::ExtensionLoadFailed(format!(
+            "extension name {extension_type:?} contains control characters"
+        )));
+    }
+
+    if extension_type.contains(INSTANCE_SEPARATOR) {
+        return Err(OmniverlayError::ExtensionLoadFailed(format!(
+            "extension name {extension_type} contains {INSTANCE_SEPARATOR}, which separates instance numbers"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Split an instance name into its extension and its number
+pub fn parse_instance_name(name: &str) -> (&str, u32) {
+    name.rsplit_once(INSTANCE_SEPARATOR)
+        .and_then(|(extension_type, number)| Some((extension_type, number.parse().ok().filter(|n| *n > 1)?)))
+        .unwrap_or((name, 1))
+}
@@ -0,0 +1,476 @@
+use std::{
+    any::TypeId,
+    ffi::{c_char, CStr},
+    fs::{File, OpenOptions},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use libloading::{Library, Symbol};
+use log::debug;
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    utils::fs::get_libraries_dir,
+};
+
+use super::{
+    host::{HostLogger, HostVTable, HOST_VTABLE},
+    instances::{ExtensionFactory, SingleInstance},
+    Extension,
+};
+
+/// Bumped whenever the `Extension` trait or the declaration layout changes in an incompatible way
+pub const EXTENSION_ABI_VERSION: u32 = 5;
+pub const HOST_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const RUSTC_VERSION: &str = env!("OMNIVERLAY_RUSTC_VERSION");
+
+pub const DECLARATION_SYMBOL: &[u8] = b"OMNIVERLAY_EXTENSION_DECLARATION\0";
+
+#[doc(hidden)]
+pub const HOST_VERSION_C: &CStr = c_str(concat!(env!("CARGO_PKG_VERSION"), "\0"));
+#[doc(hidden)]
+pub const RUSTC_VERSION_C: &CStr = c_str(concat!(env!("OMNIVERLAY_RUSTC_VERSION"), "\0"));
+
+const fn c_str(bytes: &'static str) -> &'static CStr {
+    match CStr::from_bytes_with_nul(bytes.as_bytes()) {
+        Ok(string) => string,
+        Err(_) => panic!("version contains a nul byte"),
+    }
+}
+
+/// Crates whose types cross the library boundary: the extension and its host, results and
+/// payloads, manifest versions and the runtime handles kept by the `ExtensionHost`
+pub const BOUNDARY_CRATES: [&str; 4] = ["anyhow", "serde_json", "semver", "tokio"];
+
+/// A type of each of `BOUNDARY_CRATES`. Cargo gives every build of a crate its own metadata hash,
+/// made of its version, source and enabled features among others, and the `TypeId` of a type
+/// changes with it. Equal ids mean the library was built with the same crates as the host, with
+/// the same layouts: `preserve_order` of `serde_json` alone changes the layout of a `Value`.
+fn boundary_type_ids() -> [TypeId; BOUNDARY_CRATES.len()] {
+    [
+        TypeId::of::<anyhow::Error>(),
+        TypeId::of::<serde_json::Value>(),
+        TypeId::of::<semver::Version>(),
+        TypeId::of::<tokio::runtime::Handle>(),
+    ]
+}
+
+/// Write the fingerprints of `BOUNDARY_CRATES` to `fingerprints`, called through
+/// `ExtensionDeclaration::dependencies` on the copy of the core of a library
+///
+/// # Safety
+///
+/// `fingerprints` must point to `BOUNDARY_CRATES.len()` writable values.
+#[doc(hidden)]
+pub unsafe extern "C" fn dependency_fingerprints(fingerprints: *mut u64) {
+    for (index, type_id) in boundary_type_ids().iter().enumerate() {
+        // Same hasher in both copies, the compiler is checked first
+        let mut hasher = DefaultHasher::new();
+        type_id.hash(&mut hasher);
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { fingerprints.add(index).write(hasher.finish()) };
+    }
+}
+
+/// Exported by extension libraries, see `export_extension!`.
+///
+/// Only C types until the versions are checked: a library built by another compiler may lay out
+/// Rust types differently. `abi_version` and `rustc_version` must stay the first fields, the others
+/// are only read once both match. Rust types of the core and of `BOUNDARY_CRATES` still cross the
+/// boundary afterwards, `dependencies` checks they were built the same way.
+#[repr(C)]
+pub struct ExtensionDeclaration {
+    pub abi_version: u32,
+    pub rustc_version: *const c_char,
+    pub host_version: *const c_char,
+    /// Fingerprints of the `BOUNDARY_CRATES` the library was built with, see `dependency_fingerprints`
+    pub dependencies: unsafe extern "C" fn(fingerprints: *mut u64),
+    pub register: unsafe extern "C" fn(*mut ExtensionRegistrar),
+}
+
+// SAFETY: The versions point to static strings, which are never written
+unsafe impl Sync for ExtensionDeclaration {}
+
+pub struct ExtensionRegistrar {
+    factories: Vec<Arc<dyn ExtensionFactory>>,
+    vtable: &'static HostVTable,
+}
+
+impl ExtensionRegistrar {
+    pub fn new() -> Self {
+        Self {
+            factories: Vec::new(),
+            vtable: &HOST_VTABLE,
+        }
+    }
+
+    /// Functions of the host, see `HostVTable`
+    pub fn host_vtable(&self) -> &'static HostVTable {
+        self.vtable
+    }
+
+    /// Register an extension that only has one instance
+    pub fn register<T: 'static + Extension>(&mut self, extension: T) {
+        self.factories.push(Arc::new(SingleInstance::new(extension)));
+    }
+
+    /// Register the constructor of an extension, which is called for every instance
+    pub fn register_type<T, F>(&mut self, constructor: F)
+    where
+        T: 'static + Extension,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.factories.push(Arc::new(move || Ok(constructor())));
+    }
+
+    pub fn into_factories(self) -> Vec<Arc<dyn ExtensionFactory>> {
+        self.factories
+    }
+}
+
+/// Export an extension constructor from a `cdylib` crate so the host can load it at runtime
+///
+/// ```ignore
+/// omniverlay_core::export_extension!(PerformanceExtension::new);
+/// ```
+///
+/// The library runs its own copy of the core and of its dependencies. Its `log` records are sent
+/// to the host, and it reaches the host through its `ExtensionHost`, whose requests and file access
+/// run on the host. It must not use the tokio runtime itself: spawning, timers and `tokio::fs` look
+/// for a runtime in the library's copy of tokio, which has none. `ExtensionHost::schedule` runs
+/// blocking work on the host instead, and hooks may block their thread, they run on a thread of
+/// their own. It must be built with the versions and features of `BOUNDARY_CRATES` the host uses.
+#[macro_export]
+macro_rules! export_extension {
+    ($constructor:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static OMNIVERLAY_EXTENSION_DECLARATION: $crate::extensions::loader::ExtensionDeclaration =
+            $crate::extensions::loader::ExtensionDeclaration {
+                abi_version: $crate::extensions::loader::EXTENSION_ABI_VERSION,
+                rustc_version: $crate::extensions::loader::RUSTC_VERSION_C.as_ptr(),
+                host_version: $crate::extensions::loader::HOST_VERSION_C.as_ptr(),
+                dependencies: $crate::extensions::loader::dependency_fingerprints,
+                register: __omniverlay_register_extension,
+            };
+
+        #[doc(hidden)]
+        unsafe extern "C" fn __omniverlay_register_extension(
+            registrar: *mut $crate::extensions::loader::ExtensionRegistrar,
+        ) {
+            // SAFETY: The host passes a registrar it owns for the duration of the call
+            let registrar = unsafe { &mut *registrar };
+
+            $crate::extensions::loader::forward_logs(registrar.host_vtable());
+            registrar.register_type($constructor);
+        }
+    };
+}
+
+/// Send the `log` records of an extension library to the host, called by `export_extension!`
+#[doc(hidden)]
+pub fn forward_logs(vtable: &'static HostVTable) {
+    // Registered from the host itself, which would then forward to itself
+    if std::ptr::eq(vtable, &HOST_VTABLE) {
+        return;
+    }
+
+    // Already set when the library registers again
+    if log::set_boxed_logger(Box::new(HostLogger(vtable))).is_ok() {
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+}
+
+/// Fails unless a library declared with `declaration` was built for this host. The compiler is
+/// checked first, nothing but the C fields can be trusted before, then the core and the crates
+/// whose types cross the boundary.
+///
+/// # Safety
+///
+/// The versions of `declaration` must point to nul-terminated strings.
+unsafe fn check_declaration(declaration: &ExtensionDeclaration) -> Result<(), String> {
+    if declaration.abi_version != EXTENSION_ABI_VERSION {
+        return Err(format!(
+            "extension ABI version {} does not match host ABI version {}",
+            declaration.abi_version, EXTENSION_ABI_VERSION
+        ));
+    }
+
+    let version = |version: *const c_char| match version.is_null() {
+        true => "an unknown version".into(),
+        // SAFETY: Guaranteed by the caller
+        false => unsafe { CStr::from_ptr(version) }.to_string_lossy(),
+    };
+
+    let rustc_version = version(declaration.rustc_version);
+
+    if rustc_version != RUSTC_VERSION {
+        return Err(format!("built with {rustc_version} but host was built with {RUSTC_VERSION}"));
+    }
+
+    let host_version = version(declaration.host_version);
+
+    if host_version != HOST_VERSION {
+        return Err(format!("built against omniverlay_core {host_version} but host is {HOST_VERSION}"));
+    }
+
+    let mut host = [0; BOUNDARY_CRATES.len()];
+    let mut library = [0; BOUNDARY_CRATES.len()];
+
+    // SAFETY: Both are `dependency_fingerprints`, of the host and of the library
+    unsafe {
+        dependency_fingerprints(host.as_mut_ptr());
+        (declaration.dependencies)(library.as_mut_ptr());
+    }
+
+    for (index, name) in BOUNDARY_CRATES.iter().enumerate() {
+        if library[index] != host[index] {
+            return Err(format!(
+                "built with another build of {name} than the host, its version and features must match the host's"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Load a compiled extension library and return it with the factories of the extensions it registered.
+///
+/// The returned `Library` must outlive every extension, otherwise their code gets unmapped.
+pub fn load_library(path: &Path) -> OmniverlayResult<(Library, Vec<Arc<dyn ExtensionFactory>>)> {
+    let display = path.display();
+
+    // SAFETY: Loading a library runs its initializers, extensions in the extensions directory are trusted
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{display}: {e}")))?;
+
+    let declaration = unsafe {
+        let symbol: Symbol<*const ExtensionDeclaration> =
+            library.get(DECLARATION_SYMBOL).map_err(|_| {
+                OmniverlayError::ExtensionLoadFailed(format!(
+                    "{display}: missing extension declaration, was it built with `export_extension!` ?"
+                ))
+            })?;
+
+        &**symbol
+    };
+
+    // SAFETY: Libraries declared with `export_extension!` point to static strings
+    unsafe { check_declaration(declaration) }
+        .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{display}: {e}")))?;
+
+    let mut registrar = ExtensionRegistrar::new();
+
+    // SAFETY: The library was built by the same compiler against the same core, checked above
+    unsafe { (declaration.register)(&mut registrar) };
+
+    let factories = registrar.into_factories();
+
+    if factories.is_empty() {
+        return Err(OmniverlayError::ExtensionLoadFailed(format!(
+            "{display}: library did not register any extension"
+        )));
+    }
+
+    Ok((library, factories))
+}
+
+/// Load a copy of the library at `path`. The system hands out the library already loaded from a
+/// path instead of reading it again, so a rebuilt library is only seen through a new path. The
+/// copies go to `~/.omniverlay/libraries`, only readable by the user, and are removed by
+/// `remove_library_copies` on the next start.
+pub fn load_library_copy(path: &Path) -> OmniverlayResult<(Library, Vec<Arc<dyn ExtensionFactory>>)> {
+    static COPIES: AtomicU32 = AtomicU32::new(0);
+
+    let dir = get_libraries_dir()?;
+    create_private_dir(&dir)?;
+
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("extension");
+    let copy = dir.join(format!(
+        "{stem}-{}-{}.{}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::SeqCst),
+        std::env::consts::DLL_EXTENSION
+    ));
+
+    // Never written through a file that was already there
+    let mut source = File::open(path)?;
+    let mut target = OpenOptions::new().write(true).create_new(true).open(&copy)?;
+    std::io::copy(&mut source, &mut target)?;
+    drop(target);
+
+    load_library(&copy)
+}
+
+/// Remove the library copies left by previous runs, a copy still loaded by another running app is
+/// kept
+pub fn remove_library_copies() -> OmniverlayResult<()> {
+    let dir = get_libraries_dir()?;
+
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let own_copies = format!("-{}-", std::process::id());
+
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+
+        if path.file_name().is_some_and(|name| name.to_string_lossy().contains(&own_copies)) {
+            continue;
+        }
+
+        if let Err(e) = std::fs::remove_file(&path) {
+            debug!("Keeping library copy {}: {}", path.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+fn create_private_dir(dir: &Path) -> OmniverlayResult<()> {
+    std::fs::create_dir_all(dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::extensions::ExtensionInfo;
+
+    #[derive(Default)]
+    struct Clock {
+        info: Arc<Mutex<ExtensionInfo>>,
+    }
+
+    #[async_trait]
+    impl Extension for Clock {
+        async fn enable(&mut self) -> OmniverlayResult<()> {
+            Ok(())
+        }
+
+        async fn disable(&mut self) -> OmniverlayResult<()> {
+            Ok(())
+        }
+
+        fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+            Ok(self.info.clone())
+        }
+    }
+
+    crate::export_extension!(Clock::default);
+
+    unsafe extern "C" fn register_nothing(_: *mut ExtensionRegistrar) {}
+
+    fn declaration(abi_version: u32, rustc_version: &CStr, host_version: &CStr) -> ExtensionDeclaration {
+        ExtensionDeclaration {
+            abi_version,
+            rustc_version: rustc_version.as_ptr(),
+            host_version: host_version.as_ptr(),
+            dependencies: dependency_fingerprints,
+            register: register_nothing,
+        }
+    }
+
+    // Fingerprints of a library whose serde_json was built differently
+    unsafe extern "C" fn other_serde_json(fingerprints: *mut u64) {
+        unsafe {
+            dependency_fingerprints(fingerprints);
+            *fingerprints.add(1) ^= 1;
+        }
+    }
+
+    #[test]
+    fn exported_declaration_matches_the_host() {
+        unsafe { check_declaration(&OMNIVERLAY_EXTENSION_DECLARATION) }.unwrap();
+
+        let mut registrar = ExtensionRegistrar::new();
+        unsafe { (OMNIVERLAY_EXTENSION_DECLARATION.register)(&mut registrar) };
+
+        assert_eq!(registrar.into_factories().len(), 1);
+    }
+
+    #[test]
+    fn other_abi_is_refused() {
+        let error = unsafe { check_declaration(&declaration(2, RUSTC_VERSION_C, HOST_VERSION_C)) }.unwrap_err();
+
+        assert!(error.contains("ABI version 2"), "{error}");
+    }
+
+    #[test]
+    fn compiler_is_checked_before_the_host_version() {
+        let error = unsafe { check_declaration(&declaration(EXTENSION_ABI_VERSION, c"rustc 1.0.0", c"0.0.1")) }.unwrap_err();
+
+        assert!(error.contains("built with rustc 1.0.0"), "{error}");
+    }
+
+    #[test]
+    fn other_host_version_is_refused() {
+        let error =
+            unsafe { check_declaration(&declaration(EXTENSION_ABI_VERSION, RUSTC_VERSION_C, c"0.0.1")) }.unwrap_err();
+
+        assert!(error.contains("omniverlay_core 0.0.1"), "{error}");
+    }
+
+    #[test]
+    fn other_dependency_builds_are_refused() {
+        let mut declaration = declaration(EXTENSION_ABI_VERSION, RUSTC_VERSION_C, HOST_VERSION_C);
+        declaration.dependencies = other_serde_json;
+
+        let error = unsafe { check_declaration(&declaration) }.unwrap_err();
+
+        assert!(error.contains("another build of serde_json"), "{error}");
+    }
+
+    #[test]
+    fn fingerprints_differ_between_crates() {
+        let mut fingerprints = [0; BOUNDARY_CRATES.len()];
+        unsafe { dependency_fingerprints(fingerprints.as_mut_ptr()) };
+
+        let mut unique = fingerprints.to_vec();
+        unique.sort();
+        unique.dedup();
+
+        assert_eq!(unique.len(), BOUNDARY_CRATES.len());
+    }
+
+    #[test]
+    fn missing_versions_are_refused() {
+        let mut declaration = declaration(EXTENSION_ABI_VERSION, RUSTC_VERSION_C, HOST_VERSION_C);
+        declaration.rustc_version = std::ptr::null();
+
+        let error = unsafe { check_declaration(&declaration) }.unwrap_err();
+
+        assert!(error.contains("an unknown version"), "{error}");
+    }
+
+    #[test]
+    fn files_that_are_not_libraries_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("clock.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::write(&path, b"not a library").unwrap();
+
+        assert!(matches!(load_library(&path), Err(OmniverlayError::ExtensionLoadFailed(..))));
+        assert!(matches!(
+            load_library(&dir.path().join("missing.so")),
+            Err(OmniverlayError::ExtensionLoadFailed(..))
+        ));
+    }
+}
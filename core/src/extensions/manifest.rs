@@ -0,0 +1,106 @@
+use std::{collections::HashMap, path::Path};
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{capabilities::Capability, loader::HOST_VERSION, supervisor::RestartPolicy, usage::ResourceBudget};
+
+pub const MANIFEST_FILE_NAME: &str = "extension.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionManifest {
+    /// Stable identifier, e.g. `dev.davidutz.performance`
+    pub id: String,
+    /// Name displayed in the studio
+    pub name: String,
+    pub version: Version,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    /// Font Awesome icon name, e.g. `microchip`
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub min_host_version: Option<Version>,
+    /// Ids of the extensions this one needs, with the accepted versions
+    #[serde(default)]
+    pub dependencies: HashMap<String, VersionReq>,
+    /// Defaults to a few restarts with exponential backoff
+    pub restart: Option<RestartPolicy>,
+    /// Defaults to `ResourceBudget::default()`
+    pub budget: Option<ResourceBudget>,
+    /// Used when the extension does not declare its capabilities itself
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// Sample payloads shown by the layout editor, see `Extension::preview_payloads`:
+    ///
+    /// ```toml
+    /// [[preview]]
+    /// cpu_usage = 12.5
+    /// ```
+    #[serde(default)]
+    pub preview: Vec<serde_json::Value>,
+}
+
+impl ExtensionManifest {
+    pub fn from_toml(toml: &str) -> OmniverlayResult<Self> {
+        let manifest: Self =
+            toml::from_str(toml).map_err(|e| OmniverlayError::InvalidManifest(e.to_string()))?;
+
+        manifest.validate()?;
+
+        Ok(manifest)
+    }
+
+    pub fn from_file(path: &Path) -> OmniverlayResult<Self> {
+        let toml = std::fs::read_to_string(path)?;
+
+        Self::from_toml(&toml)
+            .map_err(|e| OmniverlayError::InvalidManifest(format!("{}: {e}", path.display())))
+    }
+
+    fn validate(&self) -> OmniverlayResult<()> {
+        // The id names the install directory, `..` or a hidden name must not be possible
+        let valid_id = self
+            .id
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            && self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '_'));
+
+        if !valid_id {
+            return Err(OmniverlayError::InvalidManifest(format!(
+                "id {:?} must be lowercase letters, digits, '.', '-' and '_', starting with a letter or a digit",
+                self.id
+            )));
+        }
+
+        if self.name.trim().is_empty() {
+            return Err(OmniverlayError::InvalidManifest(format!("{}: name is empty", self.id)));
+        }
+
+        Ok(())
+    }
+
+    pub fn check_host_version(&self) -> OmniverlayResult<()> {
+        let Some(min_host_version) = &self.min_host_version else {
+            return Ok(());
+        };
+
+        let host_version = Version::parse(HOST_VERSION).map_err(|e| OmniverlayError::Other(e.into()))?;
+
+        if host_version < *min_host_version {
+            return Err(OmniverlayError::ExtensionLoadFailed(format!(
+                "{} requires Omniverlay {} or newer, current version is {}",
+                self.id, min_host_version, host_version
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Requests between extensions.
+//!
+//! An extension registers named handlers with `ExtensionHost::handle`, other extensions call them
+//! with `ExtensionHost::request`. A handler runs on its own thread without the extension lock, so it
+//! keeps working while the extension is busy ticking: it should only capture the shared state it
+//! needs. Requests are answered only while the target is running.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::debug;
+use serde_json::Value;
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{
+    supervisor::{self, TimedOutCalls},
+    usage::ExtensionUsage,
+    ExtensionInfo, ExtensionStatus,
+};
+
+/// Time given to a handler before the request fails
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[async_trait]
+pub trait RequestHandler: Send + Sync {
+    async fn handle(&self, params: Value) -> OmniverlayResult<Value>;
+}
+
+#[async_trait]
+impl<F, Fut> RequestHandler for F
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = OmniverlayResult<Value>> + Send,
+{
+    async fn handle(&self, params: Value) -> OmniverlayResult<Value> {
+        self(params).await
+    }
+}
+
+struct Endpoint {
+    info: Arc<tokio::sync::Mutex<ExtensionInfo>>,
+    usage: ExtensionUsage,
+    handlers: HashMap<String, Arc<dyn RequestHandler>>,
+}
+
+#[derive(Clone, Default)]
+pub struct MessageBus {
+    endpoints: Arc<Mutex<HashMap<String, Endpoint>>>,
+}
+
+impl MessageBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn add_extension(
+        &self,
+        extension_name: &str,
+        info: Arc<tokio::sync::Mutex<ExtensionInfo>>,
+        usage: ExtensionUsage,
+    ) {
+        self.endpoints.lock().unwrap().insert(
+            extension_name.to_string(),
+            Endpoint {
+                info,
+                usage,
+                handlers: HashMap::new(),
+            },
+        );
+    }
+
+    pub(super) fn remove_extension(&self, extension_name: &str) {
+        self.endpoints.lock().unwrap().remove(extension_name);
+    }
+
+    /// Register the handler of `method` for an extension, replacing the previous one
+    pub fn register_handler(&self, extension_name: &str, method: &str, handler: Arc<dyn RequestHandler>) {
+        if let Some(endpoint) = self.endpoints.lock().unwrap().get_mut(extension_name) {
+            endpoint.handlers.insert(method.to_string(), handler);
+        }
+    }
+
+    pub fn remove_handler(&self, extension_name: &str, method: &str) {
+        if let Some(endpoint) = self.endpoints.lock().unwrap().get_mut(extension_name) {
+            endpoint.handlers.remove(method);
+        }
+    }
+
+    /// Call `method` on `target` and wait for its answer at most `timeout`
+    pub async fn request(
+        &self,
+        from: &str,
+        target: &str,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> OmniverlayResult<Value> {
+        let (info, usage, handler) = {
+            let endpoints = self.endpoints.lock().unwrap();
+
+            let endpoint = endpoints
+                .get(target)
+                .ok_or_else(|| OmniverlayError::ExtensionNotFound(target.to_string()))?;
+
+            let handler = endpoint
+                .handlers
+                .get(method)
+                .cloned()
+                .ok_or_else(|| OmniverlayError::RequestHandlerNotFound(target.to_string(), method.to_string()))?;
+
+            (endpoint.info.clone(), endpoint.usage.clone(), handler)
+        };
+
+        if info.lock().await.status != ExtensionStatus::Running {
+            return Err(OmniverlayError::ExtensionNotRunning(target.to_string()));
+        }
+
+        debug!("Extension {} calls {} on extension {}", from, method, target);
+
+        let operation = format!("request {method} to extension {target}");
+
+        // Handlers do not hold the extension, one that does not return only keeps its own thread
+        let timed_out = TimedOutCalls::default();
+
+        supervisor::run_guarded_for(&operation, timeout, &timed_out, async move {
+            usage.measure(handler.handle(params)).await
+        }).await
+    }
+}
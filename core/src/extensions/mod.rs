@@ -1,27 +1,114 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use async_trait::async_trait;
+use capabilities::{Capability, Permissions};
+use channel::DataChannel;
 use config::{ExtensionConfig, ExtensionConfigManager};
+use dependencies::{DependencyNode, StartupOrder};
+use host::ExtensionHost;
+use instances::{ExtensionFactory, SingleInstance};
+use libloading::Library;
+use assets::AssetRegistry;
+use manifest::ExtensionManifest;
+use signing::{SigningPolicy, TrustStatus};
+use messaging::MessageBus;
+use storage::ExtensionStorage;
+use scheduler::Scheduler;
+use supervisor::{RestartPolicy, Supervision, TimedOutCalls};
+use usage::{ExtensionUsage, ResourceBudget, UsageReport};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
-use crate::errors::{OmniverlayError, OmniverlayResult};
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    event::OmniverlayEventType,
+    invoke_event,
+};
 
+pub mod assets;
+pub mod capabilities;
+pub mod catalog;
+pub mod channel;
 pub mod config;
 pub mod data;
+pub mod dependencies;
+pub mod host;
+pub mod installer;
+pub mod instances;
+pub mod loader;
+pub mod manifest;
+pub mod messaging;
+pub mod package;
+pub mod process;
+pub mod registry;
+pub mod reload;
+mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod signing;
+pub mod storage;
+pub mod supervisor;
+pub mod usage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 // Define Extension structs
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtensionInfo {
+    /// Name of the instance, see `instances`
     pub name: String,
+    /// Name of the extension this is an instance of, maintained by the `ExtensionManager`
+    #[serde(default)]
+    pub extension_type: String,
     pub state: ExtensionState,
     pub layout: Option<ExtensionLayout>,
+    #[serde(default)]
+    pub manifest: Option<ExtensionManifest>,
+    /// Maintained by the `ExtensionManager`, extensions do not need to set it
+    #[serde(default)]
+    pub status: ExtensionStatus,
+    /// What the extension needs, the user grants it in `ExtensionState::granted_capabilities`
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// Whether the files of the extension are signed by a trusted key, maintained by the
+    /// `ExtensionManager`. `None` when it is compiled into the app.
+    #[serde(default)]
+    pub trust: Option<TrustStatus>,
+    /// Whether the extension ships its own frontend, shown in place of a component of the ui
+    /// package, see `assets`
+    #[serde(default)]
+    pub has_assets: bool,
     //pub geometry: Option<ExtensionGeometry>,
 }
 
+/// What an extension is actually doing, `ExtensionState::is_enabled` is what the user asked for
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ExtensionStatus {
+    #[default]
+    Stopped,
+    Starting,
+    Running,
+    Failed { error: String },
+    /// Disabled for going over its resource budget, only enabling it explicitly starts it again
+    OverBudget { reason: String },
+    Stopping,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtensionState {
     pub is_enabled: bool,
     pub config: Option<ExtensionConfig>,
+    #[serde(default)]
+    pub granted_capabilities: Vec<Capability>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +124,7 @@ impl Default for ExtensionState {
         Self {
             is_enabled: false,
             config: None,
+            granted_capabilities: Vec::new(),
         }
     }
 }
@@ -52,11 +140,32 @@ pub struct ExtensionGeometry {
 // Define Extension trait with default implementations
 #[async_trait]
 pub trait Extension: Send {
-    fn enable(&mut self) -> OmniverlayResult<()>;
-    fn disable(&mut self) -> OmniverlayResult<()>;
+    async fn enable(&mut self) -> OmniverlayResult<()>;
+    async fn disable(&mut self) -> OmniverlayResult<()>;
 
     fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>>;
 
+    /// Called once when the extension is registered, keep the host to publish data
+    fn set_host(&mut self, _host: ExtensionHost) {}
+
+    /// Interval between two `on_tick` calls while the extension is running, `None` to never tick
+    fn tick_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called by the core scheduler every `tick_interval`. A tick that is due while the previous
+    /// one is still running is skipped.
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    /// Representative payloads, like the ones published to the overlay, shown by the studio while
+    /// laying out the extension. Called whether the extension is enabled or not. When empty, the
+    /// `preview` of the manifest is used.
+    async fn preview_payloads(&mut self) -> OmniverlayResult<Vec<serde_json::Value>> {
+        Ok(Vec::new())
+    }
+
     // Default implementations
     // async fn set_geometry(&mut self, geometry: ExtensionGeometry) -> OmniverlayResult<()> {
     //     self.get_extension_info()?.lock().await.geometry = Some(geometry);
@@ -74,29 +183,202 @@ pub trait Extension: Send {
         Ok(())
     }
 
+    #[deprecated(note = "the `ExtensionManager` runs `enable` and `disable` itself, use `enable_extension` and `disable_extension`")]
     async fn set_enabled(&mut self, enabled: bool) -> OmniverlayResult<()> {
         let info = self.get_extension_info()?;
-        let mut guard = info.lock().await;
 
-        if guard.state.is_enabled == enabled {
+        if info.lock().await.state.is_enabled == enabled {
             return Ok(()); // No change needed
         }
 
         if enabled {
-            self.enable()?;
+            self.enable().await?;
         } else {
-            self.disable()?;
+            self.disable().await?;
         }
 
-        guard.state.is_enabled = enabled;
+        info.lock().await.state.is_enabled = enabled;
         Ok(())
     }
 }
 
+/// Time given to `Extension::enable` and `Extension::disable` before the extension is marked as failed
+pub const LIFECYCLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to `Extension::preview_payloads`
+pub const PREVIEW_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An extension instance as the `ExtensionManager` keeps it, shared with the scheduler and the
+/// supervisor which may outlive its registration
+#[derive(Clone)]
+struct RegisteredExtension(Arc<Registration>);
+
+struct Registration {
+    // Instance name
+    name: String,
+    extension_type: String,
+    extension: Arc<Mutex<dyn Extension>>,
+    // Kept apart so the info stays readable while a lifecycle hook holds the extension
+    info: Arc<Mutex<ExtensionInfo>>,
+    supervision: Arc<Mutex<Supervision>>,
+    permissions: Permissions,
+    usage: ExtensionUsage,
+    timed_out: TimedOutCalls,
+    // Of the manager the extension is registered with
+    data_channel: DataChannel,
+    scheduler: Scheduler,
+    // Set once the manager forgets the extension, when it is removed or reloaded
+    dropped: AtomicBool,
+}
+
+impl Deref for RegisteredExtension {
+    type Target = Registration;
+
+    fn deref(&self) -> &Registration {
+        &self.0
+    }
+}
+
+impl RegisteredExtension {
+    fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Run `enable` or `disable` without holding the info lock, so the status stays readable
+    async fn run_lifecycle_hook(&self, enable: bool) -> OmniverlayResult<()> {
+        let (pending, done) = match enable {
+            true => (ExtensionStatus::Starting, ExtensionStatus::Running),
+            false => (ExtensionStatus::Stopping, ExtensionStatus::Stopped),
+        };
+
+        // Asked for by the user, previous failures no longer count and a pending restart must not
+        // bring the extension back
+        self.supervision.lock().await.reset();
+
+        {
+            let mut info = self.info.lock().await;
+
+            info.state.is_enabled = enable;
+
+            match (&info.status, enable) {
+                (ExtensionStatus::Running, true) => return Ok(()),
+                // A failed extension is not running, stopping it only clears the error
+                (
+                    ExtensionStatus::Stopped | ExtensionStatus::Failed { .. } | ExtensionStatus::OverBudget { .. },
+                    false,
+                ) => {
+                    info.status = ExtensionStatus::Stopped;
+                    return Ok(());
+                }
+                _ => info.status = pending,
+            }
+        }
+
+        if enable {
+            self.usage.restart_window();
+        }
+
+        if !enable {
+            self.scheduler.stop_ticking(&self.name);
+        }
+
+        let result = supervisor::call_hook(self, enable).await;
+
+        match &result {
+            Ok(()) => {
+                self.info.lock().await.status = done;
+
+                match enable {
+                    true => self.scheduler.start_ticking(&self.name, self).await,
+                    false => self.data_channel.clear(&self.name),
+                }
+            }
+            Err(e) if enable => supervisor::on_failure(self.name.clone(), self.clone(), e).await,
+            Err(e) => self.info.lock().await.status = ExtensionStatus::Failed { error: e.to_string() },
+        }
+
+        result
+    }
+}
+
+/// The instances of an extension taken out of the `ExtensionManager` by `detach_extension`, still
+/// running. They are stopped without holding the manager, so a slow `disable` blocks nobody.
+pub struct DetachedExtension {
+    extension_type: String,
+    // First instance first
+    instances: Vec<RegisteredExtension>,
+}
+
+impl DetachedExtension {
+    pub fn extension_type(&self) -> &str {
+        &self.extension_type
+    }
+
+    pub fn instance_names(&self) -> Vec<String> {
+        self.instances.iter().map(|registered| registered.name.clone()).collect()
+    }
+
+    /// Disable the instances, the last one first
+    pub async fn stop(&self) {
+        for registered in self.instances.iter().rev() {
+            if let Err(e) = registered.run_lifecycle_hook(false).await {
+                error!("Failed to disable extension {}: {}", registered.name, e);
+            }
+        }
+    }
+
+    /// Forget what the instances published, their subscribers are told they are gone
+    pub fn remove_data(&self) {
+        for registered in &self.instances {
+            registered.data_channel.remove(&registered.name);
+        }
+    }
+}
+
+/// What is needed to create more instances of an extension
+struct ExtensionType {
+    factory: Arc<dyn ExtensionFactory>,
+    manifest: Option<ExtensionManifest>,
+    /// File the extension was loaded from, `None` when it is compiled in
+    source: Option<PathBuf>,
+    trust: Option<TrustStatus>,
+}
+
+/// Extensions provided by an extension file
+struct OpenedExtensions {
+    library: Option<Library>,
+    factories: Vec<Arc<dyn ExtensionFactory>>,
+}
+
+impl OpenedExtensions {
+    fn single(factory: Arc<dyn ExtensionFactory>) -> Self {
+        Self {
+            library: None,
+            factories: vec![factory],
+        }
+    }
+}
+
 // Define ExtensionManager to manage extensions
 pub struct ExtensionManager {
     config_manager: ExtensionConfigManager,
-    extensions: HashMap<String, Arc<Mutex<dyn Extension>>>,
+    // Keyed by instance name
+    extensions: HashMap<String, RegisteredExtension>,
+    types: HashMap<String, ExtensionType>,
+    data_channel: DataChannel,
+    message_bus: MessageBus,
+    signing: SigningPolicy,
+    assets: AssetRegistry,
+    scheduler: Scheduler,
+    // Declared after `extensions` so libraries are unloaded only once their extensions are dropped
+    libraries: Vec<Library>,
+}
+
+// The ticks hold their extension, which holds the scheduler
+impl Drop for ExtensionManager {
+    fn drop(&mut self) {
+        self.scheduler.shutdown();
+    }
 }
 
 impl ExtensionManager {
@@ -106,86 +388,1016 @@ impl ExtensionManager {
         Self {
             config_manager,
             extensions: HashMap::new(),
+            types: HashMap::new(),
+            data_channel: DataChannel::new(),
+            message_bus: MessageBus::new(),
+            signing: SigningPolicy::default(),
+            assets: AssetRegistry::default(),
+            scheduler: Scheduler::default(),
+            libraries: Vec::new(),
         }
     }
 
+    /// Policy applied to the extensions loaded from now on, nothing is refused by default
+    pub fn set_signing_policy(&mut self, policy: SigningPolicy) {
+        self.signing = policy;
+    }
+
+    pub fn signing_policy(&self) -> &SigningPolicy {
+        &self.signing
+    }
+
+    /// Frontend assets of the registered extensions, kept up to date as they come and go
+    pub fn assets(&self) -> AssetRegistry {
+        self.assets.clone()
+    }
+
+    /// Register an extension that only has one instance
     pub async fn register_extension<T: 'static + Extension>(&mut self, extension: T) -> OmniverlayResult<()> {
-        let info = extension.get_extension_info()?;
-        let guard = info.lock().await;
+        self.register_extension_factory(Arc::new(SingleInstance::new(extension)), None).await?;
 
-        let name = guard.name.clone();
-        
-        // if let Some(config) = guard.config.clone() {
+        Ok(())
+    }
 
-        //     // match self.config_manager.load_config(name.as_ref()) {
-        //     //     Ok(config) => {
-        //     //         self.config_manager.register_config(name.clone(), config)?;
-        //     //         info!("Registered config for extension {}", name);
-        //     //     },
-        //     //     Err(OmniverlayError::ConfigNotFound(_)) => {
-        //     //         self.config_manager.register_config(name.clone(), config)?; // Register default config
-        //     //         info!("Registered default config for extension {}", name);
-        //     //     },
-        //     //     Err(e) => {
-        //     //         return Err(e);
-        //     //     }
-        //     // };
-        // }
+    /// Register the constructor of an extension, which is called for every instance
+    pub async fn register_extension_type<T, F>(&mut self, constructor: F) -> OmniverlayResult<()>
+    where
+        T: 'static + Extension,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.register_extension_factory(Arc::new(move || Ok(constructor())), None).await?;
 
-        let arc = Arc::new(Mutex::new(extension));
+        Ok(())
+    }
 
-        self.extensions.insert(name, arc);
+    /// Register every extension compiled into the app with `#[omniverlay_extension]`
+    pub async fn register_compiled_extensions(&mut self) -> OmniverlayResult<()> {
+        for compiled in registry::compiled_extensions() {
+            match self.register_extension_factory((compiled.factory)(), None).await {
+                Ok(name) => info!("Registered compiled extension {} ({})", name, compiled.type_name),
+                // One broken extension must not prevent the others from registering
+                Err(e) => error!("Failed to register compiled extension {}: {}", compiled.type_name, e),
+            }
+        }
 
         Ok(())
     }
 
-    pub async fn enable_extension(&mut self, extension_name: &str) -> OmniverlayResult<()> {
-        let extension = self.get_extension_by_name(extension_name)?;
-        let mut guard = extension.lock().await;
+    /// Create the first instance of an extension and register it, returns the name of the extension
+    pub async fn register_extension_factory(
+        &mut self,
+        factory: Arc<dyn ExtensionFactory>,
+        manifest: Option<&ExtensionManifest>,
+    ) -> OmniverlayResult<String> {
+        let (name, extension) = Self::create_instance(&factory).await?;
+
+        if self.types.contains_key(&name) || self.extensions.contains_key(&name) {
+            return Err(OmniverlayError::ExtensionLoadFailed(format!("extension {name} is already registered")));
+        }
+
+        self.add_extension_type(name.clone(), factory, extension, manifest, None, None).await?;
+
+        Ok(name)
+    }
+
+    async fn create_instance(factory: &Arc<dyn ExtensionFactory>) -> OmniverlayResult<(String, Arc<Mutex<dyn Extension>>)> {
+        let extension = factory.create().await?;
+        let name = extension.lock().await.get_extension_info()?.lock().await.name.clone();
+
+        instances::check_extension_type(&name)?;
+
+        Ok((name, extension))
+    }
+
+    async fn add_extension_type(
+        &mut self,
+        name: String,
+        factory: Arc<dyn ExtensionFactory>,
+        extension: Arc<Mutex<dyn Extension>>,
+        manifest: Option<&ExtensionManifest>,
+        source: Option<&Path>,
+        trust: Option<TrustStatus>,
+    ) -> OmniverlayResult<()> {
+        self.assets.insert(&name, source.and_then(assets::assets_dir_of));
+
+        self.types.insert(
+            name.clone(),
+            ExtensionType {
+                factory,
+                manifest: manifest.cloned(),
+                source: source.map(Path::to_path_buf),
+                trust,
+            },
+        );
+
+        self.add_instance(name.clone(), name, extension).await
+    }
+
+    async fn add_instance(
+        &mut self,
+        instance_name: String,
+        extension_type: String,
+        extension: Arc<Mutex<dyn Extension>>,
+    ) -> OmniverlayResult<()> {
+        let info = extension.lock().await.get_extension_info()?;
+        let mut guard = info.lock().await;
+
+        guard.name = instance_name.clone();
+        guard.extension_type = extension_type.clone();
+
+        // A manifest shipped next to the extension only fills in what the extension did not declare itself
+        if guard.manifest.is_none() {
+            guard.manifest = self.types.get(&extension_type).and_then(|t| t.manifest.clone());
+        }
+
+        // Never taken from the extension, which could claim anything
+        guard.trust = self.types.get(&extension_type).and_then(|t| t.trust.clone());
+        guard.has_assets = self.assets.has_assets(&extension_type);
 
-        guard.set_enabled(true).await?;
+        if guard.capabilities.is_empty() {
+            guard.capabilities = guard.manifest.as_ref().map(|m| m.capabilities.clone()).unwrap_or_default();
+        }
+
+        // Only the user grants capabilities, through the profile. Extensions compiled into the app
+        // ship with it, what they declare is granted in the profiles that do not know them yet.
+        let compiled_in = self.types.get(&extension_type).is_some_and(|t| t.source.is_none());
+
+        guard.state.granted_capabilities = match compiled_in {
+            true => guard.capabilities.clone(),
+            false => Vec::new(),
+        };
+
+        let permissions = Permissions::new(instance_name.clone());
+        permissions.grant(&guard.capabilities, &guard.state.granted_capabilities);
+
+        let budget = guard.manifest.as_ref().and_then(|m| m.budget.clone()).unwrap_or_default();
+        let usage = ExtensionUsage::new(&instance_name, Some(budget));
+
+        self.message_bus.add_extension(&instance_name, info.clone(), usage.clone());
+
+        let policy = guard.manifest.as_ref().and_then(|manifest| manifest.restart.clone()).unwrap_or_default();
+
+        let registered = RegisteredExtension(Arc::new(Registration {
+            name: instance_name.clone(),
+            extension_type,
+            extension: extension.clone(),
+            info: info.clone(),
+            supervision: Arc::new(Mutex::new(Supervision {
+                policy,
+                ..Default::default()
+            })),
+            permissions,
+            usage,
+            timed_out: TimedOutCalls::default(),
+            data_channel: self.data_channel.clone(),
+            scheduler: self.scheduler.clone(),
+            dropped: AtomicBool::new(false),
+        }));
+
+        registered.usage.set_owner(Arc::downgrade(&registered.0));
+
+        extension.lock().await.set_host(ExtensionHost::new(
+            instance_name.clone(),
+            self.data_channel.clone(),
+            self.message_bus.clone(),
+            ExtensionStorage::new(&instance_name)?,
+            &registered,
+        ));
+
+        self.extensions.insert(instance_name, registered);
 
         Ok(())
     }
 
-    pub async fn disable_extension(&mut self, extension_name: &str) -> OmniverlayResult<()> {
-        let extension = self.get_extension_by_name(extension_name)?;
-        let mut guard = extension.lock().await;
+    /// Create the first instance of every extension of a file. Fails if one of them is already
+    /// registered, unless `replacing` and it comes from the same file.
+    async fn create_loaded_instances(
+        &self,
+        path: &Path,
+        factories: Vec<Arc<dyn ExtensionFactory>>,
+        replacing: bool,
+    ) -> OmniverlayResult<Vec<(String, Arc<dyn ExtensionFactory>, Arc<Mutex<dyn Extension>>)>> {
+        let mut extensions: Vec<(String, Arc<dyn ExtensionFactory>, Arc<Mutex<dyn Extension>>)> = Vec::new();
+
+        for factory in factories {
+            let (name, extension) = Self::create_instance(&factory).await?;
+
+            let taken = self
+                .types
+                .get(&name)
+                .is_some_and(|extension_type| !replacing || extension_type.source.as_deref() != Some(path));
+
+            if taken || extensions.iter().any(|(n, _, _)| *n == name) {
+                return Err(OmniverlayError::ExtensionLoadFailed(format!(
+                    "{}: extension {} is already registered",
+                    path.display(),
+                    name
+                )));
+            }
+
+            extensions.push((name, factory, extension));
+        }
+
+        Ok(extensions)
+    }
+
+    /// Register the extensions of a file, nothing is registered unless every one of them can be
+    async fn add_loaded_extensions(
+        &mut self,
+        path: &Path,
+        opened: OpenedExtensions,
+        manifest: Option<&ExtensionManifest>,
+        trust: TrustStatus,
+    ) -> OmniverlayResult<()> {
+        let extensions = self.create_loaded_instances(path, opened.factories, false).await?;
+
+        for (name, factory, extension) in extensions {
+            info!("Loaded extension {} from {}", name, path.display());
+
+            self.add_extension_type(name, factory, extension, manifest, Some(path), Some(trust.clone()))
+                .await?;
+        }
+
+        if let Some(library) = opened.library {
+            self.libraries.push(library);
+        }
 
-        guard.set_enabled(false).await?;
-        
         Ok(())
     }
 
-    pub async fn update_extension_state(&mut self, extension_name: &str, state: ExtensionState) -> OmniverlayResult<()> {
-        let extension = self.get_extension_by_name(extension_name)?;
-        let mut guard = extension.lock().await;
+    /// Trust of the extension file at `path`, fails if the signing policy refuses it. Checked before
+    /// opening the file, loading a library already runs its code.
+    fn admit_source(&self, path: &Path) -> OmniverlayResult<TrustStatus> {
+        self.signing.admit(path, self.signing.verify_source(path))
+    }
+
+    pub async fn load_extension_library(&mut self, path: &Path, manifest: Option<&ExtensionManifest>) -> OmniverlayResult<()> {
+        let trust = self.admit_source(path)?;
+        let (library, factories) = loader::load_library(path)?;
+
+        let opened = OpenedExtensions {
+            library: Some(library),
+            factories,
+        };
+
+        self.add_loaded_extensions(path, opened, manifest, trust).await
+    }
+
+    #[cfg(feature = "wasm")]
+    pub async fn load_wasm_extension(&mut self, path: &Path, manifest: Option<&ExtensionManifest>) -> OmniverlayResult<()> {
+        let trust = self.admit_source(path)?;
+
+        self.add_loaded_extensions(path, Self::open_wasm_extension(path), manifest, trust).await
+    }
+
+    #[cfg(feature = "scripting")]
+    pub async fn load_script_extension(&mut self, path: &Path, manifest: Option<&ExtensionManifest>) -> OmniverlayResult<()> {
+        let trust = self.admit_source(path)?;
+
+        self.add_loaded_extensions(path, Self::open_script_extension(path), manifest, trust).await
+    }
+
+    pub async fn load_process_extension(&mut self, path: &Path, manifest: Option<&ExtensionManifest>) -> OmniverlayResult<()> {
+        let trust = self.admit_source(path)?;
+        let opened = Self::open_process_extension(path)?;
+
+        self.add_loaded_extensions(path, opened, manifest, trust).await
+    }
+
+    #[cfg(feature = "wasm")]
+    fn open_wasm_extension(path: &Path) -> OpenedExtensions {
+        let path_buf = path.to_path_buf();
+        let factory = move || wasm::WasmExtension::from_file(&path_buf, wasm::WasmLimits::default());
+
+        OpenedExtensions::single(Arc::new(factory))
+    }
+
+    #[cfg(feature = "scripting")]
+    fn open_script_extension(path: &Path) -> OpenedExtensions {
+        let path_buf = path.to_path_buf();
+        let factory = move || script::ScriptExtension::from_file(&path_buf);
+
+        OpenedExtensions::single(Arc::new(factory))
+    }
+
+    fn open_process_extension(path: &Path) -> OmniverlayResult<OpenedExtensions> {
+        let descriptor = process::ProcessExtensionDescriptor::from_file(path)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{}: {e}", path.display())))?;
+
+        Ok(OpenedExtensions::single(Arc::new(descriptor)))
+    }
+
+    /// Open the extension file at `path` based on its file extension, `None` if it is not an
+    /// extension file. A library is loaded from a copy when `copy_library` is set, see
+    /// `loader::load_library_copy`.
+    fn open_extension_file(path: &Path, copy_library: bool) -> Option<OmniverlayResult<OpenedExtensions>> {
+        if !path.is_file() {
+            return None;
+        }
+
+        let opened = match path.extension().and_then(|e| e.to_str()) {
+            Some(std::env::consts::DLL_EXTENSION) => {
+                let loaded = match copy_library {
+                    true => loader::load_library_copy(path),
+                    false => loader::load_library(path),
+                };
+
+                loaded.map(|(library, factories)| OpenedExtensions {
+                    library: Some(library),
+                    factories,
+                })
+            }
+            #[cfg(feature = "wasm")]
+            Some("wasm") => Ok(Self::open_wasm_extension(path)),
+            #[cfg(feature = "scripting")]
+            Some("rhai") => Ok(Self::open_script_extension(path)),
+            Some("json") if path.to_string_lossy().ends_with(".process.json") => Self::open_process_extension(path),
+            _ => return None,
+        };
+
+        Some(opened)
+    }
+
+    /// Load the extension at `path` based on its file extension, `None` if it is not an extension
+    /// file. `trust` is the one of its directory, `copy_library` is given to `open_extension_file`.
+    async fn load_extension_file(
+        &mut self,
+        path: &Path,
+        manifest: Option<&ExtensionManifest>,
+        trust: TrustStatus,
+        copy_library: bool,
+    ) -> Option<OmniverlayResult<()>> {
+        if !path.is_file() || !package::is_extension_file(&path.to_string_lossy()) {
+            return None;
+        }
+
+        let trust = match self.signing.admit(path, trust) {
+            Ok(trust) => trust,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let opened = match Self::open_extension_file(path, copy_library)? {
+            Ok(opened) => opened,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(self.add_loaded_extensions(path, opened, manifest, trust).await)
+    }
+
+    /// Load an extension directory, which holds an `extension.toml` next to the extension itself.
+    /// Libraries are loaded from copies, so an install can replace the directory while they are
+    /// loaded and a new version of a library is not mistaken for the loaded one.
+    pub async fn load_extension_dir(&mut self, dir: &Path) -> OmniverlayResult<()> {
+        let manifest = ExtensionManifest::from_file(&dir.join(manifest::MANIFEST_FILE_NAME))?;
+
+        manifest.check_host_version()?;
+
+        // Checked once for every file of the directory
+        let trust = self.signing.trusted_keys.verify_dir(dir);
+
+        let mut loaded = false;
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
 
-        guard.update_state(state).await?;
+            if let Some(result) = self.load_extension_file(&path, Some(&manifest), trust.clone(), true).await {
+                result?;
+                loaded = true;
+            }
+        }
+
+        if !loaded {
+            return Err(OmniverlayError::ExtensionLoadFailed(format!(
+                "{}: no extension found next to the manifest",
+                dir.display()
+            )));
+        }
 
         Ok(())
     }
 
-    pub async fn update_extension_layout(&mut self, extension_name: &str, layout: ExtensionLayout) -> OmniverlayResult<()> {
-        let extension = self.get_extension_by_name(extension_name)?;
-        let mut guard = extension.lock().await;
+    pub async fn load_extensions_from_dir(&mut self, dir: &Path) -> OmniverlayResult<()> {
+        std::fs::create_dir_all(dir)?;
+
+        if let Err(e) = loader::remove_library_copies() {
+            warn!("Failed to remove the library copies of previous runs: {}", e);
+        }
 
-        guard.update_layout(layout).await?;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if installer::is_hidden(&path) {
+                continue;
+            }
+
+            let result = if path.join(manifest::MANIFEST_FILE_NAME).is_file() {
+                self.load_extension_dir(&path).await
+            } else {
+                // Files dropped in the extensions directory are not signed
+                match self.load_extension_file(&path, None, TrustStatus::Untrusted { key: None }, false).await {
+                    Some(result) => result,
+                    None => continue,
+                }
+            };
+
+            // A broken extension must not prevent the others from loading
+            if let Err(e) = result {
+                error!("Failed to load extension: {}", e);
+            }
+        }
 
         Ok(())
     }
 
-    pub fn get_extension_by_name(&self, name: &str) -> OmniverlayResult<Arc<Mutex<dyn Extension>>> {
-        self.extensions.get(name)
-            .cloned()
+    /// Extensions loaded from the extension directory `dir`, see `load_extension_dir`
+    pub fn extensions_in_dir(&self, dir: &Path) -> Vec<String> {
+        self.types
+            .iter()
+            .filter(|(_, extension_type)| extension_type.source.as_deref().and_then(Path::parent) == Some(dir))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Files the loaded extensions come from, along with their manifest, see `reload`
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+
+        for source in self.types.values().filter_map(|extension_type| extension_type.source.as_ref()) {
+            files.push(source.clone());
+
+            if let Some(dir) = source.parent() {
+                files.push(dir.join(manifest::MANIFEST_FILE_NAME));
+            }
+        }
+
+        files.sort();
+        files.dedup();
+
+        files
+    }
+
+    /// Reload the extensions coming from `path`, an extension file or a manifest, in place: their
+    /// instances keep their state and layout, and the running ones are started again. Returns
+    /// the reloaded extensions.
+    pub async fn reload_extensions_at(&mut self, path: &Path) -> OmniverlayResult<Vec<String>> {
+        let is_manifest = path.file_name().is_some_and(|name| name == manifest::MANIFEST_FILE_NAME);
+
+        let mut sources: Vec<PathBuf> = self
+            .types
+            .values()
+            .filter_map(|extension_type| extension_type.source.clone())
+            .filter(|source| source == path || (is_manifest && source.parent() == path.parent()))
+            .collect();
+
+        sources.sort();
+        sources.dedup();
+
+        if sources.is_empty() {
+            return Err(OmniverlayError::ExtensionNotFound(path.display().to_string()));
+        }
+
+        let mut reloaded = Vec::new();
+
+        for source in sources {
+            reloaded.extend(self.reload_source(&source).await?);
+        }
+
+        invoke_event!(OmniverlayEventType::UpdateExtensionData);
+
+        Ok(reloaded)
+    }
+
+    async fn reload_source(&mut self, source: &Path) -> OmniverlayResult<Vec<String>> {
+        // Everything is loaded before the current version is stopped, so a broken build leaves it running
+        let manifest_path = source.parent().map(|dir| dir.join(manifest::MANIFEST_FILE_NAME));
+
+        let manifest = match manifest_path.filter(|path| path.is_file()) {
+            Some(path) => {
+                let manifest = ExtensionManifest::from_file(&path)?;
+                manifest.check_host_version()?;
+                Some(manifest)
+            }
+            None => None,
+        };
+
+        let trust = self.admit_source(source)?;
+
+        let OpenedExtensions { library, factories } = Self::open_extension_file(source, true).ok_or_else(|| {
+            OmniverlayError::ExtensionLoadFailed(format!("{}: not an extension file", source.display()))
+        })??;
+
+        let extensions = self.create_loaded_instances(source, factories, true).await?;
+
+        let old_types: Vec<String> = self
+            .types
+            .iter()
+            .filter(|(_, extension_type)| extension_type.source.as_deref() == Some(source))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut snapshots = Vec::new();
+
+        for name in old_types.iter().flat_map(|extension_type| self.instances_of(extension_type)) {
+            let info = self.get_registered(&name)?.info.lock().await;
+
+            snapshots.push((name.clone(), info.state.clone(), info.layout.clone()));
+        }
+
+        // The data channel is left alone, subscribers keep receiving from the new version
+        for name in &old_types {
+            self.detach_extension(name)?.stop().await;
+        }
+
+        let mut reloaded = Vec::new();
+
+        for (name, factory, extension) in extensions {
+            info!("Reloaded extension {} from {}", name, source.display());
+
+            self.add_extension_type(
+                name.clone(),
+                factory,
+                extension,
+                manifest.as_ref(),
+                Some(source),
+                Some(trust.clone()),
+            )
+            .await?;
+
+            reloaded.push(name);
+        }
+
+        if let Some(library) = library {
+            self.libraries.push(library);
+        }
+
+        let names: Vec<String> = snapshots.iter().map(|(name, _, _)| name.clone()).collect();
+        self.create_missing_instances(&names).await;
+
+        let mut enabled = Vec::new();
+
+        for (name, state, layout) in snapshots {
+            if !self.extensions.contains_key(&name) {
+                warn!("Extension instance {} no longer exists after the reload", name);
+                continue;
+            }
+
+            if state.is_enabled {
+                enabled.push(name.clone());
+            }
+
+            // Enabled below, in startup order
+            let state = ExtensionState {
+                is_enabled: false,
+                ..state
+            };
+
+            if let Err(e) = self.update_extension_state(&name, state).await {
+                error!("Failed to restore state of extension {}: {}", name, e);
+            }
+
+            if let Some(layout) = layout {
+                if let Err(e) = self.update_extension_layout(&name, layout).await {
+                    error!("Failed to restore layout of extension {}: {}", name, e);
+                }
+            }
+        }
+
+        let order = self.resolve_startup_order().await.order;
+
+        enabled.sort_by_key(|name| {
+            let extension_type = instances::parse_instance_name(name).0;
+
+            order.iter().position(|n| n == extension_type).unwrap_or(usize::MAX)
+        });
+
+        for name in &enabled {
+            if let Err(e) = self.enable_extension(name).await {
+                error!("Failed to enable extension {} after reloading it: {}", name, e);
+            }
+        }
+
+        Ok(reloaded)
+    }
+
+    /// Create a new instance of an extension, returns its name
+    pub async fn create_extension_instance(&mut self, extension_type: &str) -> OmniverlayResult<String> {
+        let number = (2..=instances::MAX_INSTANCES)
+            .find(|number| !self.extensions.contains_key(&instances::instance_name(extension_type, *number)))
+            .ok_or_else(|| {
+                OmniverlayError::ExtensionInstance(format!(
+                    "{extension_type} already has {} instances",
+                    instances::MAX_INSTANCES
+                ))
+            })?;
+
+        let name = instances::instance_name(extension_type, number);
+
+        self.create_named_instance(extension_type, &name).await?;
+
+        Ok(name)
+    }
+
+    async fn create_named_instance(&mut self, extension_type: &str, instance_name: &str) -> OmniverlayResult<()> {
+        let factory = self
+            .types
+            .get(extension_type)
+            .ok_or_else(|| OmniverlayError::ExtensionNotFound(extension_type.to_string()))?
+            .factory
+            .clone();
+
+        let extension = factory
+            .create()
+            .await
+            .map_err(|e| OmniverlayError::ExtensionInstance(format!("cannot create {instance_name}: {e}")))?;
+
+        info!("Created extension instance {}", instance_name);
+
+        self.add_instance(instance_name.to_string(), extension_type.to_string(), extension).await
+    }
+
+    /// Create the instances named in a profile or a layout that do not exist yet
+    pub async fn create_missing_instances<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) {
+        for name in names {
+            let (extension_type, number) = instances::parse_instance_name(name);
+
+            if number == 1 || self.extensions.contains_key(name) || !self.types.contains_key(extension_type) {
+                continue;
+            }
+
+            if let Err(e) = self.create_named_instance(extension_type, name).await {
+                error!("Failed to create extension instance {}: {}", name, e);
+            }
+        }
+    }
+
+    /// Stop an instance and forget it along with its data and storage, the first instance of an
+    /// extension cannot be removed
+    pub async fn remove_extension_instance(&mut self, instance_name: &str) -> OmniverlayResult<()> {
+        let registered = self.get_registered(instance_name)?;
+
+        if registered.extension_type == instance_name {
+            return Err(OmniverlayError::ExtensionInstance(format!(
+                "{instance_name} is the first instance of the extension and cannot be removed"
+            )));
+        }
+
+        if let Err(e) = self.disable_extension(instance_name).await {
+            error!("Failed to disable extension instance {}: {}", instance_name, e);
+        }
+
+        self.forget_extension(instance_name);
+        self.data_channel.remove(instance_name);
+
+        ExtensionStorage::new(instance_name)?.remove()?;
+
+        info!("Removed extension instance {}", instance_name);
+
+        Ok(())
+    }
+
+    /// Stop every instance of an extension and forget them along with the extension, returns
+    /// their names. Their storage is kept, and a library stays loaded until the app exits.
+    pub async fn unregister_extension(&mut self, extension_type: &str) -> OmniverlayResult<Vec<String>> {
+        let detached = self.detach_extension(extension_type)?;
+
+        detached.stop().await;
+        detached.remove_data();
+
+        info!("Unregistered extension {}", extension_type);
+
+        Ok(detached.instance_names())
+    }
+
+    /// Take an extension and its instances out of the manager without stopping them, they are
+    /// stopped with `DetachedExtension::stop`
+    pub fn detach_extension(&mut self, extension_type: &str) -> OmniverlayResult<DetachedExtension> {
+        if !self.types.contains_key(extension_type) {
+            return Err(OmniverlayError::ExtensionNotFound(extension_type.to_string()));
+        }
+
+        let instances = self
+            .instances_of(extension_type)
+            .iter()
+            .filter_map(|name| self.forget_extension(name))
+            .collect();
+
+        self.types.remove(extension_type);
+        self.assets.remove(extension_type);
+
+        Ok(DetachedExtension {
+            extension_type: extension_type.to_string(),
+            instances,
+        })
+    }
+
+    /// Instances of an extension, the first one comes first
+    fn instances_of(&self, extension_type: &str) -> Vec<String> {
+        let mut instances: Vec<(&str, u32)> = self
+            .extensions
+            .iter()
+            .filter(|(_, registered)| registered.extension_type == extension_type)
+            .map(|(name, _)| (name.as_str(), instances::parse_instance_name(name).1))
+            .collect();
+
+        instances.sort_by_key(|(_, number)| *number);
+
+        instances.into_iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    /// Remove an instance, whatever still holds it sees it was dropped
+    fn forget_extension(&mut self, name: &str) -> Option<RegisteredExtension> {
+        self.message_bus.remove_extension(name);
+
+        let registered = self.extensions.remove(name)?;
+        registered.dropped.store(true, Ordering::SeqCst);
+
+        Some(registered)
+    }
+
+    fn get_registered(&self, name: &str) -> OmniverlayResult<&RegisteredExtension> {
+        self.extensions
+            .get(name)
             .ok_or_else(|| OmniverlayError::ExtensionNotFound(name.to_string()))
     }
 
+    // The user still wants the extension running, the status tells why it is not
+    async fn mark_failed(&self, extension_name: &str, error: &OmniverlayError) -> OmniverlayResult<()> {
+        let mut info = self.get_registered(extension_name)?.info.lock().await;
+
+        info.state.is_enabled = true;
+        info.status = ExtensionStatus::Failed { error: error.to_string() };
+
+        Ok(())
+    }
+
+    async fn run_lifecycle_hook(&self, extension_name: &str, enable: bool) -> OmniverlayResult<()> {
+        self.get_registered(extension_name)?.run_lifecycle_hook(enable).await
+    }
+
+    /// Handle a failure of a running extension, as reported with `ExtensionHost::report_failure`
+    pub async fn handle_failure(&self, extension_name: &str, error: OmniverlayError) -> OmniverlayResult<()> {
+        let registered = self.get_registered(extension_name)?;
+
+        supervisor::fail_running(extension_name.to_string(), registered.clone(), &error).await;
+
+        Ok(())
+    }
+
+    pub async fn set_restart_policy(&self, extension_name: &str, policy: RestartPolicy) -> OmniverlayResult<()> {
+        self.get_registered(extension_name)?.supervision.lock().await.policy = policy;
+
+        Ok(())
+    }
+
+    /// Disable an extension that went over its budget, as its usage tracker does, see
+    /// `supervisor::disable_over_budget`
+    pub async fn handle_over_budget(&self, extension_name: &str, reason: String) -> OmniverlayResult<()> {
+        supervisor::disable_over_budget(self.get_registered(extension_name)?, reason).await
+    }
+
+    /// Replace the budget given by the manifest, `None` lets the extension use what it wants
+    pub fn set_budget(&self, extension_name: &str, budget: Option<ResourceBudget>) -> OmniverlayResult<()> {
+        self.get_registered(extension_name)?.usage.set_budget(budget);
+
+        Ok(())
+    }
+
+    /// What every extension instance cost so far, by name
+    pub fn usage(&self) -> HashMap<String, UsageReport> {
+        self.extensions
+            .iter()
+            .map(|(name, registered)| (name.clone(), registered.usage.report()))
+            .collect()
+    }
+
+    pub async fn enable_extension(&self, extension_name: &str) -> OmniverlayResult<()> {
+        self.get_registered(extension_name)?;
+
+        if let Err(e) = self.check_dependencies(extension_name).await {
+            self.mark_failed(extension_name, &e).await?;
+
+            return Err(e);
+        }
+
+        self.run_lifecycle_hook(extension_name, true).await
+    }
+
+    // Instances share the dependencies of their extension, only first instances are resolved
+    async fn dependency_nodes(&self) -> Vec<DependencyNode> {
+        let mut nodes = Vec::new();
+
+        for (name, registered) in &self.extensions {
+            if *name == registered.extension_type {
+                nodes.push(DependencyNode::from_info(&*registered.info.lock().await));
+            }
+        }
+
+        nodes
+    }
+
+    /// Order every registered extension after its dependencies, instances are left out and
+    /// follow their extension
+    pub async fn resolve_startup_order(&self) -> StartupOrder {
+        dependencies::resolve_startup_order(&self.dependency_nodes().await)
+    }
+
+    // Any running instance of an extension is enough for its dependents
+    async fn is_running(&self, extension_type: &str) -> bool {
+        for name in self.instances_of(extension_type) {
+            if let Ok(registered) = self.get_registered(&name) {
+                if registered.info.lock().await.status == ExtensionStatus::Running {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // Dependencies must be installed, compatible and already running
+    async fn check_dependencies(&self, extension_name: &str) -> OmniverlayResult<()> {
+        let extension_type = self.get_registered(extension_name)?.extension_type.clone();
+
+        let order = self.resolve_startup_order().await;
+
+        if let Some((_, e)) = order.errors.into_iter().find(|(name, _)| *name == extension_type) {
+            return Err(e);
+        }
+
+        let nodes = self.dependency_nodes().await;
+
+        let Some(node) = nodes.iter().find(|node| node.name == extension_type) else {
+            return Ok(());
+        };
+
+        for dependency_id in node.dependencies.keys() {
+            let Some(dependency) = nodes.iter().find(|node| node.id == *dependency_id) else {
+                continue;
+            };
+
+            if !self.is_running(&dependency.name).await {
+                return Err(OmniverlayError::DependencyNotEnabled(
+                    extension_name.to_string(),
+                    dependency.name.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the states of a profile: extensions are disabled in reverse startup order, then
+    /// enabled in startup order so dependencies always run before their dependents.
+    pub async fn apply_states(&self, states: &HashMap<String, ExtensionState>) -> OmniverlayResult<()> {
+        let StartupOrder { order, errors } = self.resolve_startup_order().await;
+
+        let order: Vec<String> = order.iter().flat_map(|name| self.instances_of(name)).collect();
+
+        for name in states.keys().filter(|name| !self.extensions.contains_key(*name)) {
+            error!("Failed to apply state of extension {}: not registered", name);
+        }
+
+        // Update the config first, the enabled flag is only changed by actually enabling/disabling
+        for (name, state) in states {
+            let Ok(registered) = self.get_registered(name) else {
+                continue;
+            };
+
+            let (is_enabled, declared) = {
+                let info = registered.info.lock().await;
+                (info.state.is_enabled, info.capabilities.clone())
+            };
+
+            registered.permissions.grant(&declared, &state.granted_capabilities);
+
+            let new_state = ExtensionState {
+                is_enabled,
+                config: state.config.clone(),
+                granted_capabilities: state.granted_capabilities.clone(),
+            };
+
+            let result = supervisor::call_update_state(registered, new_state).await;
+
+            if let Err(e) = result {
+                error!("Failed to update state of extension {}: {}", name, e);
+            }
+        }
+
+        let wants_enabled = |name: &String| states.get(name).map(|state| state.is_enabled).unwrap_or(false);
+
+        for name in order.iter().rev().filter(|name| !wants_enabled(name)) {
+            if let Err(e) = self.disable_extension(name).await {
+                error!("Failed to disable extension {}: {}", name, e);
+            }
+        }
+
+        for (extension_type, e) in &errors {
+            for name in self.instances_of(extension_type) {
+                // An unresolvable extension may still be running from a previous profile
+                if let Err(e) = self.disable_extension(&name).await {
+                    error!("Failed to disable extension {}: {}", name, e);
+                }
+
+                if wants_enabled(&name) {
+                    error!("Failed to enable extension {}: {}", name, e);
+
+                    self.mark_failed(&name, e).await?;
+                }
+            }
+        }
+
+        for name in order.iter().filter(|name| wants_enabled(name)) {
+            // Saving a profile is no reason to start a runaway extension again
+            if let Ok(registered) = self.get_registered(name) {
+                if matches!(registered.info.lock().await.status, ExtensionStatus::OverBudget { .. }) {
+                    continue;
+                }
+            }
+
+            if let Err(e) = self.enable_extension(name).await {
+                error!("Failed to enable extension {}: {}", name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Disable every extension, dependents before their dependencies
+    pub async fn disable_all(&self) -> OmniverlayResult<()> {
+        let StartupOrder { order, errors } = self.resolve_startup_order().await;
+
+        let types = errors.into_iter().map(|(name, _)| name).chain(order.into_iter().rev());
+
+        for name in types.flat_map(|name| self.instances_of(&name)).collect::<Vec<_>>() {
+            if let Err(e) = self.disable_extension(&name).await {
+                error!("Failed to disable extension {}: {}", name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn disable_extension(&self, extension_name: &str) -> OmniverlayResult<()> {
+        self.run_lifecycle_hook(extension_name, false).await
+    }
+
+    pub async fn update_extension_state(&mut self, extension_name: &str, state: ExtensionState) -> OmniverlayResult<()> {
+        let registered = self.get_registered(extension_name)?;
+
+        let declared = registered.info.lock().await.capabilities.clone();
+        registered.permissions.grant(&declared, &state.granted_capabilities);
+
+        supervisor::call_update_state(registered, state).await
+    }
+
+    pub async fn update_extension_layout(&mut self, extension_name: &str, layout: ExtensionLayout) -> OmniverlayResult<()> {
+        supervisor::call_update_layout(self.get_registered(extension_name)?, layout).await
+    }
+
+    pub fn get_extension_by_name(&self, name: &str) -> OmniverlayResult<Arc<Mutex<dyn Extension>>> {
+        Ok(self.get_registered(name)?.extension.clone())
+    }
+
+    /// Last payload published by an extension, see `DataChannel`
+    pub fn get_extension_data(&self, extension_name: &str) -> OmniverlayResult<Option<serde_json::Value>> {
+        self.get_registered(extension_name)?;
+
+        Ok(self.data_channel.last(extension_name))
+    }
+
+    pub fn get_extensions_data(&self) -> HashMap<String, serde_json::Value> {
+        self.data_channel.all()
+    }
+
+    /// Sample payloads of an extension instance for the layout editor, see
+    /// `Extension::preview_payloads`. A failing hook never marks the extension as failed.
+    pub async fn preview_payloads(&self, extension_name: &str) -> OmniverlayResult<Vec<serde_json::Value>> {
+        let registered = self.get_registered(extension_name)?;
+        let extension = registered.extension.clone();
+        let usage = registered.usage.clone();
+
+        let timed_out = &registered.timed_out;
+
+        let payloads = supervisor::run_guarded_for("preview_payloads", PREVIEW_TIMEOUT, timed_out, async move {
+            let mut extension = extension.lock_owned().await;
+
+            usage.measure(extension.preview_payloads()).await
+        })
+        .await?;
+
+        if !payloads.is_empty() {
+            return Ok(payloads);
+        }
+
+        let info = registered.info.lock().await;
+
+        Ok(info.manifest.as_ref().map(|manifest| manifest.preview.clone()).unwrap_or_default())
+    }
+
     pub async fn list_extensions(&self) -> OmniverlayResult<Vec<ExtensionInfo>> {
         let mut extension_infos = Vec::new();
-        for extension in self.extensions.values() {
-            let info = extension.lock().await.get_extension_info()?;
-            extension_infos.push(info.lock().await.clone());
+        for registered in self.extensions.values() {
+            extension_infos.push(registered.info.lock().await.clone());
         }
         Ok(extension_infos)
     }
@@ -0,0 +1,309 @@
+//! Extension packages, `.omnipkg` files.
+//!
+//! A package is a zip archive of an extension directory, see `ExtensionManager::load_extension_dir`:
+//!
+//! ```text
+//! extension.toml      manifest
+//! checksums.sha256    SHA-256 of every other file, in the format of `sha256sum`
+//! signature.json      optional, see `signing`
+//! my_extension.dll    the extension: a library, a .wasm, a .rhai or a .process.json and its program
+//! assets/             frontend assets
+//! ```
+//!
+//! `ExtensionPackage::pack` writes one from a directory. Installing is done by `installer`.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Write},
+    path::{Component, Path},
+};
+
+use ed25519_dalek::SigningKey;
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{
+    manifest::{ExtensionManifest, MANIFEST_FILE_NAME},
+    signing::{PackageSignature, TrustStatus, TrustedKeys, SIGNATURE_FILE_NAME},
+};
+
+pub const PACKAGE_EXTENSION: &str = "omnipkg";
+
+pub const CHECKSUMS_FILE_NAME: &str = "checksums.sha256";
+
+/// Directory of the frontend assets in a package
+pub const ASSETS_DIR_NAME: &str = "assets";
+
+/// Largest unpacked size accepted, protects the installer from zip bombs
+pub const MAX_UNPACKED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Mode of files stored without one
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// A package read into memory, its checksums checked
+pub struct ExtensionPackage {
+    pub manifest: ExtensionManifest,
+    /// Path inside the package with `/` separators, and contents
+    files: BTreeMap<String, PackageFile>,
+}
+
+struct PackageFile {
+    contents: Vec<u8>,
+    mode: Option<u32>,
+}
+
+fn invalid(path: &Path, message: impl std::fmt::Display) -> OmniverlayError {
+    OmniverlayError::InvalidPackage(format!("{}: {message}", path.display()))
+}
+
+fn sha256(contents: &[u8]) -> String {
+    hex::encode(Sha256::digest(contents))
+}
+
+/// Whether this host can load `name` as an extension, see `ExtensionManager::load_extension_dir`
+pub(super) fn is_extension_file(name: &str) -> bool {
+    let Some((_, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+
+    match extension {
+        std::env::consts::DLL_EXTENSION => true,
+        #[cfg(feature = "wasm")]
+        "wasm" => true,
+        #[cfg(feature = "scripting")]
+        "rhai" => true,
+        "json" => name.ends_with(".process.json"),
+        _ => false,
+    }
+}
+
+/// Parse `sha256sum` output, `./` prefixes and the `*` binary marker are accepted
+fn parse_checksums(checksums: &str) -> Option<BTreeMap<String, String>> {
+    checksums
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            let name = name.trim_start_matches([' ', '*']);
+            let name = name.strip_prefix("./").unwrap_or(name);
+
+            Some((name.to_string(), hash.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// Check `checksums`, the contents of a checksums file, against every other file of a package but
+/// its signature
+pub(super) fn check_checksums<'a>(
+    checksums: &[u8],
+    files: impl Iterator<Item = (&'a str, &'a [u8])>,
+) -> Result<(), String> {
+    let checksums = parse_checksums(&String::from_utf8_lossy(checksums))
+        .ok_or_else(|| format!("{CHECKSUMS_FILE_NAME} is malformed"))?;
+
+    let mut found = 0;
+
+    for (name, contents) in files.filter(|(name, _)| *name != CHECKSUMS_FILE_NAME && *name != SIGNATURE_FILE_NAME) {
+        match checksums.get(name) {
+            Some(hash) if *hash == sha256(contents) => found += 1,
+            Some(_) => return Err(format!("checksum of {name} does not match")),
+            None => return Err(format!("{name} is missing from {CHECKSUMS_FILE_NAME}")),
+        }
+    }
+
+    if found != checksums.len() {
+        return Err(format!("files listed in {CHECKSUMS_FILE_NAME} are missing"));
+    }
+
+    Ok(())
+}
+
+impl ExtensionPackage {
+    pub fn open(path: &Path) -> OmniverlayResult<Self> {
+        let mut archive = ZipArchive::new(File::open(path)?).map_err(|e| invalid(path, e))?;
+        let mut files = BTreeMap::new();
+        let mut unpacked_size = 0u64;
+
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(|e| invalid(path, e))?;
+
+            if entry.is_dir() {
+                continue;
+            }
+
+            let name = entry
+                .enclosed_name()
+                .filter(|name| name.components().all(|c| matches!(c, Component::Normal(_))))
+                .ok_or_else(|| invalid(path, format!("unsafe file name {:?}", entry.name())))?;
+
+            let name = name
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            // The size in the header can lie, what is actually read counts
+            let remaining = MAX_UNPACKED_SIZE - unpacked_size;
+
+            let mut contents = Vec::with_capacity(entry.size().min(remaining) as usize);
+            (&mut entry).take(remaining + 1).read_to_end(&mut contents)?;
+
+            unpacked_size += contents.len() as u64;
+
+            if unpacked_size > MAX_UNPACKED_SIZE {
+                return Err(invalid(path, format!("unpacks to more than {MAX_UNPACKED_SIZE} bytes")));
+            }
+
+            let mode = entry.unix_mode();
+
+            files.insert(name, PackageFile { contents, mode });
+        }
+
+        let manifest = files
+            .get(MANIFEST_FILE_NAME)
+            .ok_or_else(|| invalid(path, format!("no {MANIFEST_FILE_NAME}")))?;
+
+        let manifest = ExtensionManifest::from_toml(&String::from_utf8_lossy(&manifest.contents))
+            .map_err(|e| invalid(path, e))?;
+
+        let package = Self { manifest, files };
+
+        package.check_checksums().map_err(|message| invalid(path, message))?;
+
+        if !package.files.keys().any(|name| !name.contains('/') && is_extension_file(name)) {
+            return Err(invalid(path, "no extension this platform can load"));
+        }
+
+        Ok(package)
+    }
+
+    fn check_checksums(&self) -> Result<(), String> {
+        let checksums = self
+            .files
+            .get(CHECKSUMS_FILE_NAME)
+            .ok_or_else(|| format!("no {CHECKSUMS_FILE_NAME}"))?;
+
+        check_checksums(
+            &checksums.contents,
+            self.files.iter().map(|(name, file)| (name.as_str(), file.contents.as_slice())),
+        )
+    }
+
+    /// Whether the package is signed by one of `trusted_keys`
+    pub fn trust(&self, trusted_keys: &TrustedKeys) -> TrustStatus {
+        let checksums = self.files.get(CHECKSUMS_FILE_NAME).map(|file| file.contents.as_slice());
+        let signature = self.files.get(SIGNATURE_FILE_NAME).map(|file| file.contents.as_slice());
+
+        trusted_keys.verify(checksums.unwrap_or_default(), signature)
+    }
+
+    /// Files of the package, paths use `/` separators
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Write the files of the package into `dir`
+    pub(super) fn unpack(&self, dir: &Path) -> OmniverlayResult<()> {
+        std::fs::create_dir_all(dir)?;
+
+        for (name, file) in &self.files {
+            let path = dir.join(name);
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(&path, &file.contents)?;
+
+            #[cfg(unix)]
+            if let Some(mode) = file.mode {
+                use std::os::unix::fs::PermissionsExt;
+
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Package the extension directory `dir` into `output`, writing its checksums and signing them
+    /// with `signing_key` if given. Returns the manifest of the package.
+    pub fn pack(dir: &Path, output: &Path, signing_key: Option<&SigningKey>) -> OmniverlayResult<ExtensionManifest> {
+        let manifest = ExtensionManifest::from_file(&dir.join(MANIFEST_FILE_NAME))?;
+
+        let mut files = Vec::new();
+        collect_files(dir, dir, &mut files)?;
+        files.retain(|(name, _)| name != CHECKSUMS_FILE_NAME && name != SIGNATURE_FILE_NAME);
+        files.sort();
+
+        let mut writer = ZipWriter::new(File::create(output)?);
+        let mut checksums = String::new();
+
+        for (name, path) in &files {
+            let contents = std::fs::read(path)?;
+
+            checksums.push_str(&format!("{}  {}\n", sha256(&contents), name));
+
+            writer
+                .start_file(name.as_str(), SimpleFileOptions::default().unix_permissions(file_mode(path)?))
+                .map_err(|e| OmniverlayError::Other(e.into()))?;
+            writer.write_all(&contents)?;
+        }
+
+        writer
+            .start_file(CHECKSUMS_FILE_NAME, SimpleFileOptions::default().unix_permissions(DEFAULT_FILE_MODE))
+            .map_err(|e| OmniverlayError::Other(e.into()))?;
+        writer.write_all(checksums.as_bytes())?;
+
+        if let Some(signing_key) = signing_key {
+            let signature = PackageSignature::sign(checksums.as_bytes(), signing_key);
+
+            writer
+                .start_file(SIGNATURE_FILE_NAME, SimpleFileOptions::default().unix_permissions(DEFAULT_FILE_MODE))
+                .map_err(|e| OmniverlayError::Other(e.into()))?;
+            writer.write_all(&serde_json::to_vec_pretty(&signature)?)?;
+        }
+
+        writer.finish().map_err(|e| OmniverlayError::Other(e.into()))?;
+
+        Ok(manifest)
+    }
+}
+
+pub(super) fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, std::path::PathBuf)>) -> OmniverlayResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+
+        let name = path
+            .strip_prefix(root)
+            .map_err(|e| OmniverlayError::Other(e.into()))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        files.push((name, path));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Path) -> OmniverlayResult<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> OmniverlayResult<u32> {
+    Ok(DEFAULT_FILE_MODE)
+}
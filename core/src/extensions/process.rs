@@ -0,0 +1,511 @@
+//! Out-of-process extensions speaking JSON-RPC 2.0 over stdin/stdout.
+//!
+//! Every message is a single line of JSON, of at most `MAX_LINE_BYTES`: a longer line written by
+//! the process fails the extension. The host sends the following requests:
+//!
+//! - `initialize` with no params, answered with the `ExtensionInfo` of the extension
+//! - `enable` and `disable` with no params
+//! - `update_state` with an `ExtensionState` and `update_layout` with an `ExtensionLayout`
+//!
+//! The process may send a `log` notification with `{ "level": "info", "message": "..." }` and a
+//! `publish` notification whose params are sent to the overlay.
+//! Anything written to stderr is forwarded to the host's stderr. The process is killed when the
+//! extension is dropped, or as soon as it stops answering within the request timeout. A process
+//! that exited is reported to the supervisor, and started again on the next `enable`.
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::{oneshot, Mutex},
+};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{
+    host::ExtensionHost, instances::ExtensionFactory, Extension, ExtensionInfo, ExtensionLayout,
+    ExtensionState,
+};
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest message the process may write to stdout, line ending excluded
+pub const MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Read a line of the process without its line ending, `None` once its stdout is closed. Never
+/// buffers more than `MAX_LINE_BYTES`, the error is the reason the extension failed.
+async fn next_line<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut Vec<u8>) -> Result<Option<String>, String> {
+    line.clear();
+
+    let read = (&mut *reader)
+        .take(MAX_LINE_BYTES as u64 + 1)
+        .read_until(b'\n', line)
+        .await
+        .map_err(|e| format!("failed to read from extension: {e}"))?;
+
+    if read == 0 {
+        return Ok(None);
+    }
+
+    match line.last() {
+        Some(b'\n') => {
+            line.pop();
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        _ if line.len() > MAX_LINE_BYTES => {
+            return Err(format!("extension sent a line longer than {MAX_LINE_BYTES} bytes"));
+        }
+        // Last line, without a line ending
+        _ => {}
+    }
+
+    String::from_utf8(std::mem::take(line))
+        .map(Some)
+        .map_err(|_| "extension sent a line which is not valid UTF-8".to_string())
+}
+
+/// Describes how to start an out-of-process extension, read from `<name>.process.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessExtensionDescriptor {
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory, relative paths are resolved against the descriptor's directory
+    pub working_dir: Option<PathBuf>,
+    pub timeout_ms: Option<u64>,
+}
+
+impl ProcessExtensionDescriptor {
+    pub fn from_file(path: &Path) -> OmniverlayResult<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let mut descriptor: Self = serde_json::from_str(&json)?;
+
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        descriptor.working_dir = Some(match descriptor.working_dir {
+            Some(dir) if dir.is_relative() => base.join(dir),
+            Some(dir) => dir,
+            None => base.to_path_buf(),
+        });
+
+        Ok(descriptor)
+    }
+}
+
+/// Every instance of a process extension runs its own process
+#[async_trait]
+impl ExtensionFactory for ProcessExtensionDescriptor {
+    async fn create(&self) -> OmniverlayResult<Arc<Mutex<dyn Extension>>> {
+        Ok(Arc::new(Mutex::new(ProcessExtension::from_descriptor(self).await?)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcMessage {
+    id: Option<u64>,
+    method: Option<String>,
+    params: Option<Value>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+
+// Set after the process is started, read by the task reading its output
+type SharedHost = Arc<std::sync::Mutex<Option<ExtensionHost>>>;
+
+// What is needed to start the process again, `tokio::process::Command` is not `Clone`
+struct Launch {
+    program: OsString,
+    args: Vec<OsString>,
+    working_dir: Option<PathBuf>,
+}
+
+impl Launch {
+    fn from_command(command: &Command) -> Self {
+        let command = command.as_std();
+
+        Self {
+            program: command.get_program().to_os_string(),
+            args: command.get_args().map(|arg| arg.to_os_string()).collect(),
+            working_dir: command.get_current_dir().map(Path::to_path_buf),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+
+        command
+    }
+}
+
+pub struct ProcessExtension {
+    info: Arc<Mutex<ExtensionInfo>>,
+    launch: Launch,
+    child: Child,
+    stdin: ChildStdin,
+    pending: PendingRequests,
+    next_id: u64,
+    timeout: Duration,
+    // Set once the process crashed or stopped answering, every later call fails fast
+    failure: Arc<Mutex<Option<String>>>,
+    // Bumped whenever the process is replaced on purpose, so its exit is not reported as a failure
+    generation: Arc<AtomicU64>,
+    host: SharedHost,
+}
+
+impl ProcessExtension {
+    pub async fn from_descriptor(descriptor: &ProcessExtensionDescriptor) -> OmniverlayResult<Self> {
+        let mut command = Command::new(&descriptor.command);
+        command.args(&descriptor.args);
+
+        if let Some(dir) = &descriptor.working_dir {
+            command.current_dir(dir);
+        }
+
+        let timeout = descriptor
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT);
+
+        Self::spawn(command, timeout).await
+    }
+
+    pub async fn spawn(command: Command, timeout: Duration) -> OmniverlayResult<Self> {
+        let launch = Launch::from_command(&command);
+        let info = Arc::new(Mutex::new(ExtensionInfo::default()));
+        let generation = Arc::new(AtomicU64::new(0));
+        let host = SharedHost::default();
+
+        let (child, stdin, pending, failure) = Self::start(command, generation.clone(), host.clone())?;
+
+        let mut extension = Self {
+            info,
+            launch,
+            child,
+            stdin,
+            pending,
+            next_id: 1,
+            timeout,
+            failure,
+            generation,
+            host,
+        };
+
+        let info: ExtensionInfo = extension
+            .request("initialize", Value::Null)
+            .await
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(e.to_string()))?;
+
+        *extension.info.lock().await = info;
+
+        Ok(extension)
+    }
+
+    fn start(
+        mut command: Command,
+        generation: Arc<AtomicU64>,
+        host: SharedHost,
+    ) -> OmniverlayResult<(Child, ChildStdin, PendingRequests, Arc<Mutex<Option<String>>>)> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("Failed to spawn {:?}: {e}", command.as_std().get_program())))?;
+
+        let stdin = child.stdin.take().ok_or_else(|| {
+            OmniverlayError::ExtensionLoadFailed("Failed to open the extension stdin".to_string())
+        })?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            OmniverlayError::ExtensionLoadFailed("Failed to open the extension stdout".to_string())
+        })?;
+
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let failure = Arc::new(Mutex::new(None));
+
+        {
+            let pending = pending.clone();
+            let failure = failure.clone();
+            let current_generation = generation.load(Ordering::SeqCst);
+
+            tokio::spawn(async move {
+                let mut stdout = BufReader::new(stdout);
+                let mut buffer = Vec::new();
+
+                let reason = loop {
+                    match next_line(&mut stdout, &mut buffer).await {
+                        Ok(Some(line)) => Self::handle_line(&pending, &host, &line).await,
+                        Ok(None) => break "extension process exited".to_string(),
+                        Err(reason) => break reason,
+                    }
+                };
+
+                failure.lock().await.get_or_insert_with(|| reason.clone());
+
+                // Dropping the senders wakes up every caller still waiting for an answer
+                pending.lock().await.clear();
+
+                // Not set yet when the process died before answering `initialize`
+                let host = host.lock().unwrap().clone();
+
+                if let Some(host) = host.filter(|_| generation.load(Ordering::SeqCst) == current_generation) {
+                    host.report_failure(OmniverlayError::ExtensionRuntime(reason));
+                }
+            });
+        }
+
+        Ok((child, stdin, pending, failure))
+    }
+
+    /// Start a new process after the previous one failed, it gets the current state and layout
+    async fn restart(&mut self) -> OmniverlayResult<()> {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let _ = self.child.start_kill();
+
+        let (child, stdin, pending, failure) =
+            Self::start(
+                self.launch.command(),
+                self.generation.clone(),
+                self.host.clone(),
+            )
+                .map_err(|e| OmniverlayError::ExtensionRuntime(e.to_string()))?;
+
+        self.child = child;
+        self.stdin = stdin;
+        self.pending = pending;
+        self.failure = failure;
+
+        let (state, layout) = {
+            let info = self.info.lock().await;
+            (info.state.clone(), info.layout.clone())
+        };
+
+        self.request::<ExtensionInfo>("initialize", Value::Null).await?;
+        self.request::<Value>("update_state", serde_json::to_value(&state)?).await?;
+
+        if let Some(layout) = layout {
+            self.request::<Value>("update_layout", serde_json::to_value(&layout)?).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_line(pending: &PendingRequests, host: &SharedHost, line: &str) {
+        let message: RpcMessage = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Ignoring invalid message from extension: {e}");
+                return;
+            }
+        };
+
+        match (message.id, message.method.as_deref()) {
+            (Some(id), None) => {
+                let Some(sender) = pending.lock().await.remove(&id) else {
+                    warn!("Received a response for unknown request {id}");
+                    return;
+                };
+
+                let result = match message.error {
+                    Some(error) => Err(format!("{} (code {})", error.message, error.code)),
+                    None => Ok(message.result.unwrap_or(Value::Null)),
+                };
+
+                let _ = sender.send(result);
+            }
+            (_, Some("log")) => {
+                let params = message.params.unwrap_or(Value::Null);
+                let level = params["level"].as_str().unwrap_or("info");
+                let text = params["message"].as_str().unwrap_or_default();
+
+                match level {
+                    "error" => error!("{text}"),
+                    "warn" => warn!("{text}"),
+                    "debug" => debug!("{text}"),
+                    _ => info!("{text}"),
+                }
+            }
+            (_, Some("publish")) => {
+                if let Some(host) = host.lock().unwrap().as_ref() {
+                    host.publish_value(message.params.unwrap_or(Value::Null));
+                }
+            }
+            (_, Some(method)) => warn!("Ignoring unsupported method {method} from extension"),
+            (None, None) => warn!("Ignoring message without id nor method from extension"),
+        }
+    }
+
+    async fn fail(&mut self, reason: String) -> OmniverlayError {
+        error!("Extension process failed: {reason}");
+
+        *self.failure.lock().await = Some(reason.clone());
+
+        let _ = self.child.start_kill();
+
+        OmniverlayError::ExtensionRuntime(reason)
+    }
+
+    pub async fn request<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> OmniverlayResult<T> {
+        if let Some(reason) = self.failure.lock().await.clone() {
+            return Err(OmniverlayError::ExtensionRuntime(reason));
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let (sender, receiver) = oneshot::channel();
+
+        self.pending.lock().await.insert(id, sender);
+
+        let mut message = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        message.push(b'\n');
+
+        if let Err(e) = self.stdin.write_all(&message).await {
+            return Err(self.fail(format!("failed to write to extension: {e}")).await);
+        }
+        if let Err(e) = self.stdin.flush().await {
+            return Err(self.fail(format!("failed to write to extension: {e}")).await);
+        }
+
+        let result = match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => {
+                let reason = self
+                    .failure
+                    .lock()
+                    .await
+                    .clone()
+                    .unwrap_or_else(|| "extension process exited".to_string());
+
+                return Err(self.fail(reason).await);
+            }
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+
+                return Err(self.fail(format!("{method} timed out after {:?}", self.timeout)).await);
+            }
+        };
+
+        let value = result.map_err(|e| OmniverlayError::ExtensionRuntime(format!("{method} failed: {e}")))?;
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl Drop for ProcessExtension {
+    fn drop(&mut self) {
+        // The process is killed on drop, which is not a failure
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl Extension for ProcessExtension {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        if self.failure.lock().await.is_some() {
+            self.restart().await?;
+        }
+
+        self.request::<Value>("enable", Value::Null).await?;
+
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        self.request::<Value>("disable", Value::Null).await?;
+
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        *self.host.lock().unwrap() = Some(host);
+    }
+
+    // The host side copy is updated first so a failed process still reflects the user's choices
+    async fn update_layout(&mut self, layout: ExtensionLayout) -> OmniverlayResult<()> {
+        self.info.lock().await.layout = Some(layout.clone());
+
+        self.request::<Value>("update_layout", serde_json::to_value(&layout)?).await?;
+
+        Ok(())
+    }
+
+    async fn update_state(&mut self, new_state: ExtensionState) -> OmniverlayResult<()> {
+        self.info.lock().await.state = new_state.clone();
+
+        self.request::<Value>("update_state", serde_json::to_value(&new_state)?).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lines_are_read_without_their_line_ending() {
+        let mut output: &[u8] = b"{\"a\":1}\r\n{\"b\":2}\n{\"c\":3}";
+        let mut buffer = Vec::new();
+
+        assert_eq!(next_line(&mut output, &mut buffer).await.unwrap().unwrap(), "{\"a\":1}");
+        assert_eq!(next_line(&mut output, &mut buffer).await.unwrap().unwrap(), "{\"b\":2}");
+        assert_eq!(next_line(&mut output, &mut buffer).await.unwrap().unwrap(), "{\"c\":3}");
+        assert_eq!(next_line(&mut output, &mut buffer).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn too_long_lines_are_refused() {
+        let mut line = vec![b'a'; MAX_LINE_BYTES];
+        line.push(b'\n');
+
+        let mut output = line.as_slice();
+        assert_eq!(next_line(&mut output, &mut Vec::new()).await.unwrap().unwrap().len(), MAX_LINE_BYTES);
+
+        line.insert(0, b'a');
+
+        let mut output = line.as_slice();
+        let error = next_line(&mut output, &mut Vec::new()).await.unwrap_err();
+
+        assert!(error.contains("longer than"), "{error}");
+    }
+}
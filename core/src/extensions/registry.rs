@@ -0,0 +1,27 @@
+//! Extensions compiled into the app register themselves at link time with
+//! `#[omniverlay_extension]`, the host picks them up with
+//! `ExtensionManager::register_compiled_extensions` instead of listing them by hand.
+//!
+//! An extension crate is only linked when the app uses it, add `use my_extension as _;` to the
+//! app for crates it does not reference otherwise.
+
+use std::sync::Arc;
+
+use super::instances::ExtensionFactory;
+
+pub struct CompiledExtension {
+    /// Rust type of the extension, only used to order registrations and in logs
+    pub type_name: &'static str,
+    pub factory: fn() -> Arc<dyn ExtensionFactory>,
+}
+
+inventory::collect!(CompiledExtension);
+
+/// Every extension registered with `#[omniverlay_extension]`, sorted by type name
+pub fn compiled_extensions() -> Vec<&'static CompiledExtension> {
+    let mut extensions: Vec<_> = inventory::iter::<CompiledExtension>.into_iter().collect();
+
+    extensions.sort_by_key(|extension| extension.type_name);
+
+    extensions
+}
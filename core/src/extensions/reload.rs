@@ -0,0 +1,84 @@
+//! Hot reload of loaded extensions, for development.
+//!
+//! `watch` follows the files returned by `ExtensionManager::watched_files` and reloads an
+//! extension with `ExtensionManager::reload_extensions_at` once its library, script, process
+//! descriptor or manifest stops changing for `RELOAD_DEBOUNCE`. Compiled-in extensions cannot be
+//! reloaded, and extensions added to the extensions directory while the app runs are not picked up.
+
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+
+use log::{error, info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{mpsc, RwLock};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{manifest::MANIFEST_FILE_NAME, ExtensionManager};
+
+/// Quiet time after the last change before reloading, builds write their output in several steps
+pub const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Start reloading the extensions of `manager` when their files change, until the app exits
+pub async fn watch(manager: Arc<RwLock<ExtensionManager>>) -> OmniverlayResult<()> {
+    let files: HashSet<PathBuf> = manager.read().await.watched_files().into_iter().collect();
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let watched = files.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            for path in event.paths.into_iter().filter(|path| watched.contains(path)) {
+                let _ = sender.send(path);
+            }
+        }
+        Err(e) => warn!("Failed to watch extension files: {}", e),
+    })
+    .map_err(|e| OmniverlayError::Other(e.into()))?;
+
+    // Directories are watched rather than files, builds and editors often replace a file instead of writing it
+    let dirs: HashSet<PathBuf> = files.iter().filter_map(|file| file.parent().map(PathBuf::from)).collect();
+
+    for dir in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| OmniverlayError::Other(e.into()))?;
+    }
+
+    info!("Watching {} extension files for changes", files.len());
+
+    tokio::spawn(async move {
+        // Stops the watcher when the task ends
+        let _watcher: RecommendedWatcher = watcher;
+
+        while let Some(path) = receiver.recv().await {
+            let mut changed = HashSet::from([path]);
+
+            while let Ok(Some(path)) = tokio::time::timeout(RELOAD_DEBOUNCE, receiver.recv()).await {
+                changed.insert(path);
+            }
+
+            // A manifest reloads every extension next to it
+            let manifest_dirs: HashSet<PathBuf> = changed
+                .iter()
+                .filter(|path| path.file_name().is_some_and(|name| name == MANIFEST_FILE_NAME))
+                .filter_map(|path| path.parent().map(PathBuf::from))
+                .collect();
+
+            changed.retain(|path| {
+                path.file_name().is_some_and(|name| name == MANIFEST_FILE_NAME)
+                    || !path.parent().is_some_and(|dir| manifest_dirs.contains(dir))
+            });
+
+            let mut manager = manager.write().await;
+
+            for path in changed {
+                match manager.reload_extensions_at(&path).await {
+                    Ok(names) => info!("Reloaded {} after {} changed", names.join(", "), path.display()),
+                    Err(e) => error!("Failed to reload {}: {}", path.display(), e),
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Drives `Extension::on_tick` for every running extension of an `ExtensionManager` from a single
+//! task, started on the runtime of the first extension to tick and started again if that runtime
+//! shuts down.
+//!
+//! A tick that is due while the previous one is still running is skipped rather than queued,
+//! and every tick is delayed by a small random jitter so extensions sharing an interval do not
+//! all wake up at once.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::debug;
+use rand::Rng;
+use tokio::{sync::Notify, task::JoinHandle, time::Instant};
+
+use super::{supervisor, RegisteredExtension};
+
+/// Upper bound of the jitter, which is otherwise a tenth of the interval
+const MAX_JITTER: Duration = Duration::from_millis(100);
+
+/// Shortest interval accepted, protects the scheduler from extensions asking for 0
+const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct TickEntry {
+    // Tells a tick waiting for the extension lock whether it is still wanted
+    id: u64,
+    registered: RegisteredExtension,
+    interval: Duration,
+    due: Instant,
+    fire_at: Instant,
+    in_flight: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    // Keyed by instance name
+    entries: std::sync::Mutex<HashMap<String, TickEntry>>,
+    wake: Notify,
+    task: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Ticks of the extensions of one `ExtensionManager`, shared with their `RegisteredExtension`
+#[derive(Clone, Default)]
+pub(super) struct Scheduler(Arc<SchedulerState>);
+
+fn jitter(interval: Duration) -> Duration {
+    let max = (interval / 10).min(MAX_JITTER);
+
+    rand::thread_rng().gen_range(Duration::ZERO..=max)
+}
+
+impl Scheduler {
+    fn is_scheduled(&self, extension_name: &str, id: u64) -> bool {
+        let entries = self.0.entries.lock().unwrap();
+
+        entries.get(extension_name).is_some_and(|entry| entry.id == id)
+    }
+
+    /// Start ticking an extension that just started running, if it has a `tick_interval`
+    pub async fn start_ticking(&self, extension_name: &str, registered: &RegisteredExtension) {
+        let Some(interval) = registered.extension.lock().await.tick_interval() else {
+            return;
+        };
+
+        let interval = interval.max(MIN_INTERVAL);
+        let due = Instant::now() + interval;
+
+        {
+            let mut task = self.0.task.lock().unwrap();
+
+            // The task ends with the runtime it was spawned on
+            if task.as_ref().is_none_or(JoinHandle::is_finished) {
+                *task = Some(tokio::spawn(self.clone().run()));
+            }
+        }
+
+        self.0.entries.lock().unwrap().insert(
+            extension_name.to_string(),
+            TickEntry {
+                id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+                registered: registered.clone(),
+                interval,
+                due,
+                fire_at: due + jitter(interval),
+                in_flight: Arc::new(AtomicBool::new(false)),
+            },
+        );
+
+        self.0.wake.notify_one();
+    }
+
+    /// Stop ticking an extension, a tick already running is left to finish
+    pub fn stop_ticking(&self, extension_name: &str) {
+        if self.0.entries.lock().unwrap().remove(extension_name).is_some() {
+            self.0.wake.notify_one();
+        }
+    }
+
+    /// Stop every tick and the task, called when the manager is dropped. Entries hold their
+    /// extension, which holds the scheduler.
+    pub fn shutdown(&self) {
+        self.0.entries.lock().unwrap().clear();
+
+        if let Some(task) = self.0.task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    async fn run(self) {
+        loop {
+            let next = self.0.entries.lock().unwrap().values().map(|entry| entry.fire_at).min();
+
+            match next {
+                Some(next) => tokio::select! {
+                    _ = self.0.wake.notified() => continue,
+                    _ = tokio::time::sleep_until(next) => {}
+                },
+                None => {
+                    self.0.wake.notified().await;
+                    continue;
+                }
+            }
+
+            let now = Instant::now();
+            let mut entries = self.0.entries.lock().unwrap();
+
+            for (name, entry) in entries.iter_mut().filter(|(_, entry)| entry.fire_at <= now) {
+                if entry.in_flight.swap(true, Ordering::SeqCst) {
+                    debug!("Skipping tick of extension {}: the previous tick is still running", name);
+                } else {
+                    self.spawn_tick(name.clone(), entry);
+                }
+
+                entry.due += entry.interval;
+
+                // Ticks missed while the host was busy are dropped, not caught up
+                if entry.due <= now {
+                    entry.due = now + entry.interval;
+                }
+
+                entry.fire_at = entry.due + jitter(entry.interval);
+            }
+        }
+    }
+
+    fn spawn_tick(&self, extension_name: String, entry: &TickEntry) {
+        let registered = entry.registered.clone();
+        let in_flight = entry.in_flight.clone();
+        let id = entry.id;
+        let scheduler = self.clone();
+
+        tokio::spawn(async move {
+            let extension = registered.extension.clone();
+            let usage = registered.usage.clone();
+            let name = extension_name.clone();
+
+            let timed_out = registered.timed_out.clone();
+
+            let result = supervisor::run_guarded("on_tick", &timed_out, async move {
+                let mut extension = extension.lock_owned().await;
+
+                // Disabled while waiting for the lock
+                if !scheduler.is_scheduled(&name, id) {
+                    return Ok(());
+                }
+
+                usage.measure(extension.on_tick()).await
+            })
+            .await;
+
+            in_flight.store(false, Ordering::SeqCst);
+
+            if let Err(e) = result {
+                supervisor::fail_running(extension_name, registered, &e).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,391 @@
+//! Extensions written as Rhai scripts.
+//!
+//! A script must define `info()` returning a map with at least a `name`. It may also contain a
+//! `width`, `height`, `x` and `y` for the default layout and an `interval` in milliseconds.
+//!
+//! ```rhai
+//! fn info() {
+//!     #{ name: "Clock", width: 200, height: 80, interval: 1000 }
+//! }
+//!
+//! fn config() {
+//!     config_builder()
+//!         .add_category(category("General")
+//!             .add_value("label", "Text shown before the time", "Time:")
+//!             .add_choice("format", "Clock format", "24h", ["12h", "24h"]))
+//!         .build()
+//! }
+//!
+//! fn on_enable(config) {}
+//! fn on_disable() {}
+//!
+//! // Called every `interval`, the returned value is published to the overlay
+//! fn on_tick(config) {
+//!     #{ label: config.label, uptime: timestamp().elapsed }
+//! }
+//!
+//! // Sample values like the ones `on_tick` returns, shown by the layout editor
+//! fn preview(config) {
+//!     [#{ label: config.label, uptime: 3600 }]
+//! }
+//! ```
+//!
+//! Every hook is optional and may take the current config values as its only parameter. `on_tick`
+//! is driven by the core scheduler, an error in it is handled by the supervisor like any other
+//! failure. Hooks run on a blocking thread and are terminated once they run for longer than
+//! `LIFECYCLE_TIMEOUT`.
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use log::{debug, info};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use tokio::sync::Mutex;
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{
+    config::{
+        ConfigCategoryBuilder, ConfigEnum, ConfigValue, ConfigValueType,
+        ExtensionConfig, ExtensionConfigBuilder,
+    },
+    host::ExtensionHost,
+    Extension, ExtensionInfo, ExtensionLayout, ExtensionState, LIFECYCLE_TIMEOUT,
+};
+
+/// Upper bound of operations a single hook call may run, stops runaway loops
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+type Deadline = Arc<std::sync::Mutex<Instant>>;
+
+fn script_error(e: impl std::fmt::Display) -> OmniverlayError {
+    OmniverlayError::ExtensionRuntime(e.to_string())
+}
+
+fn to_config_value_type(value: Dynamic) -> Result<ConfigValueType, String> {
+    let type_name = value.type_name();
+
+    if value.is_string() {
+        Ok(ConfigValueType::String(value.into_string()?))
+    } else if value.is_int() {
+        Ok(ConfigValueType::Int(value.as_int()?))
+    } else if value.is_float() {
+        Ok(ConfigValueType::Float(value.as_float()?))
+    } else if value.is_bool() {
+        Ok(ConfigValueType::Bool(value.as_bool()?))
+    } else if value.is_array() {
+        value
+            .into_array()?
+            .into_iter()
+            .map(to_config_value_type)
+            .collect::<Result<Vec<_>, _>>()
+            .map(ConfigValueType::List)
+    } else {
+        Err(format!("{type_name} cannot be used as a config value"))
+    }
+}
+
+fn to_dynamic(value: &ConfigValueType) -> Dynamic {
+    match value {
+        ConfigValueType::String(value) | ConfigValueType::Path(value) => value.clone().into(),
+        ConfigValueType::Float(value) => (*value).into(),
+        ConfigValueType::Int(value) => (*value).into(),
+        ConfigValueType::Bool(value) => (*value).into(),
+        ConfigValueType::List(values) => values.iter().map(to_dynamic).collect::<Array>().into(),
+        ConfigValueType::Enum(value) => value.current.clone().into(),
+    }
+}
+
+fn config_values(config: Option<&ExtensionConfig>) -> Map {
+    let mut values = Map::new();
+
+    for category in config.map(|c| c.categories.iter()).into_iter().flatten() {
+        for (name, value) in &category.values {
+            values.insert(name.into(), to_dynamic(&value.value));
+        }
+    }
+
+    values
+}
+
+fn create_engine(deadline: Deadline) -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(MAX_OPERATIONS);
+    // Some functions are slow on their own, the operation count alone does not bound the time
+    engine.on_progress(move |_| (Instant::now() >= *deadline.lock().unwrap()).then_some(Dynamic::UNIT));
+    engine.on_print(|text| info!("{text}"));
+    engine.on_debug(|text, _, position| debug!("{position:?} {text}"));
+
+    engine
+        .register_type_with_name::<ExtensionConfigBuilder>("ExtensionConfigBuilder")
+        .register_type_with_name::<ConfigCategoryBuilder>("ConfigCategoryBuilder")
+        .register_type_with_name::<ExtensionConfig>("ExtensionConfig")
+        .register_fn("config_builder", ExtensionConfigBuilder::new)
+        .register_fn("category", |name: &str| ConfigCategoryBuilder::new(name.to_string()))
+        .register_fn(
+            "add_category",
+            |builder: &mut ExtensionConfigBuilder, category: ConfigCategoryBuilder| {
+                builder.clone().add_category(category.build())
+            },
+        )
+        .register_fn("build", |builder: &mut ExtensionConfigBuilder| builder.clone().build())
+        .register_fn(
+            "add_value",
+            |builder: &mut ConfigCategoryBuilder, name: &str, description: &str, value: Dynamic| {
+                let value = to_config_value_type(value)?;
+
+                Ok::<_, Box<EvalAltResult>>(builder.clone().add_value(
+                    name.to_string(),
+                    ConfigValue::new(description.to_string(), value),
+                ))
+            },
+        )
+        .register_fn(
+            "add_choice",
+            |builder: &mut ConfigCategoryBuilder, name: &str, description: &str, current: &str, values: Array| {
+                let values = values
+                    .into_iter()
+                    .map(|value| value.into_string())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok::<_, Box<EvalAltResult>>(builder.clone().add_value(
+                    name.to_string(),
+                    ConfigValue::new(
+                        description.to_string(),
+                        ConfigValueType::Enum(ConfigEnum::new(name.to_string(), current.to_string(), values)),
+                    ),
+                ))
+            },
+        )
+        .register_fn(
+            "add_path",
+            |builder: &mut ConfigCategoryBuilder, name: &str, description: &str, path: &str| {
+                builder.clone().add_value(
+                    name.to_string(),
+                    ConfigValue::new(description.to_string(), ConfigValueType::Path(path.to_string())),
+                )
+            },
+        );
+
+    engine
+}
+
+struct Script {
+    engine: Engine,
+    ast: AST,
+    deadline: Deadline,
+    // A call left running by a timed out hook ends at its deadline before the next one starts
+    running: std::sync::Mutex<()>,
+}
+
+impl Script {
+    fn has_fn(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    /// Call an optional hook, passing the config values when the hook accepts them
+    fn call_hook(&self, name: &str, config: Option<&ExtensionConfig>) -> OmniverlayResult<Dynamic> {
+        let Some(params) = self
+            .ast
+            .iter_functions()
+            .find(|f| f.name == name)
+            .map(|f| f.params.len())
+        else {
+            return Ok(Dynamic::UNIT);
+        };
+
+        let _running = self.running.lock().unwrap();
+
+        *self.deadline.lock().unwrap() = Instant::now() + LIFECYCLE_TIMEOUT;
+
+        let mut scope = Scope::new();
+
+        let result = match params {
+            0 => self.engine.call_fn::<Dynamic>(&mut scope, &self.ast, name, ()),
+            _ => self
+                .engine
+                .call_fn::<Dynamic>(&mut scope, &self.ast, name, (config_values(config),)),
+        };
+
+        result.map_err(|e| match *e {
+            EvalAltResult::ErrorTerminated(..) => {
+                script_error(format!("{name} did not return within {LIFECYCLE_TIMEOUT:?}"))
+            }
+            e => script_error(format!("{name}: {e}")),
+        })
+    }
+}
+
+pub struct ScriptExtension {
+    info: Arc<Mutex<ExtensionInfo>>,
+    script: Arc<Script>,
+    interval: Option<Duration>,
+    host: Option<ExtensionHost>,
+}
+
+impl ScriptExtension {
+    pub fn from_file(path: &Path) -> OmniverlayResult<Self> {
+        let source = std::fs::read_to_string(path)?;
+
+        Self::from_source(&source)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{}: {e}", path.display())))
+    }
+
+    pub fn from_source(source: &str) -> OmniverlayResult<Self> {
+        let deadline = Arc::new(std::sync::Mutex::new(Instant::now()));
+
+        let engine = create_engine(deadline.clone());
+        let ast = engine.compile(source).map_err(script_error)?;
+
+        let script = Script {
+            engine,
+            ast,
+            deadline,
+            running: std::sync::Mutex::new(()),
+        };
+
+        if !script.has_fn("info") {
+            return Err(script_error("script does not define `fn info()`"));
+        }
+
+        let declared: Map = script
+            .call_hook("info", None)?
+            .try_cast()
+            .ok_or_else(|| script_error("`info()` must return a map"))?;
+
+        let get_int = |key: &str| declared.get(key).and_then(|v| v.as_int().ok());
+
+        // Sizes and positions of the layout, a script error when negative or too large
+        let get_u32 = |key: &str| {
+            get_int(key)
+                .map(|value| {
+                    u32::try_from(value).map_err(|_| {
+                        script_error(format!("`{key}` in `info()` must be between 0 and {}, got {value}", u32::MAX))
+                    })
+                })
+                .transpose()
+        };
+
+        let name = declared
+            .get("name")
+            .and_then(|v| v.clone().into_string().ok())
+            .ok_or_else(|| script_error("`info()` must contain a `name`"))?;
+
+        let layout = match (get_u32("width")?, get_u32("height")?) {
+            (Some(width), Some(height)) => Some(ExtensionLayout {
+                width,
+                height,
+                x: get_u32("x")?.unwrap_or(0),
+                y: get_u32("y")?.unwrap_or(0),
+            }),
+            _ => None,
+        };
+
+        let interval = get_int("interval")
+            .filter(|interval| *interval > 0)
+            .map(|interval| Duration::from_millis(interval as u64));
+
+        let config = if script.has_fn("config") {
+            let config = script
+                .call_hook("config", None)?
+                .try_cast::<ExtensionConfig>()
+                .ok_or_else(|| script_error("`config()` must return `config_builder().build()`"))?;
+
+            Some(config)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name,
+                state: ExtensionState {
+                    config,
+                    ..Default::default()
+                },
+                layout,
+                ..Default::default()
+            })),
+            script: Arc::new(script),
+            interval,
+            host: None,
+        })
+    }
+
+    /// Call a hook with the current config values on a blocking thread, a script never yields
+    /// so running it on the runtime would keep the supervisor from timing it out
+    async fn call_hook(&self, name: &'static str) -> OmniverlayResult<Dynamic> {
+        let config = self.info.lock().await.state.config.clone();
+        let script = self.script.clone();
+
+        tokio::task::spawn_blocking(move || script.call_hook(name, config.as_ref()))
+            .await
+            .map_err(script_error)?
+    }
+}
+
+#[async_trait]
+impl Extension for ScriptExtension {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        let _ = self.call_hook("on_enable").await?;
+
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        let _ = self.call_hook("on_disable").await?;
+
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.interval.filter(|_| self.script.has_fn("on_tick"))
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        let data = self.call_hook("on_tick").await?;
+
+        if data.is_unit() {
+            return Ok(());
+        }
+
+        let data = rhai::serde::from_dynamic::<serde_json::Value>(&data)
+            .map_err(|e| script_error(format!("on_tick returned invalid data: {e}")))?;
+
+        if let Some(host) = &self.host {
+            host.publish_value(data);
+        }
+
+        Ok(())
+    }
+
+    async fn preview_payloads(&mut self) -> OmniverlayResult<Vec<serde_json::Value>> {
+        let preview = self.call_hook("preview").await?;
+
+        if preview.is_unit() {
+            return Ok(Vec::new());
+        }
+
+        // A single value is accepted too
+        let preview = match preview.is_array() {
+            true => preview,
+            false => Dynamic::from_array(vec![preview]),
+        };
+
+        rhai::serde::from_dynamic::<Vec<serde_json::Value>>(&preview)
+            .map_err(|e| script_error(format!("preview returned invalid data: {e}")))
+    }
+}
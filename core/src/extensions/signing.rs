@@ -0,0 +1,291 @@
+//! Signed extension packages.
+//!
+//! A package is signed by adding a `signature.json` next to its `checksums.sha256`, holding the
+//! ed25519 signature of the checksums and the public key that made it, both in hex. Since the
+//! checksums cover every other file, the signature covers the whole package.
+//!
+//! Keys are trusted by listing them in `~/.omniverlay/trusted_keys`, one per line with an optional
+//! name:
+//!
+//! ```text
+//! # Public keys whose packages are trusted
+//! 3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c  Team tools
+//! ```
+//!
+//! The files of an installed package are checked again whenever it is loaded, so a file modified
+//! or added after signing makes it invalid. `SigningPolicy::require_signed` refuses anything that
+//! is not trusted.
+
+use std::path::Path;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    settings::OmniverlaySettings,
+    utils::fs::get_omniverlay_dir,
+};
+
+use super::{
+    manifest::MANIFEST_FILE_NAME,
+    package::{self, ExtensionPackage, CHECKSUMS_FILE_NAME},
+};
+
+pub const SIGNATURE_FILE_NAME: &str = "signature.json";
+
+pub const TRUSTED_KEYS_FILE_NAME: &str = "trusted_keys";
+
+/// Contents of `signature.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSignature {
+    pub key: String,
+    pub signature: String,
+}
+
+impl PackageSignature {
+    pub fn sign(checksums: &[u8], key: &SigningKey) -> Self {
+        Self {
+            key: hex::encode(key.verifying_key().as_bytes()),
+            signature: hex::encode(key.sign(checksums).to_bytes()),
+        }
+    }
+}
+
+/// Whether the files of an extension come from someone the user trusts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "trust", rename_all = "snake_case")]
+pub enum TrustStatus {
+    /// Signed by a key of the trusted keys file, named `signer` there
+    Trusted { signer: String },
+    /// Not signed when `key` is `None`, otherwise signed by a key that is not trusted
+    Untrusted { key: Option<String> },
+    /// Modified after it was signed
+    Invalid { error: String },
+}
+
+impl TrustStatus {
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Trusted { .. })
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Trusted { signer } => format!("it is signed by {signer}"),
+            Self::Untrusted { key: None } => "it is not signed".to_string(),
+            Self::Untrusted { key: Some(key) } => format!("it is signed by {key}, which is not a trusted key"),
+            Self::Invalid { error } => format!("its signature is invalid: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrustedKeys {
+    keys: Vec<(VerifyingKey, String)>,
+}
+
+impl TrustedKeys {
+    /// Keys of `~/.omniverlay/trusted_keys`, none if the file does not exist
+    pub fn load() -> OmniverlayResult<Self> {
+        let path = get_omniverlay_dir()?.join(TRUSTED_KEYS_FILE_NAME);
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Self::parse(&std::fs::read_to_string(&path)?)
+            .map_err(|e| OmniverlayError::SerializationError(format!("{}: {e}", path.display())))
+    }
+
+    pub fn parse(keys: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+
+        for (number, line) in keys.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+            let key = parse_key(key).map_err(|e| format!("line {}: {e}", number + 1))?;
+            let name = match name.trim() {
+                "" => hex::encode(key.as_bytes()),
+                name => name.to_string(),
+            };
+
+            parsed.keys.push((key, name));
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn add(&mut self, key: VerifyingKey, name: &str) {
+        self.keys.push((key, name.to_string()));
+    }
+
+    fn name_of(&self, key: &VerifyingKey) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(trusted, _)| trusted == key)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Trust of `checksums` given the contents of the signature file, `None` when unsigned
+    pub fn verify(&self, checksums: &[u8], signature: Option<&[u8]>) -> TrustStatus {
+        let Some(signature) = signature else {
+            return TrustStatus::Untrusted { key: None };
+        };
+
+        let signature: PackageSignature = match serde_json::from_slice(signature) {
+            Ok(signature) => signature,
+            Err(e) => return TrustStatus::Invalid { error: format!("{SIGNATURE_FILE_NAME}: {e}") },
+        };
+
+        let verified = parse_key(&signature.key).and_then(|key| {
+            let bytes: [u8; 64] = hex::decode(&signature.signature)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| "malformed signature".to_string())?;
+
+            key.verify(checksums, &Signature::from_bytes(&bytes))
+                .map_err(|_| "the signature does not match the checksums".to_string())?;
+
+            Ok(key)
+        });
+
+        match verified {
+            Ok(key) => match self.name_of(&key) {
+                Some(signer) => TrustStatus::Trusted { signer: signer.to_string() },
+                None => TrustStatus::Untrusted { key: Some(signature.key) },
+            },
+            Err(error) => TrustStatus::Invalid { error },
+        }
+    }
+
+    /// Trust of an extension directory, see the module documentation
+    pub fn verify_dir(&self, dir: &Path) -> TrustStatus {
+        match self.check_dir(dir) {
+            Ok(status) => status,
+            Err(error) => TrustStatus::Invalid { error },
+        }
+    }
+
+    fn check_dir(&self, dir: &Path) -> Result<TrustStatus, String> {
+        let checksums_path = dir.join(CHECKSUMS_FILE_NAME);
+        let signature_path = dir.join(SIGNATURE_FILE_NAME);
+
+        if !signature_path.is_file() {
+            return Ok(TrustStatus::Untrusted { key: None });
+        }
+
+        let read = |path: &Path| std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()));
+
+        let checksums = read(&checksums_path)?;
+        let status = self.verify(&checksums, Some(&read(&signature_path)?));
+
+        if let TrustStatus::Invalid { .. } = status {
+            return Ok(status);
+        }
+
+        let mut files = Vec::new();
+        package::collect_files(dir, dir, &mut files).map_err(|e| e.to_string())?;
+
+        let mut contents = Vec::new();
+
+        for (name, path) in files {
+            if name != CHECKSUMS_FILE_NAME && name != SIGNATURE_FILE_NAME {
+                contents.push((name, read(&path)?));
+            }
+        }
+
+        package::check_checksums(&checksums, contents.iter().map(|(name, contents)| (name.as_str(), contents.as_slice())))?;
+
+        Ok(status)
+    }
+}
+
+fn parse_key(key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{key:?} is not a hex encoded ed25519 public key"))?;
+
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+/// New random key to sign packages with, see `ExtensionPackage::pack`
+pub fn generate_key() -> SigningKey {
+    SigningKey::generate(&mut rand::rngs::OsRng)
+}
+
+/// What the `ExtensionManager` and the installer accept
+#[derive(Debug, Clone, Default)]
+pub struct SigningPolicy {
+    pub trusted_keys: TrustedKeys,
+    /// Refuse extensions that are not signed by a trusted key
+    pub require_signed: bool,
+}
+
+impl SigningPolicy {
+    /// Policy of the user, from the trusted keys file and the settings
+    pub fn load() -> OmniverlayResult<Self> {
+        Ok(Self {
+            trusted_keys: TrustedKeys::load()?,
+            require_signed: OmniverlaySettings::load()?.require_signed_packages,
+        })
+    }
+
+    /// Same as `load`, but a file that cannot be read is logged instead of failing. Both fall back
+    /// on the safe side: without trusted keys every extension is untrusted, and settings that exist
+    /// but cannot be read require signed extensions, so a corrupt file does not turn that off
+    pub fn load_or_default() -> Self {
+        let trusted_keys = TrustedKeys::load().unwrap_or_else(|e| {
+            error!("Failed to load the trusted keys, no key is trusted: {}", e);
+            TrustedKeys::default()
+        });
+
+        let require_signed = match OmniverlaySettings::load() {
+            Ok(settings) => settings.require_signed_packages,
+            Err(e) => {
+                error!("Failed to load the settings, unsigned extensions are refused: {}", e);
+                true
+            }
+        };
+
+        Self {
+            trusted_keys,
+            require_signed,
+        }
+    }
+
+    /// Trust of an extension file, the one of its directory if it has a manifest
+    pub fn verify_source(&self, source: &Path) -> TrustStatus {
+        match source.parent().filter(|dir| dir.join(MANIFEST_FILE_NAME).is_file()) {
+            Some(dir) => self.trusted_keys.verify_dir(dir),
+            None => TrustStatus::Untrusted { key: None },
+        }
+    }
+
+    /// Fails unless the policy allows loading `path`, whose trust is `status`
+    pub fn admit(&self, path: &Path, status: TrustStatus) -> OmniverlayResult<TrustStatus> {
+        if self.require_signed && !status.is_trusted() {
+            return Err(OmniverlayError::UntrustedExtension(path.display().to_string(), status.describe()));
+        }
+
+        Ok(status)
+    }
+
+    /// Same as `admit` for a package about to be installed, which is always refused when invalid
+    pub fn admit_package(&self, path: &Path, package: &ExtensionPackage) -> OmniverlayResult<TrustStatus> {
+        let status = package.trust(&self.trusted_keys);
+
+        if let TrustStatus::Invalid { .. } = status {
+            return Err(OmniverlayError::UntrustedExtension(path.display().to_string(), status.describe()));
+        }
+
+        self.admit(path, status)
+    }
+}
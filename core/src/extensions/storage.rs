@@ -0,0 +1,217 @@
+//! Key-value storage private to each extension, for state that is neither config nor layout.
+//!
+//! Values are kept in `~/.omniverlay/storage/<extension>-<hash>/storage.json`, loaded on first use
+//! and written back atomically after every change.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    utils::fs::{get_storage_dir, write_atomic},
+};
+
+const STORAGE_FILE: &str = "storage.json";
+
+/// Characters percent-encoded in directory names, `%` included so two names never share one
+const ENCODED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b' ');
+
+/// Directory name for an extension, anything that could escape the storage directory is encoded.
+/// An empty name would be the storage directory itself.
+///
+/// The name is lowercased since Windows and macOS do not tell case apart, and followed by a hash of
+/// the exact name which keeps `Clock` and `clock` apart and turns reserved device names such as
+/// `con` into plain names.
+fn dir_name(extension_name: &str) -> OmniverlayResult<String> {
+    if extension_name.is_empty() {
+        return Err(OmniverlayError::ExtensionInstance(
+            "an extension without a name has no storage".to_string(),
+        ));
+    }
+
+    let encoded = utf8_percent_encode(&extension_name.to_lowercase(), ENCODED).to_string();
+    let hash = hex::encode(&Sha256::digest(extension_name)[..4]);
+
+    Ok(format!("{encoded}-{hash}"))
+}
+
+#[derive(Clone)]
+pub struct ExtensionStorage {
+    dir: PathBuf,
+    values: Arc<Mutex<Option<HashMap<String, Value>>>>,
+}
+
+impl ExtensionStorage {
+    pub fn new(extension_name: &str) -> OmniverlayResult<Self> {
+        Ok(Self::in_dir(get_storage_dir()?.join(dir_name(extension_name)?)))
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            values: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn with_values<T>(&self, f: impl FnOnce(&mut HashMap<String, Value>) -> T) -> OmniverlayResult<T> {
+        let mut values = self
+            .values
+            .lock()
+            .map_err(|e| OmniverlayError::LockError(e.to_string()))?;
+
+        let values = match &mut *values {
+            Some(values) => values,
+            None => values.insert(self.read()?),
+        };
+
+        Ok(f(values))
+    }
+
+    fn read(&self) -> OmniverlayResult<HashMap<String, Value>> {
+        let path = self.dir.join(STORAGE_FILE);
+
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let json = std::fs::read_to_string(&path)?;
+
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn write(&self, values: &HashMap<String, Value>) -> OmniverlayResult<()> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        write_atomic(&self.dir.join(STORAGE_FILE), serde_json::to_string_pretty(values)?)
+    }
+
+    pub fn get_value(&self, key: &str) -> OmniverlayResult<Option<Value>> {
+        self.with_values(|values| values.get(key).cloned())
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> OmniverlayResult<Option<T>> {
+        match self.get_value(key)? {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_value(&self, key: &str, value: Value) -> OmniverlayResult<()> {
+        self.with_values(|values| {
+            let previous = values.insert(key.to_string(), value);
+
+            // Keep memory and disk in sync if the write fails
+            self.write(values).inspect_err(|_| match previous {
+                Some(previous) => {
+                    values.insert(key.to_string(), previous);
+                }
+                None => {
+                    values.remove(key);
+                }
+            })
+        })?
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> OmniverlayResult<()> {
+        self.set_value(key, serde_json::to_value(value)?)
+    }
+
+    /// Remove a key, returns its previous value
+    pub fn delete(&self, key: &str) -> OmniverlayResult<Option<Value>> {
+        self.with_values(|values| {
+            let Some(previous) = values.remove(key) else {
+                return Ok(None);
+            };
+
+            match self.write(values) {
+                Ok(()) => Ok(Some(previous)),
+                Err(e) => {
+                    values.insert(key.to_string(), previous);
+                    Err(e)
+                }
+            }
+        })?
+    }
+
+    pub fn list(&self) -> OmniverlayResult<Vec<String>> {
+        self.with_values(|values| {
+            let mut keys: Vec<String> = values.keys().cloned().collect();
+            keys.sort();
+            keys
+        })
+    }
+
+    /// Delete every value along with the storage directory
+    pub fn remove(&self) -> OmniverlayResult<()> {
+        let mut values = self
+            .values
+            .lock()
+            .map_err(|e| OmniverlayError::LockError(e.to_string()))?;
+
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+
+        *values = Some(HashMap::new());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_names_do_not_collide() {
+        let names = [
+            "a#2", "a_2", "a%232", "a/2", "a.2", "a 2", "Clock", "clock", "CLOCK", "Straße", "STRASSE",
+        ];
+
+        let dirs: Vec<String> = names.iter().map(|name| dir_name(name).unwrap()).collect();
+
+        for (i, dir) in dirs.iter().enumerate() {
+            // Compared the way Windows and macOS compare them
+            assert!(
+                !dirs[i + 1..].iter().any(|other| other.eq_ignore_ascii_case(dir)),
+                "{dir} is used twice"
+            );
+        }
+    }
+
+    #[test]
+    fn dir_names_stay_in_the_storage_directory() {
+        for name in ["..", ".", "../clock", "clock/../..", "C:\\clock"] {
+            let dir = dir_name(name).unwrap();
+
+            assert!(!dir.contains(['/', '\\', '.']), "{name} became {dir}");
+        }
+
+        assert!(dir_name("Clock widget_2-b").unwrap().starts_with("clock widget_2-b-"));
+    }
+
+    #[test]
+    fn reserved_device_names_are_not_used() {
+        for name in ["con", "NUL", "aux", "com1", "lpt1"] {
+            let dir = dir_name(name).unwrap();
+
+            assert!(
+                dir.len() > name.len() && dir.starts_with(&name.to_lowercase()),
+                "{name} became {dir}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_name_has_no_storage() {
+        assert!(dir_name("").is_err());
+    }
+}
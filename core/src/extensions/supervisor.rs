@@ -0,0 +1,439 @@
+//! Keeps extensions running.
+//!
+//! Calls into an extension run on a blocking thread of their own, so a panic is caught as a
+//! `JoinError` instead of leaving the extension half way through a hook, and a hook blocking its
+//! thread cannot stall the runtime. A call that times out is cancelled: an async hook is dropped
+//! at its next await, a hook blocking its thread is asked to return through
+//! `ExtensionHost::is_cancelled`. Until it does, the extension stays locked and later calls fail
+//! right away instead of waiting for it.
+//!
+//! Failures are then answered with the extension's `RestartPolicy`.
+
+use std::{
+    any::Any,
+    future::Future,
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    runtime::Handle,
+    sync::Notify,
+    task::{JoinError, JoinHandle},
+};
+
+use crate::{
+    errors::{OmniverlayError, OmniverlayResult},
+    event::OmniverlayEventType,
+    invoke_event,
+};
+
+use super::{ExtensionLayout, ExtensionState, ExtensionStatus, RegisteredExtension, LIFECYCLE_TIMEOUT};
+
+fn default_initial_backoff_ms() -> u64 {
+    1_000
+}
+
+fn default_max_backoff_ms() -> u64 {
+    60_000
+}
+
+/// What to do when an extension fails, set with `restart` in the manifest:
+///
+/// ```toml
+/// [restart]
+/// policy = "max-retries"
+/// max_retries = 5
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Leave a failed extension stopped
+    Never,
+    /// Always restart, the delay doubles after every failure
+    OnFailure {
+        #[serde(default = "default_initial_backoff_ms")]
+        initial_backoff_ms: u64,
+        #[serde(default = "default_max_backoff_ms")]
+        max_backoff_ms: u64,
+    },
+    /// Same as `OnFailure`, but give up after `max_retries` restarts
+    MaxRetries {
+        max_retries: u32,
+        #[serde(default = "default_initial_backoff_ms")]
+        initial_backoff_ms: u64,
+        #[serde(default = "default_max_backoff_ms")]
+        max_backoff_ms: u64,
+    },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::MaxRetries {
+            max_retries: 3,
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `attempt` (starting at 0), `None` once the policy gives up
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        let (initial_backoff_ms, max_backoff_ms) = match self {
+            Self::Never => return None,
+            Self::MaxRetries { max_retries, .. } if attempt >= *max_retries => return None,
+            Self::OnFailure { initial_backoff_ms, max_backoff_ms }
+            | Self::MaxRetries { initial_backoff_ms, max_backoff_ms, .. } => (*initial_backoff_ms, *max_backoff_ms),
+        };
+
+        let factor = 1u64.checked_shl(attempt).unwrap_or(u64::MAX);
+
+        Some(Duration::from_millis(initial_backoff_ms.saturating_mul(factor).min(max_backoff_ms)))
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Supervision {
+    pub policy: RestartPolicy,
+    pub restarts: u32,
+    pub pending_restart: Option<JoinHandle<()>>,
+}
+
+impl Supervision {
+    /// Forget previous failures, called when the user enables or disables the extension
+    pub fn reset(&mut self) {
+        self.restarts = 0;
+
+        if let Some(restart) = self.pending_restart.take() {
+            restart.abort();
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+fn join_error(e: JoinError) -> OmniverlayError {
+    match e.try_into_panic() {
+        Ok(payload) => OmniverlayError::ExtensionPanicked(panic_message(&*payload)),
+        Err(e) => OmniverlayError::ExtensionRuntime(e.to_string()),
+    }
+}
+
+/// Calls into an extension that timed out and did not return yet, see `ExtensionHost::is_cancelled`
+#[derive(Clone, Default)]
+pub(super) struct TimedOutCalls(Arc<AtomicUsize>);
+
+impl TimedOutCalls {
+    pub fn any(&self) -> bool {
+        self.0.load(Ordering::SeqCst) > 0
+    }
+}
+
+const CALL_RUNNING: u8 = 0;
+const CALL_TIMED_OUT: u8 = 1;
+const CALL_DONE: u8 = 2;
+
+#[derive(Default)]
+struct Call {
+    state: AtomicU8,
+    cancel: Notify,
+}
+
+impl Call {
+    fn time_out(&self, timed_out: &TimedOutCalls) {
+        if self
+            .state
+            .compare_exchange(CALL_RUNNING, CALL_TIMED_OUT, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            timed_out.0.fetch_add(1, Ordering::SeqCst);
+            self.cancel.notify_one();
+        }
+    }
+
+    fn finish(&self, timed_out: &TimedOutCalls) {
+        if self.state.swap(CALL_DONE, Ordering::SeqCst) == CALL_TIMED_OUT {
+            timed_out.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// Also when the call panics
+struct FinishOnDrop(Arc<Call>, TimedOutCalls);
+
+impl Drop for FinishOnDrop {
+    fn drop(&mut self) {
+        self.0.finish(&self.1);
+    }
+}
+
+/// Run a call into an extension on a blocking thread, panics and timeouts become errors
+pub(super) async fn run_guarded<F>(operation: &str, timed_out: &TimedOutCalls, future: F) -> OmniverlayResult<()>
+where
+    F: Future<Output = OmniverlayResult<()>> + Send + 'static,
+{
+    run_guarded_for(operation, LIFECYCLE_TIMEOUT, timed_out, future).await
+}
+
+/// Same as `run_guarded` with another timeout
+pub(super) async fn run_guarded_for<F, T>(
+    operation: &str,
+    timeout: Duration,
+    timed_out: &TimedOutCalls,
+    future: F,
+) -> OmniverlayResult<T>
+where
+    F: Future<Output = OmniverlayResult<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    if timed_out.any() {
+        return Err(OmniverlayError::ExtensionRuntime(format!(
+            "{operation} not called, a previous call did not return yet"
+        )));
+    }
+
+    let call = Arc::new(Call::default());
+    let finish = FinishOnDrop(call.clone(), timed_out.clone());
+    let runtime = Handle::current();
+
+    let task = tokio::task::spawn_blocking(move || {
+        runtime.block_on(async {
+            tokio::select! {
+                result = future => Some(result),
+                () = finish.0.cancel.notified() => None,
+            }
+        })
+    });
+
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(Some(result))) => result,
+        Ok(Ok(None)) => Err(OmniverlayError::ExtensionRuntime(format!("{operation} was cancelled"))),
+        Ok(Err(e)) => Err(join_error(e)),
+        Err(_) => {
+            call.time_out(timed_out);
+
+            Err(OmniverlayError::ExtensionRuntime(format!(
+                "{operation} did not return within {timeout:?}"
+            )))
+        }
+    }
+}
+
+/// Run `enable` or `disable`, see `run_guarded`
+pub(super) async fn call_hook(registered: &RegisteredExtension, enable: bool) -> OmniverlayResult<()> {
+    let extension = registered.extension.clone();
+    let usage = registered.usage.clone();
+
+    let operation = if enable { "enable" } else { "disable" };
+
+    run_guarded(operation, &registered.timed_out, async move {
+        let mut extension = extension.lock_owned().await;
+
+        usage
+            .measure(async {
+                match enable {
+                    true => extension.enable().await,
+                    false => extension.disable().await,
+                }
+            })
+            .await
+    })
+    .await
+}
+
+/// Run `update_state`, see `run_guarded`
+pub(super) async fn call_update_state(registered: &RegisteredExtension, state: ExtensionState) -> OmniverlayResult<()> {
+    let extension = registered.extension.clone();
+    let usage = registered.usage.clone();
+
+    run_guarded("update_state", &registered.timed_out, async move {
+        let mut extension = extension.lock_owned().await;
+
+        usage.measure(extension.update_state(state)).await
+    })
+    .await
+}
+
+/// Run `update_layout`, see `run_guarded`
+pub(super) async fn call_update_layout(registered: &RegisteredExtension, layout: ExtensionLayout) -> OmniverlayResult<()> {
+    let extension = registered.extension.clone();
+    let usage = registered.usage.clone();
+
+    run_guarded("update_layout", &registered.timed_out, async move {
+        let mut extension = extension.lock_owned().await;
+
+        usage.measure(extension.update_layout(layout)).await
+    })
+    .await
+}
+
+/// Disable a running extension that went over its `ResourceBudget`, see `usage`. It stays enabled
+/// in its state so the status can tell why it is not running, applying a profile leaves it stopped
+/// until it is enabled again.
+pub(super) async fn disable_over_budget(registered: &RegisteredExtension, reason: String) -> OmniverlayResult<()> {
+    if registered.is_dropped() || registered.info.lock().await.status != ExtensionStatus::Running {
+        return Ok(());
+    }
+
+    let error = OmniverlayError::OverBudget(registered.name.clone(), reason.clone());
+
+    error!("{}", error);
+
+    registered.run_lifecycle_hook(false).await?;
+
+    {
+        let mut info = registered.info.lock().await;
+
+        info.state.is_enabled = true;
+        info.status = ExtensionStatus::OverBudget { reason: reason.clone() };
+    }
+
+    invoke_event!(OmniverlayEventType::ExtensionOverBudget(registered.name.clone(), reason));
+    invoke_event!(OmniverlayEventType::UpdateExtensionData);
+
+    Ok(())
+}
+
+/// Report that a running extension went over its `ResourceBudget` to the manager owning it
+pub(super) async fn report_over_budget(registered: RegisteredExtension, reason: String) {
+    if let Err(e) = disable_over_budget(&registered, reason).await {
+        warn!("Failed to disable extension {} over its budget: {}", registered.name, e);
+    }
+}
+
+async fn next_backoff(registered: &RegisteredExtension) -> Option<Duration> {
+    let mut supervision = registered.supervision.lock().await;
+
+    let backoff = supervision.policy.backoff(supervision.restarts)?;
+    supervision.restarts += 1;
+
+    Some(backoff)
+}
+
+/// Handle the failure of a running extension, ignored when it is starting, stopping or stopped
+/// since the lifecycle hooks already report their own failures
+pub(super) async fn fail_running(extension_name: String, registered: RegisteredExtension, error: &OmniverlayError) {
+    {
+        let info = registered.info.lock().await;
+
+        if !info.state.is_enabled || info.status != ExtensionStatus::Running {
+            return;
+        }
+    }
+
+    on_failure(extension_name, registered, error).await;
+}
+
+/// Mark the extension as failed and restart it if its policy allows it
+pub(super) async fn on_failure(extension_name: String, registered: RegisteredExtension, error: &OmniverlayError) {
+    error!("Extension {} failed: {}", extension_name, error);
+
+    registered.scheduler.stop_ticking(&extension_name);
+
+    registered.info.lock().await.status = ExtensionStatus::Failed { error: error.to_string() };
+
+    // Restarting cannot help until the user grants the missing capability
+    let backoff = match error {
+        OmniverlayError::PermissionDenied(..) => None,
+        _ => next_backoff(&registered).await,
+    };
+
+    let Some(backoff) = backoff else {
+        give_up(extension_name, error).await;
+        return;
+    };
+
+    let supervision = registered.supervision.clone();
+
+    let restart = tokio::spawn(restart_loop(extension_name, registered, backoff));
+
+    supervision.lock().await.pending_restart = Some(restart);
+
+    invoke_event!(OmniverlayEventType::UpdateExtensionData);
+}
+
+async fn give_up(extension_name: String, error: &OmniverlayError) {
+    error!("Giving up on extension {}: {}", extension_name, error);
+
+    invoke_event!(OmniverlayEventType::ExtensionGaveUp(extension_name, error.to_string()));
+    invoke_event!(OmniverlayEventType::UpdateExtensionData);
+}
+
+/// Whether the extension should still be restarted, the user may have disabled it or it may have
+/// been removed or reloaded since it failed
+async fn wants_restart(registered: &RegisteredExtension) -> bool {
+    !registered.is_dropped() && registered.info.lock().await.state.is_enabled
+}
+
+async fn restart_loop(extension_name: String, registered: RegisteredExtension, mut backoff: Duration) {
+    loop {
+        info!("Restarting extension {} in {:?}", extension_name, backoff);
+
+        tokio::time::sleep(backoff).await;
+
+        if !wants_restart(&registered).await {
+            info!("Extension {} is no longer wanted, not restarting it", extension_name);
+            return;
+        }
+
+        // Same statuses as the extension goes through when the user disables and enables it
+        registered.info.lock().await.status = ExtensionStatus::Stopping;
+
+        // Whatever is left of the failed run is cleaned up first, this may well fail too
+        if let Err(e) = call_hook(&registered, false).await {
+            warn!("Failed to disable extension {} before restarting it: {}", extension_name, e);
+        }
+
+        registered.info.lock().await.status = ExtensionStatus::Starting;
+        invoke_event!(OmniverlayEventType::UpdateExtensionData);
+
+        let error = match call_hook(&registered, true).await {
+            // Disabled or dropped while its enable hook ran, it is stopped again
+            Ok(()) if !wants_restart(&registered).await => {
+                registered.info.lock().await.status = ExtensionStatus::Stopping;
+
+                if let Err(e) = call_hook(&registered, false).await {
+                    warn!("Failed to disable extension {} after restarting it: {}", extension_name, e);
+                }
+
+                registered.info.lock().await.status = ExtensionStatus::Stopped;
+
+                return;
+            }
+            Ok(()) => {
+                info!("Restarted extension {}", extension_name);
+
+                registered.info.lock().await.status = ExtensionStatus::Running;
+                registered.scheduler.start_ticking(&extension_name, &registered).await;
+                invoke_event!(OmniverlayEventType::UpdateExtensionData);
+
+                return;
+            }
+            Err(e) => e,
+        };
+
+        error!("Failed to restart extension {}: {}", extension_name, error);
+
+        registered.info.lock().await.status = ExtensionStatus::Failed { error: error.to_string() };
+
+        backoff = match next_backoff(&registered).await {
+            Some(backoff) => backoff,
+            None => {
+                give_up(extension_name, &error).await;
+                return;
+            }
+        };
+    }
+}
@@ -0,0 +1,347 @@
+//! What extensions cost the host.
+//!
+//! Hooks and request handlers are timed around every poll of their future, on the thread polling
+//! it, so their CPU time only covers that work: threads an extension starts itself and process
+//! extensions are not counted. Publishing is counted by the `ExtensionHost`.
+//!
+//! Rates cover the last `USAGE_WINDOW`, counted in buckets of `USAGE_BUCKET` so a burst after an
+//! idle period is not spread over the idle time. They are checked against the extension's
+//! `ResourceBudget` once per bucket, an extension going over it is disabled by the
+//! `ExtensionManager` it is registered with.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use cpu_time::ThreadTime;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::runtime::RuntimeHandle;
+
+use super::{supervisor, RegisteredExtension, Registration};
+
+/// Period over which the CPU usage and the publish rate are measured
+pub const USAGE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Period counted together within a `USAGE_WINDOW`
+pub const USAGE_BUCKET: Duration = Duration::from_millis(500);
+
+fn default_max_cpu_percent() -> f64 {
+    25.0
+}
+
+fn default_max_publish_rate() -> f64 {
+    200.0
+}
+
+/// Limits of an extension, set with `budget` in the manifest:
+///
+/// ```toml
+/// [budget]
+/// max_cpu_percent = 50
+/// max_publish_rate = 20
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceBudget {
+    /// CPU time spent in hooks over a window, 100 is a whole core
+    #[serde(default = "default_max_cpu_percent")]
+    pub max_cpu_percent: f64,
+    /// Payloads published per second over a window
+    #[serde(default = "default_max_publish_rate")]
+    pub max_publish_rate: f64,
+}
+
+impl Default for ResourceBudget {
+    fn default() -> Self {
+        Self {
+            max_cpu_percent: default_max_cpu_percent(),
+            max_publish_rate: default_max_publish_rate(),
+        }
+    }
+}
+
+/// Usage of an extension, totals count from its registration and rates over the last `USAGE_WINDOW`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub hook_calls: u64,
+    pub wall_time_ms: f64,
+    pub cpu_time_ms: f64,
+    pub published: u64,
+    pub cpu_percent: f64,
+    pub publish_rate: f64,
+    pub budget: Option<ResourceBudget>,
+}
+
+struct UsageBucket {
+    start: Instant,
+    cpu_time: Duration,
+    published: u64,
+}
+
+struct UsageWindow {
+    // Since the extension was enabled, rates are only checked once a whole window is measured
+    start: Instant,
+    last_check: Instant,
+    // Oldest first, none older than `USAGE_WINDOW`
+    buckets: VecDeque<UsageBucket>,
+}
+
+impl UsageWindow {
+    fn new(now: Instant) -> Self {
+        Self {
+            start: now,
+            last_check: now,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    /// Bucket counting what happens at `now`, after forgetting the buckets gone out of the window
+    fn bucket(&mut self, now: Instant) -> &mut UsageBucket {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| now.saturating_duration_since(bucket.start) >= USAGE_WINDOW)
+        {
+            self.buckets.pop_front();
+        }
+
+        if !self
+            .buckets
+            .back()
+            .is_some_and(|bucket| now.saturating_duration_since(bucket.start) < USAGE_BUCKET)
+        {
+            self.buckets.push_back(UsageBucket {
+                start: now,
+                cpu_time: Duration::ZERO,
+                published: 0,
+            });
+        }
+
+        self.buckets.back_mut().unwrap()
+    }
+
+    /// CPU time and payloads published over the last `USAGE_WINDOW`
+    fn totals(&self) -> (Duration, u64) {
+        self.buckets
+            .iter()
+            .fold((Duration::ZERO, 0), |(cpu_time, published), bucket| {
+                (cpu_time + bucket.cpu_time, published + bucket.published)
+            })
+    }
+}
+
+struct UsageState {
+    report: UsageReport,
+    window: UsageWindow,
+    // Reported once until the extension is enabled again
+    over_budget: bool,
+}
+
+/// Usage counters of an extension, shared by its host and the `ExtensionManager`
+#[derive(Clone)]
+pub struct ExtensionUsage {
+    extension_name: String,
+    state: Arc<Mutex<UsageState>>,
+    // Publishing, which is counted here, may happen on threads outside of tokio
+    runtime: RuntimeHandle,
+    // Registration the usage is counted for, unset for standalone hosts
+    owner: Arc<Mutex<Weak<Registration>>>,
+}
+
+impl ExtensionUsage {
+    pub fn new(extension_name: &str, budget: Option<ResourceBudget>) -> Self {
+        Self {
+            extension_name: extension_name.to_string(),
+            state: Arc::new(Mutex::new(UsageState {
+                report: UsageReport {
+                    budget,
+                    ..Default::default()
+                },
+                window: UsageWindow::new(Instant::now()),
+                over_budget: false,
+            })),
+            runtime: RuntimeHandle::current(),
+            owner: Arc::new(Mutex::new(Weak::new())),
+        }
+    }
+
+    /// Report going over the budget to `registration`, set by the manager once it registered the extension
+    pub(super) fn set_owner(&self, registration: Weak<Registration>) {
+        *self.owner.lock().unwrap() = registration;
+    }
+
+    /// `None` never stops the extension
+    pub fn set_budget(&self, budget: Option<ResourceBudget>) {
+        self.state.lock().unwrap().report.budget = budget;
+    }
+
+    /// Start measuring from scratch, called when the extension is enabled
+    pub(super) fn restart_window(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.window = UsageWindow::new(Instant::now());
+        state.over_budget = false;
+    }
+
+    /// Run a hook of the extension, counting its time
+    pub async fn measure<F: Future>(&self, future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let start = Instant::now();
+
+        let output = std::future::poll_fn(|cx| {
+            let thread_start = ThreadTime::try_now().ok();
+            let poll = future.as_mut().poll(cx);
+
+            if let Some(thread_start) = thread_start {
+                let cpu_time = thread_start.elapsed();
+
+                self.update(|state, now| {
+                    state.window.bucket(now).cpu_time += cpu_time;
+                    state.report.cpu_time_ms += cpu_time.as_secs_f64() * 1000.0;
+                });
+            }
+
+            poll
+        })
+        .await;
+
+        let wall_time = start.elapsed();
+
+        self.update(|state, _| {
+            state.report.hook_calls += 1;
+            state.report.wall_time_ms += wall_time.as_secs_f64() * 1000.0;
+        });
+
+        output
+    }
+
+    pub(super) fn record_publish(&self) {
+        self.update(|state, now| {
+            state.window.bucket(now).published += 1;
+            state.report.published += 1;
+        });
+    }
+
+    pub fn report(&self) -> UsageReport {
+        self.update(|_, _| {});
+
+        self.state.lock().unwrap().report.clone()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut UsageState, Instant)) {
+        self.update_at(Instant::now(), change);
+    }
+
+    fn update_at(&self, now: Instant, change: impl FnOnce(&mut UsageState, Instant)) {
+        let exceeded = {
+            let mut state = self.state.lock().unwrap();
+
+            change(&mut state, now);
+
+            let window = &mut state.window;
+
+            if now.saturating_duration_since(window.start) < USAGE_WINDOW
+                || now.saturating_duration_since(window.last_check) < USAGE_BUCKET
+            {
+                return;
+            }
+
+            window.last_check = now;
+            // Forget what went out of the window, whether or not something happened since
+            window.bucket(now);
+
+            let (cpu_time, published) = window.totals();
+            let report = &mut state.report;
+
+            report.cpu_percent = cpu_time.as_secs_f64() / USAGE_WINDOW.as_secs_f64() * 100.0;
+            report.publish_rate = published as f64 / USAGE_WINDOW.as_secs_f64();
+
+            match report.budget.as_ref().and_then(|budget| exceeded(report, budget)) {
+                Some(reason) if !state.over_budget => {
+                    state.over_budget = true;
+                    reason
+                }
+                _ => return,
+            }
+        };
+
+        warn!("Extension {} is over its budget: {}", self.extension_name, exceeded);
+
+        // Gone when the extension was removed, or never registered with a manager
+        let Some(registration) = self.owner.lock().unwrap().upgrade() else {
+            return;
+        };
+
+        self.runtime
+            .spawn(async move { supervisor::report_over_budget(RegisteredExtension(registration), exceeded).await });
+    }
+}
+
+fn exceeded(report: &UsageReport, budget: &ResourceBudget) -> Option<String> {
+    if report.cpu_percent > budget.max_cpu_percent {
+        return Some(format!(
+            "used {:.0}% CPU, its budget is {:.0}%",
+            report.cpu_percent, budget.max_cpu_percent
+        ));
+    }
+
+    if report.publish_rate > budget.max_publish_rate {
+        return Some(format!(
+            "published {:.0} payloads per second, its budget is {:.0}",
+            report.publish_rate, budget.max_publish_rate
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLL: Duration = Duration::from_millis(100);
+
+    // A poll of a hook spending its whole time on the CPU, ending at `at`
+    fn spin(usage: &ExtensionUsage, at: Instant) {
+        usage.update_at(at, |state, now| state.window.bucket(now).cpu_time += POLL);
+    }
+
+    #[tokio::test]
+    async fn burst_after_an_idle_period_is_over_budget() {
+        let usage = ExtensionUsage::new("clock", Some(ResourceBudget::default()));
+        let start = Instant::now() + Duration::from_secs(60);
+
+        usage.update_at(start, |_, _| {});
+        assert_eq!(usage.state.lock().unwrap().report.cpu_percent, 0.0);
+
+        // A whole core for 4 seconds
+        for poll in 1..=40 {
+            spin(&usage, start + POLL * poll);
+        }
+
+        let state = usage.state.lock().unwrap();
+
+        assert!(state.report.cpu_percent > 60.0, "{}", state.report.cpu_percent);
+        assert!(state.over_budget);
+    }
+
+    #[tokio::test]
+    async fn usage_spread_over_time_stays_within_budget() {
+        let usage = ExtensionUsage::new("clock", Some(ResourceBudget::default()));
+        let start = Instant::now() + Duration::from_secs(5);
+
+        // The same 4 seconds spread over a minute
+        for poll in 0..40 {
+            spin(&usage, start + Duration::from_millis(1500) * poll);
+        }
+
+        let state = usage.state.lock().unwrap();
+
+        assert!(state.report.cpu_percent < 25.0, "{}", state.report.cpu_percent);
+        assert!(!state.over_budget);
+    }
+}
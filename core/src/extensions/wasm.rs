@@ -0,0 +1,416 @@
+//! Sandboxed WebAssembly extensions.
+//!
+//! A guest module talks to the host through JSON encoded `ExtensionInfo`, `ExtensionState` and
+//! `ExtensionLayout` values written into its linear memory. It must export:
+//!
+//! - `memory`
+//! - `omniverlay_alloc(len: i32) -> i32`: allocate `len` bytes and return the pointer
+//! - `omniverlay_info() -> i64`: pointer in the high 32 bits, length in the low 32 bits, of the JSON `ExtensionInfo`
+//! - `omniverlay_enable() -> i32` and `omniverlay_disable() -> i32`: `0` on success
+//!
+//! And may export `omniverlay_update_state(ptr: i32, len: i32) -> i32` and
+//! `omniverlay_update_layout(ptr: i32, len: i32) -> i32` to be notified of changes, and
+//! `omniverlay_tick() -> i32` with `omniverlay_tick_interval() -> i32`, in milliseconds, to be
+//! called by the scheduler while running.
+//!
+//! The guest may import `env.omniverlay_log(level: i32, ptr: i32, len: i32)` and
+//! `env.omniverlay_publish(ptr: i32, len: i32)`, which sends a JSON payload to the overlay.
+//!
+//! Calls into the guest run on a blocking thread, each one is bounded by its fuel and trapped once
+//! it runs for longer than `LIFECYCLE_TIMEOUT`. A panic during a call fails the extension, the
+//! guest is not called again.
+
+use std::{
+    path::Path,
+    sync::{Arc, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use wasmtime::{Caller, Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+use crate::errors::{OmniverlayError, OmniverlayResult};
+
+use super::{host::ExtensionHost, Extension, ExtensionInfo, ExtensionLayout, ExtensionState, LIFECYCLE_TIMEOUT};
+
+/// Time between two epochs of the engine, the precision of the deadline of a call
+const EPOCH_INTERVAL: Duration = Duration::from_millis(100);
+
+static ENGINE: Lazy<Engine> = Lazy::new(|| {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+
+    let engine = Engine::new(&config).expect("Failed to create the WebAssembly engine");
+
+    let ticker = engine.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(EPOCH_INTERVAL);
+        ticker.increment_epoch();
+    });
+
+    engine
+});
+
+// Epochs a call may run for before it traps
+fn deadline_epochs() -> u64 {
+    (LIFECYCLE_TIMEOUT.as_millis() / EPOCH_INTERVAL.as_millis()) as u64
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WasmLimits {
+    /// Fuel given to the guest for every call into it
+    pub fuel: u64,
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            max_memory_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+// Keep the whole error chain, the root cause (e.g. "all fuel consumed") is not the top-level message
+fn runtime_error(e: wasmtime::Error) -> OmniverlayError {
+    if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) {
+        return OmniverlayError::ExtensionRuntime(format!("guest did not return within {LIFECYCLE_TIMEOUT:?}"));
+    }
+
+    OmniverlayError::ExtensionRuntime(format!("{e:#}"))
+}
+
+struct WasmState {
+    name: String,
+    limits: StoreLimits,
+    host: Option<ExtensionHost>,
+}
+
+// Bytes of the guest memory at `ptr..ptr + len`, `None` when out of bounds
+fn guest_bytes(caller: &mut Caller<'_, WasmState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory").and_then(|e| e.into_memory())?;
+
+    memory
+        .data(&caller)
+        .get(ptr as usize..(ptr as usize).saturating_add(len as usize))
+        .map(<[u8]>::to_vec)
+}
+
+// The instance of the module, only used from blocking threads
+struct Guest {
+    store: Store<WasmState>,
+    instance: Instance,
+    memory: Memory,
+    limits: WasmLimits,
+}
+
+pub struct WasmExtension {
+    info: Arc<Mutex<ExtensionInfo>>,
+    // A call left running by a timed out hook traps at its deadline before the next one starts
+    guest: Arc<std::sync::Mutex<Guest>>,
+    // Given by `omniverlay_tick_interval`, `None` when the guest does not tick
+    tick_interval: Option<Duration>,
+}
+
+impl WasmExtension {
+    pub fn from_file(path: &Path, limits: WasmLimits) -> OmniverlayResult<Self> {
+        let module = Module::from_file(&ENGINE, path)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{}: {e:#}", path.display())))?;
+
+        Self::from_module(&module, limits).map_err(|e| match e {
+            OmniverlayError::ExtensionLoadFailed(message) => {
+                OmniverlayError::ExtensionLoadFailed(format!("{}: {message}", path.display()))
+            }
+            e => e,
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8], limits: WasmLimits) -> OmniverlayResult<Self> {
+        let module = Module::new(&ENGINE, bytes)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{e:#}")))?;
+
+        Self::from_module(&module, limits)
+    }
+
+    fn from_module(module: &Module, limits: WasmLimits) -> OmniverlayResult<Self> {
+        let state = WasmState {
+            name: "unknown".to_string(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.max_memory_bytes)
+                .instances(1)
+                .build(),
+            host: None,
+        };
+
+        let mut store = Store::new(&ENGINE, state);
+        store.limiter(|state| &mut state.limits);
+
+        let mut linker = Linker::new(&ENGINE);
+        linker
+            .func_wrap(
+                "env",
+                "omniverlay_log",
+                |mut caller: Caller<'_, WasmState>, level: i32, ptr: i32, len: i32| {
+                    let Some(bytes) = guest_bytes(&mut caller, ptr, len) else {
+                        return;
+                    };
+
+                    let message = String::from_utf8_lossy(&bytes);
+                    let name = &caller.data().name;
+
+                    match level {
+                        0 => error!("[{name}] {message}"),
+                        1 => warn!("[{name}] {message}"),
+                        2 => info!("[{name}] {message}"),
+                        _ => debug!("[{name}] {message}"),
+                    }
+                },
+            )
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{e:#}")))?;
+
+        linker
+            .func_wrap(
+                "env",
+                "omniverlay_publish",
+                |mut caller: Caller<'_, WasmState>, ptr: i32, len: i32| {
+                    let Some(bytes) = guest_bytes(&mut caller, ptr, len) else {
+                        return;
+                    };
+
+                    let state = caller.data();
+
+                    match serde_json::from_slice(&bytes) {
+                        Ok(payload) => {
+                            if let Some(host) = &state.host {
+                                host.publish_value(payload);
+                            }
+                        }
+                        Err(e) => warn!("[{}] published invalid JSON: {e}", state.name),
+                    }
+                },
+            )
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{e:#}")))?;
+
+        store
+            .set_fuel(limits.fuel)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{e:#}")))?;
+        store.epoch_deadline_trap();
+        store.set_epoch_deadline(deadline_epochs());
+
+        let instance = linker
+            .instantiate(&mut store, module)
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(format!("{e:#}")))?;
+
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| {
+            OmniverlayError::ExtensionLoadFailed("module does not export its memory".to_string())
+        })?;
+
+        let mut guest = Guest {
+            store,
+            instance,
+            memory,
+            limits,
+        };
+
+        let info = guest
+            .read_info()
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(e.to_string()))?;
+
+        guest.store.data_mut().name = info.name.clone();
+
+        let tick_interval = guest
+            .read_tick_interval()
+            .map_err(|e| OmniverlayError::ExtensionLoadFailed(e.to_string()))?;
+
+        Ok(Self {
+            info: Arc::new(Mutex::new(info)),
+            guest: Arc::new(std::sync::Mutex::new(guest)),
+            tick_interval,
+        })
+    }
+
+    /// Run a call into the guest on a blocking thread, it never yields so running it on the
+    /// runtime would keep the supervisor from timing it out
+    async fn call<T, F>(&self, call: F) -> OmniverlayResult<T>
+    where
+        F: FnOnce(&mut Guest) -> OmniverlayResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let guest = self.guest.clone();
+
+        tokio::task::spawn_blocking(move || call(&mut *lock_guest(&guest)?))
+            .await
+            .map_err(|e| OmniverlayError::ExtensionRuntime(e.to_string()))?
+    }
+}
+
+/// Lock the guest, a previous call panicked while holding it when it is poisoned. Its state can
+/// be anything then, the failure is reported to the supervisor instead of calling it again.
+fn lock_guest(guest: &std::sync::Mutex<Guest>) -> OmniverlayResult<MutexGuard<'_, Guest>> {
+    guest.lock().map_err(|poisoned| {
+        let message = "a previous call into the guest panicked";
+
+        if let Some(host) = &poisoned.get_ref().store.data().host {
+            host.report_failure(OmniverlayError::ExtensionRuntime(message.to_string()));
+        }
+
+        OmniverlayError::ExtensionRuntime(message.to_string())
+    })
+}
+
+impl Guest {
+    // Every call gets the whole fuel and time of a hook
+    fn refuel(&mut self) -> OmniverlayResult<()> {
+        self.store.set_epoch_deadline(deadline_epochs());
+
+        self.store
+            .set_fuel(self.limits.fuel)
+            .map_err(runtime_error)
+    }
+
+    fn read_info(&mut self) -> OmniverlayResult<ExtensionInfo> {
+        self.refuel()?;
+
+        let func = self
+            .instance
+            .get_typed_func::<(), i64>(&mut self.store, "omniverlay_info")
+            .map_err(runtime_error)?;
+
+        let packed = func
+            .call(&mut self.store, ())
+            .map_err(runtime_error)? as u64;
+
+        let ptr = (packed >> 32) as usize;
+        let len = (packed & 0xFFFF_FFFF) as usize;
+
+        let bytes = self
+            .memory
+            .data(&self.store)
+            .get(ptr..ptr.saturating_add(len))
+            .ok_or_else(|| OmniverlayError::ExtensionRuntime("info points outside of the guest memory".to_string()))?;
+
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    fn read_tick_interval(&mut self) -> OmniverlayResult<Option<Duration>> {
+        // Ticking is optional for the guest
+        if self.instance.get_typed_func::<(), i32>(&mut self.store, "omniverlay_tick").is_err() {
+            return Ok(None);
+        }
+
+        let Ok(func) = self.instance.get_typed_func::<(), i32>(&mut self.store, "omniverlay_tick_interval") else {
+            return Ok(None);
+        };
+
+        self.refuel()?;
+
+        let interval = func.call(&mut self.store, ()).map_err(runtime_error)?;
+
+        Ok(u64::try_from(interval).ok().filter(|ms| *ms > 0).map(Duration::from_millis))
+    }
+
+    fn call_status(&mut self, name: &str) -> OmniverlayResult<()> {
+        self.refuel()?;
+
+        let func = self
+            .instance
+            .get_typed_func::<(), i32>(&mut self.store, name)
+            .map_err(runtime_error)?;
+
+        let status = func
+            .call(&mut self.store, ())
+            .map_err(runtime_error)?;
+
+        if status != 0 {
+            return Err(OmniverlayError::ExtensionRuntime(format!("{name} returned {status}")));
+        }
+
+        Ok(())
+    }
+
+    fn call_with_json<T: Serialize>(&mut self, name: &str, value: &T) -> OmniverlayResult<()> {
+        // Notifications are optional for the guest
+        let Ok(func) = self.instance.get_typed_func::<(i32, i32), i32>(&mut self.store, name) else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_vec(value)?;
+
+        self.refuel()?;
+
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "omniverlay_alloc")
+            .map_err(runtime_error)?;
+
+        let ptr = alloc
+            .call(&mut self.store, json.len() as i32)
+            .map_err(runtime_error)?;
+
+        self.memory
+            .write(&mut self.store, ptr as usize, &json)
+            .map_err(|e| OmniverlayError::ExtensionRuntime(e.to_string()))?;
+
+        let status = func
+            .call(&mut self.store, (ptr, json.len() as i32))
+            .map_err(runtime_error)?;
+
+        if status != 0 {
+            return Err(OmniverlayError::ExtensionRuntime(format!("{name} returned {status}")));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Extension for WasmExtension {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        self.call(|guest| guest.call_status("omniverlay_enable")).await
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        self.call(|guest| guest.call_status("omniverlay_disable")).await
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        // Only the host is set, whatever state a panicking call left the guest in
+        self.guest.lock().unwrap_or_else(PoisonError::into_inner).store.data_mut().host = Some(host);
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.tick_interval
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        self.call(|guest| guest.call_status("omniverlay_tick")).await
+    }
+
+    async fn update_layout(&mut self, layout: ExtensionLayout) -> OmniverlayResult<()> {
+        let json = layout.clone();
+        self.call(move |guest| guest.call_with_json("omniverlay_update_layout", &json)).await?;
+
+        self.info.lock().await.layout = Some(layout);
+
+        Ok(())
+    }
+
+    async fn update_state(&mut self, new_state: ExtensionState) -> OmniverlayResult<()> {
+        let json = new_state.clone();
+        self.call(move |guest| guest.call_with_json("omniverlay_update_state", &json)).await?;
+
+        self.info.lock().await.state = new_state;
+
+        Ok(())
+    }
+}
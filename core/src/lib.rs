@@ -1,10 +1,17 @@
 use core::panic;
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use env_logger::Env;
 use errors::OmniverlayResult;
-use extensions::{data::{ExtensionDataManager, OmniverlayLayout, OmniverlayProfile}, ExtensionManager};
-use log::info;
+use event::OmniverlayEventType;
+use extensions::{
+    catalog::{self, CatalogEntry},
+    data::{DataRetention, ExtensionDataManager, OmniverlayLayout, OmniverlayProfile},
+    installer::{InstallOutcome, InstalledPackage, PackageInstaller},
+    package::ExtensionPackage,
+    ExtensionManager,
+};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 use utils::fs::get_omniverlay_dir;
@@ -12,7 +19,20 @@ use utils::fs::get_omniverlay_dir;
 pub mod errors;
 pub mod event;
 pub mod extensions;
+pub mod settings;
 pub mod utils;
+
+/// Attribute registering an `impl Extension` block, see `extensions::registry`
+pub use omniverlay_macros::omniverlay_extension;
+
+// Used by the code generated by `omniverlay_macros`
+#[doc(hidden)]
+pub mod __private {
+    pub use async_trait::async_trait;
+    pub use inventory;
+    pub use tokio;
+}
+
 pub struct Omniverlay {
     extension_manager: Arc<RwLock<ExtensionManager>>,
     profile_manager: Arc<RwLock<ExtensionDataManager<OmniverlayProfile>>>,
@@ -74,6 +94,166 @@ impl Omniverlay {
         Ok(())
     }
 
+    /// Unregister an extension and its instances, then prune or keep their entries in the current
+    /// profile and layout
+    pub async fn unregister_extension(&self, extension_type: &str, retention: DataRetention) -> OmniverlayResult<()> {
+        // Stopped without holding the manager, a slow extension would hold up everything else
+        let detached = self.extension_manager.write().await.detach_extension(extension_type)?;
+
+        detached.stop().await;
+        detached.remove_data();
+
+        info!("Unregistered extension {}", extension_type);
+
+        let instances = detached.instance_names();
+
+        match retention {
+            DataRetention::Prune => {
+                let profile_manager = self.profile_manager.read().await;
+                let layout_manager = self.layout_manager.read().await;
+
+                {
+                    let profile = profile_manager.get_current().await?;
+                    let layout = layout_manager.get_current().await?;
+
+                    let (mut profile, mut layout) = (profile.write().await, layout.write().await);
+
+                    for name in &instances {
+                        profile.extensions.remove(name);
+                        layout.extensions.remove(name);
+                    }
+                }
+
+                // Both tell the UI to refresh
+                profile_manager.save_data().await?;
+                layout_manager.save_data().await?;
+            }
+            DataRetention::Keep => invoke_event!(OmniverlayEventType::UpdateExtensionData),
+        }
+
+        Ok(())
+    }
+
+    /// Install the extension package at `path` and load it, in place of the installed version. The
+    /// instances of the replaced version keep their profile and layout entries and come back as
+    /// they were.
+    pub async fn install_package(
+        &self,
+        path: &Path,
+        allow_downgrade: bool,
+    ) -> OmniverlayResult<(InstalledPackage, InstallOutcome)> {
+        self.install_opened_package(path, &ExtensionPackage::open(path)?, allow_downgrade).await
+    }
+
+    async fn install_opened_package(
+        &self,
+        path: &Path,
+        package: &ExtensionPackage,
+        allow_downgrade: bool,
+    ) -> OmniverlayResult<(InstalledPackage, InstallOutcome)> {
+        let installer = PackageInstaller::new()?;
+
+        let id = &package.manifest.id;
+        let dir = installer.dir_of(id);
+
+        let replaced = {
+            let mut extension_manager = self.extension_manager.write().await;
+
+            // Nothing is stopped unless the package can be installed
+            extension_manager.signing_policy().admit_package(path, package)?;
+            installer.plan(package, allow_downgrade)?;
+
+            let mut replaced = Vec::new();
+
+            for extension_type in extension_manager.extensions_in_dir(&dir) {
+                replaced.push(extension_manager.detach_extension(&extension_type)?);
+            }
+
+            replaced
+        };
+
+        // Without holding the manager, which the disable hooks may well need
+        for detached in &replaced {
+            detached.stop().await;
+            detached.remove_data();
+        }
+
+        let mut extension_manager = self.extension_manager.write().await;
+
+        let installed = Self::replace_package(&mut extension_manager, &installer, package, allow_downgrade).await;
+
+        // The installed version comes back when the new one could not take its place
+        if installed.is_err() && !replaced.is_empty() {
+            for extension_type in extension_manager.extensions_in_dir(&dir) {
+                if let Err(e) = extension_manager.unregister_extension(&extension_type).await {
+                    warn!("Failed to unregister extension {}: {}", extension_type, e);
+                }
+            }
+
+            if let Err(e) = extension_manager.load_extension_dir(&dir).await {
+                error!("Failed to load extension package {} back: {}", id, e);
+            }
+        }
+
+        drop(extension_manager);
+
+        // Both create the missing instances, then apply their state and layout
+        self.profile_manager.read().await.save_data().await?;
+        self.layout_manager.read().await.save_data().await?;
+
+        installed
+    }
+
+    /// Install `package` in place of the stopped installed version, putting the files of the
+    /// installed version back if the new one fails to install or load
+    async fn replace_package(
+        extension_manager: &mut ExtensionManager,
+        installer: &PackageInstaller,
+        package: &ExtensionPackage,
+        allow_downgrade: bool,
+    ) -> OmniverlayResult<(InstalledPackage, InstallOutcome)> {
+        let installed = installer.install(package, allow_downgrade)?;
+
+        if let Err(e) = extension_manager.load_extension_dir(&installed.0.dir).await {
+            for extension_type in extension_manager.extensions_in_dir(&installed.0.dir) {
+                if let Err(e) = extension_manager.unregister_extension(&extension_type).await {
+                    warn!("Failed to unregister extension {}: {}", extension_type, e);
+                }
+            }
+
+            installer.restore_previous(&package.manifest.id)?;
+
+            return Err(e);
+        }
+
+        installer.remove_previous(&package.manifest.id);
+
+        Ok(installed)
+    }
+
+    /// Download the package of a catalog entry and install it, see `install_package`. Older
+    /// versions than the installed one are refused, and so is a package that is not the one the
+    /// entry describes.
+    pub async fn install_catalog_entry(
+        &self,
+        entry: &CatalogEntry,
+    ) -> OmniverlayResult<(InstalledPackage, InstallOutcome)> {
+        let path = catalog::download(entry).await?;
+        let installed = async {
+            let package = ExtensionPackage::open(&path)?;
+            entry.check_package(&package)?;
+
+            self.install_opened_package(&path, &package, false).await
+        }
+        .await;
+
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!("Failed to remove the downloaded package {}: {}", path.display(), e);
+        }
+
+        installed
+    }
+
     pub async fn get_extension_manager(&self) -> Arc<RwLock<ExtensionManager>> {
         self.extension_manager.clone()
     }
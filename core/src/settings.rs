@@ -0,0 +1,40 @@
+//! App settings, stored in `~/.omniverlay/settings.json`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::OmniverlayResult,
+    utils::fs::{get_omniverlay_dir, write_atomic},
+};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OmniverlaySettings {
+    /// Only load and install extensions signed by a trusted key, see `extensions::signing`
+    #[serde(default)]
+    pub require_signed_packages: bool,
+    /// Urls of the extension catalogs, see `extensions::catalog`
+    #[serde(default)]
+    pub catalogs: Vec<String>,
+}
+
+impl OmniverlaySettings {
+    /// Settings of the user, the defaults if they never changed them
+    pub fn load() -> OmniverlayResult<Self> {
+        let path = get_omniverlay_dir()?.join(SETTINGS_FILE_NAME);
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self) -> OmniverlayResult<()> {
+        let dir = get_omniverlay_dir()?;
+        std::fs::create_dir_all(&dir)?;
+
+        write_atomic(&dir.join(SETTINGS_FILE_NAME), serde_json::to_string_pretty(self)?)
+    }
+}
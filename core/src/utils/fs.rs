@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{io::Write, path::{Path, PathBuf}};
 
 use crate::errors::{OmniverlayError, OmniverlayResult};
 
@@ -20,4 +20,45 @@ pub fn get_omniverlay_dir() -> OmniverlayResult<PathBuf> {
     let home_dir = get_home_dir()?;
 
     Ok(home_dir.join(".omniverlay"))
-}
\ No newline at end of file
+}
+pub fn get_extensions_dir() -> OmniverlayResult<PathBuf> {
+    let omniverlay_dir = get_omniverlay_dir()?;
+    Ok(omniverlay_dir.join("extensions"))
+}
+
+/// Packages downloaded from catalogs until they are installed
+pub fn get_downloads_dir() -> OmniverlayResult<PathBuf> {
+    let omniverlay_dir = get_omniverlay_dir()?;
+    Ok(omniverlay_dir.join("downloads"))
+}
+
+/// Copies of the extension libraries loaded by the app, see `loader::load_library_copy`
+pub fn get_libraries_dir() -> OmniverlayResult<PathBuf> {
+    let omniverlay_dir = get_omniverlay_dir()?;
+    Ok(omniverlay_dir.join("libraries"))
+}
+
+pub fn get_storage_dir() -> OmniverlayResult<PathBuf> {
+    let omniverlay_dir = get_omniverlay_dir()?;
+    Ok(omniverlay_dir.join("storage"))
+}
+
+/// Write a file through a temporary file renamed over it, so a crash never leaves it half written
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> OmniverlayResult<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| OmniverlayError::IoError(std::io::Error::other(format!("invalid file path: {}", path.display()))))?;
+
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+
+    {
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+    }
+
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
@@ -1 +1,2 @@
-pub mod fs;
\ No newline at end of file
+pub mod fs;
+pub mod runtime;
@@ -0,0 +1,46 @@
+//! Spawning tasks from threads tokio does not know about.
+//!
+//! Native extensions may publish from threads of their own, where `tokio::spawn` panics. Types
+//! reachable from the `ExtensionHost` keep the runtime they were created on to spawn there instead.
+
+use std::future::Future;
+
+use log::warn;
+use tokio::runtime::Handle;
+
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeHandle(Option<Handle>);
+
+impl RuntimeHandle {
+    /// Runtime of the calling thread, if any
+    pub fn current() -> Self {
+        Self(Handle::try_current().ok())
+    }
+
+    /// Spawn `future` on the runtime of the calling thread, or on the one kept when there is none.
+    /// `future` is dropped with a warning when neither exists.
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match Handle::try_current().ok().or_else(|| self.0.clone()) {
+            Some(handle) => {
+                handle.spawn(future);
+            }
+            None => warn!("No tokio runtime to spawn a task on, it is dropped"),
+        }
+    }
+
+    /// Same as `spawn` for a blocking task
+    pub fn spawn_blocking<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match Handle::try_current().ok().or_else(|| self.0.clone()) {
+            Some(handle) => {
+                handle.spawn_blocking(task);
+            }
+            None => warn!("No tokio runtime to spawn a blocking task on, it is dropped"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    extensions::{
+        host::ExtensionHost,
+        usage::{ResourceBudget, USAGE_WINDOW},
+        Extension, ExtensionInfo, ExtensionManager, ExtensionStatus,
+    },
+};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+mod common;
+
+use common::status;
+
+/// Publishes on every tick
+struct Publisher {
+    info: Arc<Mutex<ExtensionInfo>>,
+    host: Option<ExtensionHost>,
+}
+
+#[async_trait]
+impl Extension for Publisher {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(10))
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        self.host.as_ref().unwrap().publish(&json!({ "tick": true }))
+    }
+}
+
+// The manager is not the one of the app, going over the budget must still reach it
+#[tokio::test]
+async fn over_budget_extensions_are_disabled_by_their_manager() {
+    let mut manager = ExtensionManager::new();
+
+    manager
+        .register_extension(Publisher {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "publisher".to_string(),
+                ..Default::default()
+            })),
+            host: None,
+        })
+        .await
+        .unwrap();
+
+    manager
+        .set_budget(
+            "publisher",
+            Some(ResourceBudget {
+                max_publish_rate: 1.0,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    manager.enable_extension("publisher").await.unwrap();
+
+    tokio::time::sleep(USAGE_WINDOW).await;
+
+    for _ in 0..100 {
+        if let ExtensionStatus::OverBudget { reason } = status(&manager, "publisher").await {
+            assert!(reason.contains("payloads per second"), "{reason}");
+            assert!(manager.list_extensions().await.unwrap()[0].state.is_enabled);
+
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("still {:?}", status(&manager, "publisher").await);
+}
@@ -0,0 +1,192 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use omniverlay_core::{
+    errors::OmniverlayError,
+    extensions::{
+        capabilities::Capability,
+        host::{ExtensionHost, StandaloneGrants},
+        storage::ExtensionStorage,
+        ExtensionInfo,
+    },
+};
+use tokio::sync::Mutex;
+
+fn filesystem(path: &str, write: bool) -> Capability {
+    Capability::Filesystem {
+        path: PathBuf::from(path),
+        write,
+    }
+}
+
+#[test]
+fn filesystem_covers_paths_under_the_granted_one() {
+    let granted = filesystem("/data/logs", false);
+
+    assert!(granted.covers(&filesystem("/data/logs", false)));
+    assert!(granted.covers(&filesystem("/data/logs/today/app.log", false)));
+    assert!(!granted.covers(&filesystem("/data", false)));
+    assert!(!granted.covers(&filesystem("/data/logs-old", false)));
+}
+
+#[test]
+fn filesystem_write_needs_a_write_grant() {
+    assert!(!filesystem("/data", false).covers(&filesystem("/data/file", true)));
+    assert!(filesystem("/data", true).covers(&filesystem("/data/file", true)));
+    assert!(filesystem("/data", true).covers(&filesystem("/data/file", false)));
+}
+
+#[test]
+fn filesystem_parent_directories_do_not_escape_the_grant() {
+    let granted = filesystem("/data/logs", false);
+
+    assert!(!granted.covers(&filesystem("/data/logs/../secrets", false)));
+    assert!(!granted.covers(&filesystem("/data/logs/a/../../../etc/passwd", false)));
+    assert!(granted.covers(&filesystem("/data/logs/a/../b", false)));
+    assert!(granted.covers(&filesystem("/data/logs/./b", false)));
+}
+
+#[test]
+fn filesystem_relative_paths_are_not_covered() {
+    for granted in [".", "", "assets", "./assets"] {
+        let granted = filesystem(granted, true);
+
+        assert!(!granted.covers(&filesystem("/etc/passwd", false)), "{granted}");
+        assert!(!granted.covers(&filesystem("assets/x", false)), "{granted}");
+        assert!(!granted.covers(&filesystem("", false)), "{granted}");
+    }
+
+    assert!(!filesystem("/", false).covers(&filesystem("assets/../../assets/x", false)));
+}
+
+#[test]
+fn filesystem_grant_is_normalized_too() {
+    let granted = filesystem("/data/logs/../cache", false);
+
+    assert!(granted.covers(&filesystem("/data/cache/file", false)));
+    assert!(!granted.covers(&filesystem("/data/logs/file", false)));
+}
+
+#[test]
+fn other_capabilities_only_cover_themselves() {
+    assert!(Capability::SystemInfo.covers(&Capability::SystemInfo));
+    assert!(!Capability::SystemInfo.covers(&Capability::ProcessList));
+    assert!(!Capability::Network.covers(&filesystem("/", false)));
+    assert!(!filesystem("/", true).covers(&Capability::SpawnProcess));
+}
+
+fn standalone_host(declared: &[Capability]) -> (tempfile::TempDir, ExtensionHost, StandaloneGrants) {
+    let dir = tempfile::tempdir().unwrap();
+    let info = Arc::new(Mutex::new(ExtensionInfo {
+        name: "clock".to_string(),
+        ..Default::default()
+    }));
+    let storage = ExtensionStorage::in_dir(dir.path().join("storage"));
+    let (host, grants) = ExtensionHost::standalone(info, "clock", storage, declared);
+
+    (dir, host, grants)
+}
+
+#[tokio::test]
+async fn only_declared_capabilities_are_granted() {
+    let (_dir, host, grants) = standalone_host(&[filesystem("/data", false), Capability::SystemInfo]);
+
+    grants.grant(&[
+        filesystem("/data/logs", false),
+        filesystem("/etc", false),
+        Capability::Network,
+    ]);
+
+    assert!(host.is_granted(&filesystem("/data/logs/app.log", false)));
+    assert!(!host.is_granted(&filesystem("/data/other", false)));
+    assert!(!host.is_granted(&filesystem("/etc/passwd", false)));
+    assert!(!host.is_granted(&Capability::Network));
+    assert!(!host.is_granted(&Capability::SystemInfo));
+}
+
+#[tokio::test]
+async fn write_cannot_be_granted_over_a_read_declaration() {
+    let (_dir, host, grants) = standalone_host(&[filesystem("/data", false)]);
+
+    grants.grant(&[filesystem("/data", true)]);
+
+    assert!(!host.is_granted(&filesystem("/data/file", true)));
+    assert!(!host.is_granted(&filesystem("/data/file", false)));
+}
+
+#[tokio::test]
+async fn revoked_capability_is_denied() {
+    let (_dir, host, grants) = standalone_host(&[Capability::SpawnProcess]);
+
+    assert!(host.require(&Capability::SpawnProcess).is_ok());
+
+    grants.grant(&[]);
+
+    assert!(matches!(
+        host.require(&Capability::SpawnProcess),
+        Err(OmniverlayError::PermissionDenied(name, _)) if name == "clock"
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symbolic_links_do_not_escape_the_grant() {
+    let (dir, host, grants) = standalone_host(&[Capability::Filesystem {
+        path: PathBuf::from("/"),
+        write: true,
+    }]);
+
+    let granted = dir.path().join("granted");
+    let outside = dir.path().join("outside");
+    std::fs::create_dir_all(&granted).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+    std::fs::write(granted.join("own.txt"), "own").unwrap();
+
+    std::os::unix::fs::symlink(&outside, granted.join("link")).unwrap();
+    std::os::unix::fs::symlink(outside.join("secret.txt"), granted.join("secret.txt")).unwrap();
+    std::os::unix::fs::symlink(outside.join("missing.txt"), granted.join("dangling.txt")).unwrap();
+
+    grants.grant(&[Capability::Filesystem {
+        path: granted.clone(),
+        write: true,
+    }]);
+
+    assert_eq!(host.read_file(&granted.join("own.txt")).await.unwrap(), b"own");
+
+    for path in [granted.join("link/secret.txt"), granted.join("secret.txt")] {
+        assert!(
+            matches!(host.read_file(&path).await, Err(OmniverlayError::PermissionDenied(..))),
+            "{}",
+            path.display()
+        );
+    }
+
+    assert!(host.read_dir(&granted.join("link")).await.is_err());
+    assert!(host.write_file(&granted.join("link/new.txt"), "x").await.is_err());
+    assert!(host.write_file(&granted.join("secret.txt"), "x").await.is_err());
+    assert!(host.write_file(&granted.join("dangling.txt"), "x").await.is_err());
+
+    assert_eq!(std::fs::read_to_string(outside.join("secret.txt")).unwrap(), "secret");
+    assert!(!outside.join("new.txt").exists() && !outside.join("missing.txt").exists());
+}
+
+#[tokio::test]
+async fn relative_paths_are_refused() {
+    let (_dir, host, _grants) = standalone_host(&[Capability::Filesystem {
+        path: PathBuf::from("/"),
+        write: true,
+    }]);
+
+    for path in ["Cargo.toml", "./Cargo.toml", "src/../Cargo.toml"] {
+        assert!(
+            matches!(
+                host.read_file(Path::new(path)).await,
+                Err(OmniverlayError::PermissionDenied(..))
+            ),
+            "{path}"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{path::Path, sync::OnceLock};
+
+use omniverlay_core::{
+    errors::OmniverlayError,
+    extensions::{
+        catalog::{self, Catalog, ExtensionCatalog},
+        manifest::MANIFEST_FILE_NAME,
+    },
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use url::Url;
+
+const PACKAGE: &[u8] = b"not opened by the catalog";
+
+/// Home directory shared by the tests of this file, with `dev.test.clock` 1.0.0 installed
+fn home() -> &'static Path {
+    static HOME: OnceLock<TempDir> = OnceLock::new();
+
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".omniverlay/extensions/dev.test.clock");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            "id = \"dev.test.clock\"\nname = \"Clock\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+
+        std::env::set_var("HOME", home.path());
+
+        home
+    })
+    .path()
+}
+
+fn entry(id: &str, version: &str, url: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": id,
+        "version": version,
+        "url": url,
+        "sha256": hex::encode(Sha256::digest(PACKAGE)),
+    })
+}
+
+/// Write `catalog.json` listing `extensions` into `dir`, returning its url
+fn write_catalog(dir: &Path, extensions: serde_json::Value) -> Url {
+    let path = dir.join("catalog.json");
+    std::fs::write(&path, json!({ "name": "Team", "extensions": extensions }).to_string()).unwrap();
+
+    Url::from_file_path(path).unwrap()
+}
+
+#[tokio::test]
+async fn newest_compatible_version_is_offered() {
+    home();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut too_new = entry("dev.test.clock", "3.0.0", "clock-3.0.0.omnipkg");
+    too_new["min_host_version"] = json!("999.0.0");
+
+    let url = write_catalog(
+        dir.path(),
+        json!([
+            entry("dev.test.clock", "1.2.0", "clock-1.2.0.omnipkg"),
+            too_new,
+            entry("dev.test.clock", "2.0.0", "clock-2.0.0.omnipkg"),
+            entry("dev.test.clock", "0.9.0", "clock-0.9.0.omnipkg"),
+            entry("dev.test.weather", "1.0.0", "weather.omnipkg"),
+        ]),
+    );
+
+    let available = ExtensionCatalog::new(vec![url.clone()]).available().await.unwrap();
+    assert_eq!(available.len(), 2);
+
+    let clock = available.iter().find(|a| a.entry.id == "dev.test.clock").unwrap();
+    assert_eq!(clock.entry.version.to_string(), "2.0.0");
+    assert_eq!(clock.entry.url, url.join("clock-2.0.0.omnipkg").unwrap().to_string());
+    assert_eq!(clock.catalog, url.to_string());
+    assert_eq!(
+        clock.installed.as_ref().map(|v| v.to_string()).as_deref(),
+        Some("1.0.0")
+    );
+    assert!(clock.update_available);
+
+    let weather = available.iter().find(|a| a.entry.id == "dev.test.weather").unwrap();
+    assert!(weather.installed.is_none());
+    assert!(!weather.update_available);
+}
+
+#[tokio::test]
+async fn no_update_without_a_newer_version() {
+    home();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = write_catalog(
+        dir.path(),
+        json!([
+            entry("dev.test.clock", "1.0.0", "clock-1.0.0.omnipkg"),
+            entry("dev.test.clock", "0.9.0", "clock-0.9.0.omnipkg"),
+        ]),
+    );
+
+    let catalog = ExtensionCatalog::new(vec![url]);
+
+    let available = catalog.available().await.unwrap();
+    assert_eq!(available.len(), 1);
+    assert!(!available[0].update_available);
+
+    assert!(catalog.updates().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn package_urls_keep_the_scheme_of_the_catalog() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = write_catalog(
+        dir.path(),
+        json!([entry("dev.test.clock", "1.0.0", "http://127.0.0.1/clock.omnipkg")]),
+    );
+
+    assert!(matches!(
+        Catalog::fetch(&url).await,
+        Err(OmniverlayError::CatalogError(_))
+    ));
+
+    let package_url = Url::from_file_path(dir.path().join("clock.omnipkg")).unwrap();
+    let url = write_catalog(
+        dir.path(),
+        json!([entry("dev.test.clock", "1.0.0", package_url.as_str())]),
+    );
+
+    let catalog = Catalog::fetch(&url).await.unwrap();
+    assert_eq!(catalog.extensions[0].url, package_url.to_string());
+}
+
+#[tokio::test]
+async fn downloads_are_checked_against_the_catalog() {
+    let home = home();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("clock.omnipkg"), PACKAGE).unwrap();
+
+    let mut tampered = entry("dev.test.clock", "1.1.0", "clock.omnipkg");
+    tampered["sha256"] = json!(hex::encode(Sha256::digest(b"another package")));
+
+    let url = write_catalog(
+        dir.path(),
+        json!([entry("dev.test.clock", "1.2.0", "clock.omnipkg"), tampered]),
+    );
+    let catalog = Catalog::fetch(&url).await.unwrap();
+
+    let path = catalog::download(&catalog.extensions[0]).await.unwrap();
+    assert!(path.starts_with(home.join(".omniverlay/downloads")));
+    assert_eq!(std::fs::read(&path).unwrap(), PACKAGE);
+
+    match catalog::download(&catalog.extensions[1]).await {
+        Err(OmniverlayError::CatalogError(message)) => assert!(message.contains("checksum")),
+        result => panic!("expected a checksum error, got {result:?}"),
+    }
+}
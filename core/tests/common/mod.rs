@@ -0,0 +1,26 @@
+//! Helpers shared by the integration tests, each test file uses only some of them
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use omniverlay_core::extensions::{ExtensionManager, ExtensionStatus};
+use serde_json::Value;
+
+/// First payload published by the extension `name`, waiting up to 5 seconds for it
+pub async fn published(manager: &ExtensionManager, name: &str) -> Value {
+    for _ in 0..100 {
+        if let Some(data) = manager.get_extension_data(name).unwrap() {
+            return data;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    panic!("{name} published nothing");
+}
+
+pub async fn status(manager: &ExtensionManager, name: &str) -> ExtensionStatus {
+    let infos = manager.list_extensions().await.unwrap();
+
+    infos.into_iter().find(|info| info.name == name).unwrap().status
+}
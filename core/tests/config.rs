@@ -0,0 +1,76 @@
+use omniverlay_core::{
+    errors::OmniverlayError,
+    extensions::config::{ConfigValue, ConfigValueType, ExtensionConfig, OmniverlayConfig},
+};
+
+#[derive(Debug, PartialEq, OmniverlayConfig)]
+#[config(category = "General")]
+struct ClockConfig {
+    #[config(description = "Time zone to display", default = "UTC")]
+    time_zone: String,
+    #[config(category = "Style", description = "Hour format", default = "24h", choices = ["12h", "24h"])]
+    format: String,
+    #[config(name = "refresh", default = 1_000u64)]
+    refresh_ms: u64,
+    show_seconds: bool,
+}
+
+#[test]
+fn defaults_fill_every_category() {
+    let config = ClockConfig::default_config().unwrap();
+
+    let general = config.get_category("General").unwrap();
+    assert!(matches!(&general.get_value("time_zone").unwrap().value, ConfigValueType::String(zone) if zone == "UTC"));
+    assert!(matches!(general.get_value("refresh").unwrap().value, ConfigValueType::Int(1_000)));
+    assert!(matches!(general.get_value("show_seconds").unwrap().value, ConfigValueType::Bool(false)));
+
+    let ConfigValueType::Enum(format) = &config.get_category("Style").unwrap().get_value("format").unwrap().value else {
+        panic!("format is not a list of choices");
+    };
+    assert_eq!(format.current, "24h");
+    assert_eq!(format.values, ["12h", "24h"]);
+}
+
+#[test]
+fn round_trips_through_a_config() {
+    let clock = ClockConfig {
+        time_zone: "Europe/Paris".to_string(),
+        format: "12h".to_string(),
+        refresh_ms: 250,
+        show_seconds: true,
+    };
+
+    assert_eq!(ClockConfig::from_config(&clock.to_config().unwrap()).unwrap(), clock);
+}
+
+#[test]
+fn missing_values_keep_their_default() {
+    let config = ExtensionConfig { categories: Vec::new() };
+
+    assert_eq!(ClockConfig::from_config(&config).unwrap(), ClockConfig::default_values());
+}
+
+#[test]
+fn values_of_the_wrong_type_are_an_error() {
+    let mut config = ClockConfig::default_config().unwrap();
+    config.set_value("General", "refresh", ConfigValue::new(String::new(), ConfigValueType::Bool(true)));
+
+    let error = ClockConfig::from_config(&config).unwrap_err();
+
+    assert!(matches!(&error, OmniverlayError::InvalidConfigValue(key) if key == "General.refresh"), "{error}");
+}
+
+#[test]
+fn integers_out_of_range_are_an_error() {
+    let clock = ClockConfig {
+        refresh_ms: u64::MAX,
+        ..ClockConfig::default_values()
+    };
+
+    assert!(matches!(clock.to_config(), Err(OmniverlayError::InvalidConfigValue(..))));
+
+    let mut config = ClockConfig::default_config().unwrap();
+    config.set_value("General", "refresh", ConfigValue::new(String::new(), ConfigValueType::Int(-1)));
+
+    assert!(ClockConfig::from_config(&config).is_err());
+}
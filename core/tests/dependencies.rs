@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use omniverlay_core::{
+    errors::OmniverlayError,
+    extensions::dependencies::{resolve_startup_order, DependencyNode},
+};
+use semver::{Version, VersionReq};
+
+fn node(id: &str, version: &str, dependencies: &[(&str, &str)]) -> DependencyNode {
+    DependencyNode {
+        name: id.to_string(),
+        id: id.to_string(),
+        version: Some(Version::parse(version).unwrap()),
+        dependencies: dependencies
+            .iter()
+            .map(|(id, requirement)| (id.to_string(), VersionReq::parse(requirement).unwrap()))
+            .collect(),
+    }
+}
+
+fn position(order: &[String], name: &str) -> usize {
+    order
+        .iter()
+        .position(|n| n == name)
+        .unwrap_or_else(|| panic!("{name} is not in the startup order {order:?}"))
+}
+
+fn error_of<'a>(errors: &'a [(String, OmniverlayError)], name: &str) -> &'a OmniverlayError {
+    &errors
+        .iter()
+        .find(|(n, _)| n == name)
+        .unwrap_or_else(|| panic!("{name} has no startup error"))
+        .1
+}
+
+#[test]
+fn dependencies_start_first() {
+    let nodes = [
+        node("clock", "1.0.0", &[("time", "^1.2")]),
+        node("time", "1.4.0", &[("core", "*")]),
+        node("core", "0.1.0", &[]),
+        node("alone", "2.0.0", &[]),
+    ];
+
+    let startup = resolve_startup_order(&nodes);
+
+    assert!(startup.errors.is_empty(), "{:?}", startup.errors);
+    assert_eq!(startup.order.len(), 4);
+    assert!(position(&startup.order, "core") < position(&startup.order, "time"));
+    assert!(position(&startup.order, "time") < position(&startup.order, "clock"));
+}
+
+#[test]
+fn missing_dependency_is_reported_with_its_dependents() {
+    let nodes = [
+        node("clock", "1.0.0", &[("time", "^1")]),
+        node("time", "1.0.0", &[("core", "^1")]),
+    ];
+
+    let startup = resolve_startup_order(&nodes);
+
+    assert!(startup.order.is_empty());
+    assert!(matches!(error_of(&startup.errors, "time"), OmniverlayError::MissingDependency(..)));
+    assert!(matches!(error_of(&startup.errors, "clock"), OmniverlayError::DependencyFailed(..)));
+}
+
+#[test]
+fn incompatible_version_is_refused() {
+    let nodes = [node("clock", "1.0.0", &[("time", "^2")]), node("time", "1.4.0", &[])];
+
+    let startup = resolve_startup_order(&nodes);
+
+    assert_eq!(startup.order, vec!["time".to_string()]);
+    assert!(matches!(
+        error_of(&startup.errors, "clock"),
+        OmniverlayError::IncompatibleDependency(..)
+    ));
+}
+
+#[test]
+fn unversioned_dependency_only_matches_any_version() {
+    let unversioned = DependencyNode {
+        name: "time".to_string(),
+        id: "time".to_string(),
+        version: None,
+        dependencies: HashMap::new(),
+    };
+
+    let startup = resolve_startup_order(&[node("clock", "1.0.0", &[("time", "*")]), unversioned.clone()]);
+    assert!(startup.errors.is_empty(), "{:?}", startup.errors);
+
+    let startup = resolve_startup_order(&[node("clock", "1.0.0", &[("time", "^1")]), unversioned]);
+    assert!(matches!(
+        error_of(&startup.errors, "clock"),
+        OmniverlayError::IncompatibleDependency(..)
+    ));
+}
+
+#[test]
+fn cycles_are_left_out() {
+    let nodes = [
+        node("a", "1.0.0", &[("b", "*")]),
+        node("b", "1.0.0", &[("a", "*")]),
+        node("c", "1.0.0", &[]),
+    ];
+
+    let startup = resolve_startup_order(&nodes);
+
+    assert_eq!(startup.order, vec!["c".to_string()]);
+    assert!(startup
+        .errors
+        .iter()
+        .any(|(_, error)| matches!(error, OmniverlayError::DependencyCycle(_))));
+    assert!(startup.errors.iter().any(|(name, _)| name == "a"));
+    assert!(startup.errors.iter().any(|(name, _)| name == "b"));
+}
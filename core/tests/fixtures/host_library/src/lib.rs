@@ -0,0 +1,118 @@
+//! Extension library loaded by `tests/library.rs`. It calls the host from its own copy of the core
+//! and of tokio, which has no runtime, and publishes what it got back.
+
+use std::{path::PathBuf, process::Command, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::{OmniverlayError, OmniverlayResult},
+    extensions::{capabilities::Capability, host::ExtensionHost, Extension, ExtensionInfo},
+};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+/// Directory the extension works in, set by the test
+const DIR_VARIABLE: &str = "OMNIVERLAY_HOST_LIBRARY_DIR";
+/// Program the extension starts with `--version`, set by the test
+const PROGRAM_VARIABLE: &str = "OMNIVERLAY_HOST_LIBRARY_PROGRAM";
+
+fn variable(name: &str) -> OmniverlayResult<String> {
+    std::env::var(name).map_err(|e| OmniverlayError::ExtensionRuntime(format!("{name}: {e}")))
+}
+
+struct HostCalls {
+    info: Arc<Mutex<ExtensionInfo>>,
+    host: Option<ExtensionHost>,
+    done: bool,
+}
+
+impl HostCalls {
+    fn new() -> Self {
+        let dir = PathBuf::from(variable(DIR_VARIABLE).unwrap_or_default());
+
+        Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "host_calls".to_string(),
+                capabilities: vec![Capability::Filesystem { path: dir, write: true }, Capability::SpawnProcess],
+                ..Default::default()
+            })),
+            host: None,
+            done: false,
+        }
+    }
+
+    async fn call_host(host: &ExtensionHost) -> OmniverlayResult<Value> {
+        let dir = PathBuf::from(variable(DIR_VARIABLE)?);
+        let file = dir.join("hello.txt");
+
+        host.write_file(&file, "hello from the library").await?;
+        let read = String::from_utf8_lossy(&host.read_file(&file).await?).into_owned();
+
+        let entries: Vec<String> = host
+            .read_dir(&dir)
+            .await?
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+
+        let echo: Value = host.request(host.extension_name(), "echo", &json!({ "ping": 1 })).await?;
+
+        let status = host
+            .spawn_process(Command::new(variable(PROGRAM_VARIABLE)?).arg("--version"))?
+            .wait()?;
+
+        Ok(json!({
+            "read": read,
+            "entries": entries,
+            "echo": echo,
+            "spawned": status.success(),
+        }))
+    }
+}
+
+#[async_trait]
+impl Extension for HostCalls {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        self.host
+            .as_ref()
+            .unwrap()
+            .handle("echo", |params: Value| async move { Ok::<_, OmniverlayError>(params) });
+
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+
+    // Requests are only answered once the extension is running
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        if self.done {
+            return Ok(());
+        }
+
+        self.done = true;
+
+        let host = self.host.as_ref().unwrap();
+
+        match Self::call_host(host).await {
+            Ok(result) => host.publish(&result),
+            Err(e) => host.publish(&json!({ "error": e.to_string() })),
+        }
+    }
+}
+
+omniverlay_core::export_extension!(HostCalls::new);
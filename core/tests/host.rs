@@ -0,0 +1,113 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::{OmniverlayError, OmniverlayResult},
+    extensions::{
+        host::ExtensionHost, supervisor::RestartPolicy, Extension, ExtensionInfo, ExtensionManager,
+        ExtensionStatus,
+    },
+};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+mod common;
+
+use common::status;
+
+/// Publishes when enabled, and runs `work` in the background once it is running
+struct Worker {
+    info: Arc<Mutex<ExtensionInfo>>,
+    host: Option<ExtensionHost>,
+    work: fn() -> OmniverlayResult<()>,
+}
+
+impl Worker {
+    fn new(work: fn() -> OmniverlayResult<()>) -> Self {
+        Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "worker".to_string(),
+                ..Default::default()
+            })),
+            host: None,
+            work,
+        }
+    }
+}
+
+#[async_trait]
+impl Extension for Worker {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        let host = self.host.as_ref().unwrap();
+
+        host.publish(&json!({ "started": true }))?;
+        let work = self.work;
+
+        host.schedule(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            work()
+        });
+
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+}
+
+async fn run_worker(work: fn() -> OmniverlayResult<()>) -> ExtensionManager {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(Worker::new(work)).await.unwrap();
+    manager.set_restart_policy("worker", RestartPolicy::Never).await.unwrap();
+    manager.enable_extension("worker").await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    manager
+}
+
+async fn failure(manager: &ExtensionManager) -> String {
+    for _ in 0..50 {
+        if let ExtensionStatus::Failed { error } = status(manager, "worker").await {
+            return error;
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("still {:?}", status(manager, "worker").await);
+}
+
+#[tokio::test]
+async fn published_payloads_reach_the_manager() {
+    let manager = run_worker(|| Ok(())).await;
+
+    assert_eq!(manager.get_extension_data("worker").unwrap(), Some(json!({ "started": true })));
+    assert_eq!(status(&manager, "worker").await, ExtensionStatus::Running);
+}
+
+#[tokio::test]
+async fn failed_scheduled_work_fails_the_extension() {
+    let manager = run_worker(|| Err(OmniverlayError::ExtensionRuntime("sensor is gone".to_string()))).await;
+
+    let error = failure(&manager).await;
+    assert!(error.contains("sensor is gone"), "{error}");
+}
+
+#[tokio::test]
+async fn panicking_scheduled_work_fails_the_extension() {
+    let manager = run_worker(|| panic!("sensor exploded")).await;
+
+    let error = failure(&manager).await;
+    assert!(error.contains("sensor exploded"), "{error}");
+}
+
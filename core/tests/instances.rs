@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    extensions::{
+        channel::data_event_name,
+        instances::{instance_name, parse_instance_name, MAX_INSTANCES},
+        Extension, ExtensionInfo, ExtensionManager,
+    },
+};
+use tokio::sync::Mutex;
+
+struct Clock {
+    info: Arc<Mutex<ExtensionInfo>>,
+}
+
+impl Clock {
+    fn named(name: &str) -> Self {
+        Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: name.to_string(),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+#[async_trait]
+impl Extension for Clock {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+}
+
+async fn names(manager: &ExtensionManager) -> Vec<String> {
+    let mut names: Vec<String> = manager.list_extensions().await.unwrap().into_iter().map(|info| info.name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn instance_names_round_trip() {
+    assert_eq!(instance_name("clock", 1), "clock");
+    assert_eq!(instance_name("clock", 3), "clock#3");
+
+    assert_eq!(parse_instance_name("clock"), ("clock", 1));
+    assert_eq!(parse_instance_name("clock#3"), ("clock", 3));
+    // Not instance numbers, the whole name is the extension
+    assert_eq!(parse_instance_name("clock#1"), ("clock#1", 1));
+    assert_eq!(parse_instance_name("clock#utc"), ("clock#utc", 1));
+}
+
+#[test]
+fn data_event_names_only_use_characters_tauri_accepts() {
+    assert_eq!(data_event_name("clock"), "Extension://clock/data");
+    assert_eq!(data_event_name("clock#2"), "Extension://clock:232/data");
+    assert_eq!(data_event_name("My clock.v2"), "Extension://My:20clock:2Ev2/data");
+    assert_eq!(data_event_name("a:b/c"), "Extension://a:3Ab:2Fc/data");
+    assert_eq!(data_event_name("é"), "Extension://:C3:A9/data");
+
+    for name in ["clock#2", "My clock.v2", "a:b/c", "é"] {
+        let event = data_event_name(name);
+        let topic = &event["Extension://".len()..event.len() - "/data".len()];
+
+        assert!(
+            topic.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':')),
+            "{event}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn instances_are_numbered_from_two() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension_type(|| Clock::named("clock")).await.unwrap();
+
+    assert_eq!(manager.create_extension_instance("clock").await.unwrap(), "clock#2");
+    assert_eq!(manager.create_extension_instance("clock").await.unwrap(), "clock#3");
+
+    let infos = manager.list_extensions().await.unwrap();
+    assert!(infos.iter().all(|info| info.extension_type == "clock"));
+    assert_eq!(names(&manager).await, ["clock", "clock#2", "clock#3"]);
+}
+
+#[tokio::test]
+async fn instances_are_limited() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension_type(|| Clock::named("clock")).await.unwrap();
+
+    for _ in 1..MAX_INSTANCES {
+        manager.create_extension_instance("clock").await.unwrap();
+    }
+
+    let error = manager.create_extension_instance("clock").await.unwrap_err();
+    assert!(error.to_string().contains("already has"), "{error}");
+}
+
+#[tokio::test]
+async fn missing_instances_are_created_from_their_names() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension_type(|| Clock::named("clock")).await.unwrap();
+
+    let wanted = ["clock#4".to_string(), "weather#2".to_string(), "clock".to_string()];
+    manager.create_missing_instances(wanted.iter()).await;
+
+    // Unknown extensions are ignored
+    assert_eq!(names(&manager).await, ["clock", "clock#4"]);
+}
+
+#[tokio::test]
+async fn single_instance_extensions_refuse_more_instances() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(Clock::named("clock")).await.unwrap();
+
+    assert!(manager.create_extension_instance("clock").await.is_err());
+    assert_eq!(names(&manager).await, ["clock"]);
+}
+
+#[tokio::test]
+async fn first_instance_cannot_be_removed() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension_type(|| Clock::named("clock")).await.unwrap();
+
+    assert!(manager.remove_extension_instance("clock").await.is_err());
+    assert_eq!(names(&manager).await, ["clock"]);
+}
+
+#[tokio::test]
+async fn names_with_an_instance_separator_are_refused() {
+    let mut manager = ExtensionManager::new();
+
+    assert!(manager.register_extension(Clock::named("clock#2")).await.is_err());
+    assert!(manager.register_extension_type(|| Clock::named("clock#utc")).await.is_err());
+    assert!(names(&manager).await.is_empty());
+}
+
+#[tokio::test]
+async fn empty_names_and_control_characters_are_refused() {
+    let mut manager = ExtensionManager::new();
+
+    assert!(manager.register_extension(Clock::named("")).await.is_err());
+    assert!(manager.register_extension(Clock::named("  ")).await.is_err());
+    assert!(manager.register_extension_type(|| Clock::named("clock\n")).await.is_err());
+    assert!(names(&manager).await.is_empty());
+
+    manager.register_extension(Clock::named("My clock.v2")).await.unwrap();
+    assert_eq!(names(&manager).await, ["My clock.v2"]);
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use omniverlay_core::extensions::{capabilities::Capability, ExtensionManager, ExtensionState};
+use serde_json::json;
+
+mod common;
+
+use common::published;
+
+/// `tests/fixtures/host_library`, built as a dev-dependency next to the tests so it uses the same
+/// crates as the core they link
+fn fixture_library() -> PathBuf {
+    let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let prefix = format!("{}host_library_fixture", std::env::consts::DLL_PREFIX);
+
+    std::fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION))
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(&prefix)))
+        .max_by_key(|path| path.metadata().and_then(|metadata| metadata.modified()).ok())
+        .unwrap_or_else(|| panic!("no {prefix} library in {}", deps.display()))
+}
+
+#[tokio::test]
+async fn library_reaches_the_host() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Read by the library when its extension is created
+    std::env::set_var("OMNIVERLAY_HOST_LIBRARY_DIR", dir.path());
+    std::env::set_var("OMNIVERLAY_HOST_LIBRARY_PROGRAM", env!("CARGO"));
+
+    let mut manager = ExtensionManager::new();
+    manager.load_extension_library(&fixture_library(), None).await.unwrap();
+
+    let state = ExtensionState {
+        is_enabled: false,
+        config: None,
+        granted_capabilities: vec![
+            Capability::Filesystem {
+                path: dir.path().to_path_buf(),
+                write: true,
+            },
+            Capability::SpawnProcess,
+        ],
+    };
+
+    manager.update_extension_state("host_calls", state).await.unwrap();
+    manager.enable_extension("host_calls").await.unwrap();
+
+    assert_eq!(
+        published(&manager, "host_calls").await,
+        json!({
+            "read": "hello from the library",
+            "entries": ["hello.txt"],
+            "echo": { "ping": 1 },
+            "spawned": true,
+        })
+    );
+}
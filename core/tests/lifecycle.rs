@@ -0,0 +1,157 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    extensions::{host::ExtensionHost, Extension, ExtensionInfo, ExtensionManager, ExtensionStatus},
+};
+use tokio::sync::Mutex;
+
+mod common;
+
+use common::status;
+
+#[derive(Default)]
+struct Stubborn {
+    info: Arc<Mutex<ExtensionInfo>>,
+    host: Option<ExtensionHost>,
+    // Whether `preview_payloads` gives up once cancelled
+    cooperative: bool,
+    returned: Arc<AtomicBool>,
+}
+
+impl Stubborn {
+    fn new(cooperative: bool) -> (Self, Arc<AtomicBool>) {
+        let returned = Arc::new(AtomicBool::new(false));
+
+        let stubborn = Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "stubborn".to_string(),
+                ..Default::default()
+            })),
+            cooperative,
+            returned: returned.clone(),
+            ..Default::default()
+        };
+
+        (stubborn, returned)
+    }
+}
+
+#[async_trait]
+impl Extension for Stubborn {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+
+    // Blocks its thread, never awaits
+    async fn preview_payloads(&mut self) -> OmniverlayResult<Vec<serde_json::Value>> {
+        let host = self.host.as_ref().unwrap();
+        let start = Instant::now();
+
+        while start.elapsed() < Duration::from_secs(4) {
+            if self.cooperative && host.is_cancelled() {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        self.returned.store(true, Ordering::SeqCst);
+
+        Ok(Vec::new())
+    }
+}
+
+#[derive(Default)]
+struct Panicking {
+    info: Arc<Mutex<ExtensionInfo>>,
+}
+
+#[async_trait]
+impl Extension for Panicking {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        panic!("enable is broken");
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+}
+
+#[tokio::test]
+async fn blocked_hooks_are_cancelled() {
+    let (stubborn, returned) = Stubborn::new(true);
+
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(stubborn).await.unwrap();
+
+    let start = Instant::now();
+    assert!(manager.preview_payloads("stubborn").await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(3), "{:?}", start.elapsed());
+
+    // The hook saw it was cancelled and let go of the extension
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(returned.load(Ordering::SeqCst));
+
+    manager.enable_extension("stubborn").await.unwrap();
+    assert_eq!(status(&manager, "stubborn").await, ExtensionStatus::Running);
+}
+
+#[tokio::test]
+async fn calls_fail_fast_while_a_hook_is_stuck() {
+    let (stubborn, returned) = Stubborn::new(false);
+
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(stubborn).await.unwrap();
+
+    assert!(manager.preview_payloads("stubborn").await.is_err());
+
+    // Does not wait for the lock held by the stuck hook
+    let start = Instant::now();
+    assert!(manager.enable_extension("stubborn").await.is_err());
+    assert!(start.elapsed() < Duration::from_millis(500), "{:?}", start.elapsed());
+    assert!(!returned.load(Ordering::SeqCst));
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(returned.load(Ordering::SeqCst));
+
+    manager.enable_extension("stubborn").await.unwrap();
+    assert_eq!(status(&manager, "stubborn").await, ExtensionStatus::Running);
+}
+
+#[tokio::test]
+async fn panicking_hooks_fail_the_extension() {
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(Panicking {
+        info: Arc::new(Mutex::new(ExtensionInfo {
+            name: "panicking".to_string(),
+            ..Default::default()
+        })),
+    }).await.unwrap();
+
+    assert!(manager.enable_extension("panicking").await.is_err());
+    assert!(matches!(status(&manager, "panicking").await, ExtensionStatus::Failed { .. }));
+}
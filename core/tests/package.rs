@@ -0,0 +1,257 @@
+use std::{fs::File, io::Write, path::Path, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::{OmniverlayError, OmniverlayResult},
+    extensions::{
+        package::{ExtensionPackage, CHECKSUMS_FILE_NAME},
+        Extension, ExtensionInfo, ExtensionManager,
+    },
+};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tokio::sync::{Mutex, RwLock};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+const MANIFEST: &str = "id = \"dev.test.clock\"\nname = \"Clock\"\nversion = \"1.0.0\"\n";
+const EXTENSION: &str = "{ \"command\": \"clock\" }";
+
+fn sha256(contents: &[u8]) -> String {
+    hex::encode(Sha256::digest(contents))
+}
+
+/// Zip `files` as they are, names included
+fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+    let mut writer = ZipWriter::new(File::create(path).unwrap());
+
+    for (name, contents) in files {
+        writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+        writer.write_all(contents).unwrap();
+    }
+
+    writer.finish().unwrap();
+}
+
+/// Checksums of `files` in the format of `sha256sum`
+fn checksums(files: &[(&str, &[u8])]) -> String {
+    files
+        .iter()
+        .map(|(name, contents)| format!("{}  {}\n", sha256(contents), name))
+        .collect()
+}
+
+fn open_error(dir: &TempDir, files: &[(&str, &[u8])]) -> String {
+    let path = dir.path().join("test.omnipkg");
+    write_zip(&path, files);
+
+    match ExtensionPackage::open(&path) {
+        Ok(_) => panic!("package should be refused"),
+        Err(OmniverlayError::InvalidPackage(message)) => message,
+        Err(e) => panic!("unexpected error {e}"),
+    }
+}
+
+#[test]
+fn pack_then_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("clock");
+
+    std::fs::create_dir_all(source.join("assets/img")).unwrap();
+    std::fs::write(source.join("extension.toml"), MANIFEST).unwrap();
+    std::fs::write(source.join("clock.process.json"), EXTENSION).unwrap();
+    std::fs::write(source.join("assets/img/icon.svg"), "<svg/>").unwrap();
+
+    let path = dir.path().join("clock.omnipkg");
+    let manifest = ExtensionPackage::pack(&source, &path, None).unwrap();
+    let package = ExtensionPackage::open(&path).unwrap();
+
+    assert_eq!(manifest.id, "dev.test.clock");
+    assert_eq!(package.manifest.id, "dev.test.clock");
+    assert_eq!(
+        package.files().collect::<Vec<_>>(),
+        ["assets/img/icon.svg", CHECKSUMS_FILE_NAME, "clock.process.json", "extension.toml"]
+    );
+}
+
+#[test]
+fn checksums_written_by_sha256sum_are_accepted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.omnipkg");
+
+    // Binary marker and `./` prefixes, as `sha256sum -b ./*` writes them
+    let checksums = format!(
+        "{} *./extension.toml\n\n{}  ./clock.process.json\n",
+        sha256(MANIFEST.as_bytes()),
+        sha256(EXTENSION.as_bytes()).to_uppercase()
+    );
+
+    write_zip(
+        &path,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("clock.process.json", EXTENSION.as_bytes()),
+            (CHECKSUMS_FILE_NAME, checksums.as_bytes()),
+        ],
+    );
+
+    assert!(ExtensionPackage::open(&path).is_ok());
+}
+
+#[test]
+fn modified_file_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let sums = checksums(&[("extension.toml", MANIFEST.as_bytes()), ("clock.process.json", b"{}")]);
+
+    let error = open_error(
+        &dir,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("clock.process.json", EXTENSION.as_bytes()),
+            (CHECKSUMS_FILE_NAME, sums.as_bytes()),
+        ],
+    );
+
+    assert!(error.contains("checksum of clock.process.json does not match"), "{error}");
+}
+
+#[test]
+fn unlisted_file_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let sums = checksums(&[("extension.toml", MANIFEST.as_bytes()), ("clock.process.json", EXTENSION.as_bytes())]);
+
+    let error = open_error(
+        &dir,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("clock.process.json", EXTENSION.as_bytes()),
+            ("extra.txt", b"added later"),
+            (CHECKSUMS_FILE_NAME, sums.as_bytes()),
+        ],
+    );
+
+    assert!(error.contains("extra.txt is missing from"), "{error}");
+}
+
+#[test]
+fn removed_file_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let sums = checksums(&[
+        ("extension.toml", MANIFEST.as_bytes()),
+        ("clock.process.json", EXTENSION.as_bytes()),
+        ("clock.exe", b"program"),
+    ]);
+
+    let error = open_error(
+        &dir,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("clock.process.json", EXTENSION.as_bytes()),
+            (CHECKSUMS_FILE_NAME, sums.as_bytes()),
+        ],
+    );
+
+    assert!(error.contains("are missing"), "{error}");
+}
+
+#[test]
+fn malformed_checksums_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = open_error(
+        &dir,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("clock.process.json", EXTENSION.as_bytes()),
+            (CHECKSUMS_FILE_NAME, b"not-a-checksum-line"),
+        ],
+    );
+
+    assert!(error.contains("is malformed"), "{error}");
+}
+
+#[test]
+fn missing_checksums_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = open_error(
+        &dir,
+        &[("extension.toml", MANIFEST.as_bytes()), ("clock.process.json", EXTENSION.as_bytes())],
+    );
+
+    assert!(error.contains(&format!("no {CHECKSUMS_FILE_NAME}")), "{error}");
+}
+
+#[test]
+fn unsafe_file_names_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for name in ["../evil.txt", "assets/../../evil.txt", "/etc/evil.txt", "./../evil.txt"] {
+        let error = open_error(&dir, &[("extension.toml", MANIFEST.as_bytes()), (name, b"evil")]);
+
+        assert!(error.contains("unsafe file name"), "{name}: {error}");
+    }
+}
+
+#[test]
+fn package_without_a_loadable_extension_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let sums = checksums(&[("extension.toml", MANIFEST.as_bytes()), ("readme.txt", b"hello")]);
+
+    let error = open_error(
+        &dir,
+        &[
+            ("extension.toml", MANIFEST.as_bytes()),
+            ("readme.txt", b"hello"),
+            (CHECKSUMS_FILE_NAME, sums.as_bytes()),
+        ],
+    );
+
+    assert!(error.contains("no extension this platform can load"), "{error}");
+}
+
+/// Needs the manager to stop, like extensions reaching the app through `get_omniverlay`
+struct NeedsManager {
+    info: Arc<Mutex<ExtensionInfo>>,
+    manager: Arc<RwLock<ExtensionManager>>,
+}
+
+#[async_trait]
+impl Extension for NeedsManager {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        let manager = self.manager.read().await;
+        manager.list_extensions().await?;
+
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+}
+
+#[tokio::test]
+async fn replaced_extensions_stop_without_the_manager() {
+    let manager = Arc::new(RwLock::new(ExtensionManager::new()));
+
+    let extension = NeedsManager {
+        info: Arc::new(Mutex::new(ExtensionInfo {
+            name: "clock".to_string(),
+            ..Default::default()
+        })),
+        manager: manager.clone(),
+    };
+
+    manager.write().await.register_extension(extension).await.unwrap();
+    manager.read().await.enable_extension("clock").await.unwrap();
+
+    // What installing a package does with the version it replaces
+    let detached = manager.write().await.detach_extension("clock").unwrap();
+    assert!(manager.read().await.list_extensions().await.unwrap().is_empty());
+
+    tokio::time::timeout(Duration::from_secs(5), detached.stop()).await.unwrap();
+    assert_eq!(detached.instance_names(), ["clock"]);
+}
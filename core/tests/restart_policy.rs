@@ -0,0 +1,213 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::{OmniverlayError, OmniverlayResult},
+    extensions::{supervisor::RestartPolicy, Extension, ExtensionInfo, ExtensionManager, ExtensionStatus},
+};
+use tokio::sync::Mutex;
+
+mod common;
+
+use common::status;
+
+type Seen = Arc<std::sync::Mutex<Vec<(&'static str, ExtensionStatus)>>>;
+
+/// Fails its first tick, then enables successfully `working_enables` times
+struct Flaky {
+    info: Arc<Mutex<ExtensionInfo>>,
+    // Status seen by every hook call
+    seen: Seen,
+    ticks: Arc<AtomicU32>,
+    working_enables: u32,
+    enables: u32,
+}
+
+impl Flaky {
+    fn new(working_enables: u32) -> (Self, Seen) {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let flaky = Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "flaky".to_string(),
+                ..Default::default()
+            })),
+            seen: seen.clone(),
+            ticks: Arc::new(AtomicU32::new(0)),
+            working_enables,
+            enables: 0,
+        };
+
+        (flaky, seen)
+    }
+
+    async fn record(&self, hook: &'static str) {
+        let status = self.info.lock().await.status.clone();
+
+        self.seen.lock().unwrap().push((hook, status));
+    }
+}
+
+#[async_trait]
+impl Extension for Flaky {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        self.record("enable").await;
+        self.enables += 1;
+
+        match self.enables <= self.working_enables {
+            true => Ok(()),
+            false => Err(OmniverlayError::ExtensionRuntime("cannot start".to_string())),
+        }
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        self.record("disable").await;
+
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(20))
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        match self.ticks.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(OmniverlayError::ExtensionRuntime("first tick fails".to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn flaky_manager(working_enables: u32, policy: RestartPolicy) -> (ExtensionManager, Seen) {
+    let (flaky, seen) = Flaky::new(working_enables);
+
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(flaky).await.unwrap();
+    manager.set_restart_policy("flaky", policy).await.unwrap();
+    manager.enable_extension("flaky").await.unwrap();
+
+    (manager, seen)
+}
+
+#[test]
+fn never_does_not_restart() {
+    assert_eq!(RestartPolicy::Never.backoff(0), None);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_maximum() {
+    let policy = RestartPolicy::OnFailure {
+        initial_backoff_ms: 100,
+        max_backoff_ms: 1_000,
+    };
+
+    let delays: Vec<_> = (0..6).map(|attempt| policy.backoff(attempt)).collect();
+
+    assert_eq!(
+        delays,
+        [100, 200, 400, 800, 1_000, 1_000].map(|ms| Some(Duration::from_millis(ms)))
+    );
+}
+
+#[test]
+fn backoff_does_not_overflow() {
+    let policy = RestartPolicy::OnFailure {
+        initial_backoff_ms: 1_000,
+        max_backoff_ms: 60_000,
+    };
+
+    assert_eq!(policy.backoff(63), Some(Duration::from_secs(60)));
+    assert_eq!(policy.backoff(64), Some(Duration::from_secs(60)));
+    assert_eq!(policy.backoff(u32::MAX), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn max_retries_gives_up() {
+    let policy = RestartPolicy::MaxRetries {
+        max_retries: 2,
+        initial_backoff_ms: 10,
+        max_backoff_ms: 1_000,
+    };
+
+    assert_eq!(policy.backoff(0), Some(Duration::from_millis(10)));
+    assert_eq!(policy.backoff(1), Some(Duration::from_millis(20)));
+    assert_eq!(policy.backoff(2), None);
+}
+
+#[test]
+fn default_policy_retries_three_times() {
+    let policy = RestartPolicy::default();
+
+    assert_eq!(policy.backoff(0), Some(Duration::from_secs(1)));
+    assert!(policy.backoff(2).is_some());
+    assert_eq!(policy.backoff(3), None);
+}
+
+#[tokio::test]
+async fn failed_extensions_restart_through_the_lifecycle_statuses() {
+    let policy = RestartPolicy::OnFailure {
+        initial_backoff_ms: 50,
+        max_backoff_ms: 50,
+    };
+
+    let (manager, seen) = flaky_manager(2, policy).await;
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(status(&manager, "flaky").await, ExtensionStatus::Running);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            ("enable", ExtensionStatus::Starting),
+            ("disable", ExtensionStatus::Stopping),
+            ("enable", ExtensionStatus::Starting),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn extensions_failing_to_restart_are_given_up() {
+    let policy = RestartPolicy::MaxRetries {
+        max_retries: 2,
+        initial_backoff_ms: 20,
+        max_backoff_ms: 20,
+    };
+
+    let (manager, seen) = flaky_manager(1, policy).await;
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    assert!(matches!(status(&manager, "flaky").await, ExtensionStatus::Failed { .. }));
+
+    let enables = seen.lock().unwrap().iter().filter(|(hook, _)| *hook == "enable").count();
+    assert_eq!(enables, 3);
+}
+
+#[tokio::test]
+async fn disabled_extensions_are_not_restarted() {
+    let policy = RestartPolicy::OnFailure {
+        initial_backoff_ms: 100,
+        max_backoff_ms: 100,
+    };
+
+    let (manager, seen) = flaky_manager(2, policy).await;
+
+    // Failed on its first tick, the restart is pending
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    manager.disable_extension("flaky").await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(status(&manager, "flaky").await, ExtensionStatus::Stopped);
+    assert_eq!(*seen.lock().unwrap(), [("enable", ExtensionStatus::Starting)]);
+}
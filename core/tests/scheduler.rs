@@ -0,0 +1,139 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    extensions::{Extension, ExtensionInfo, ExtensionManager},
+};
+use tokio::sync::Mutex;
+
+struct Ticker {
+    info: Arc<Mutex<ExtensionInfo>>,
+    ticks: Arc<AtomicU32>,
+    interval: Duration,
+    // Time every tick takes
+    busy: Duration,
+}
+
+impl Ticker {
+    fn new(interval: Duration, busy: Duration) -> (Self, Arc<AtomicU32>) {
+        let ticks = Arc::new(AtomicU32::new(0));
+
+        let ticker = Self {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: "ticker".to_string(),
+                ..Default::default()
+            })),
+            ticks: ticks.clone(),
+            interval,
+            busy,
+        };
+
+        (ticker, ticks)
+    }
+}
+
+#[async_trait]
+impl Extension for Ticker {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
+        tokio::time::sleep(self.busy).await;
+        self.ticks.fetch_add(1, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+async fn ticking_manager(interval: Duration, busy: Duration) -> (ExtensionManager, Arc<AtomicU32>) {
+    let (ticker, ticks) = Ticker::new(interval, busy);
+
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(ticker).await.unwrap();
+    manager.enable_extension("ticker").await.unwrap();
+
+    (manager, ticks)
+}
+
+#[tokio::test]
+async fn ticks_while_running() {
+    let (manager, ticks) = ticking_manager(Duration::from_millis(20), Duration::ZERO).await;
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(ticks.load(Ordering::SeqCst) >= 5, "{ticks:?}");
+
+    manager.disable_extension("ticker").await.unwrap();
+    let stopped_at = ticks.load(Ordering::SeqCst);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+}
+
+#[tokio::test]
+async fn ticks_running_late_are_skipped() {
+    let (_manager, ticks) = ticking_manager(Duration::from_millis(20), Duration::from_millis(100)).await;
+
+    tokio::time::sleep(Duration::from_millis(350)).await;
+
+    // Queued ticks would have caught up to about 17
+    assert!((2..=4).contains(&ticks.load(Ordering::SeqCst)), "{ticks:?}");
+}
+
+#[tokio::test]
+async fn managers_tick_their_own_extensions() {
+    let (first, first_ticks) = ticking_manager(Duration::from_millis(20), Duration::ZERO).await;
+    let (_second, second_ticks) = ticking_manager(Duration::from_millis(20), Duration::ZERO).await;
+
+    // Same instance name in both managers
+    first.disable_extension("ticker").await.unwrap();
+    let first_stopped_at = first_ticks.load(Ordering::SeqCst);
+    let second_before = second_ticks.load(Ordering::SeqCst);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(first_ticks.load(Ordering::SeqCst), first_stopped_at);
+    assert!(second_ticks.load(Ordering::SeqCst) >= second_before + 3, "{second_ticks:?}");
+}
+
+#[test]
+fn ticks_again_on_a_new_runtime() {
+    let first = tokio::runtime::Runtime::new().unwrap();
+    let (manager, ticks) = first.block_on(ticking_manager(Duration::from_millis(20), Duration::ZERO));
+
+    first.block_on(async { tokio::time::sleep(Duration::from_millis(100)).await });
+    drop(first);
+
+    let second = tokio::runtime::Runtime::new().unwrap();
+
+    second.block_on(async {
+        manager.disable_extension("ticker").await.unwrap();
+        manager.enable_extension("ticker").await.unwrap();
+
+        let restarted_at = ticks.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(ticks.load(Ordering::SeqCst) >= restarted_at + 3, "{ticks:?}");
+    });
+
+    drop(manager);
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "scripting")]
+
+use omniverlay_core::extensions::{script::ScriptExtension, Extension};
+
+#[test]
+fn layout_is_read_from_info() {
+    let source = r#"fn info() { #{ name: "Clock", width: 200, height: 80, x: 10 } }"#;
+    let info = ScriptExtension::from_source(source)
+        .unwrap()
+        .get_extension_info()
+        .unwrap();
+
+    let layout = info.blocking_lock().layout.clone().unwrap();
+
+    assert_eq!((layout.width, layout.height, layout.x, layout.y), (200, 80, 10, 0));
+}
+
+#[test]
+fn out_of_range_sizes_are_script_errors() {
+    for info in [
+        r#"#{ name: "Clock", width: -1, height: 80 }"#,
+        r#"#{ name: "Clock", width: 200, height: 4294967296 }"#,
+        r#"#{ name: "Clock", width: 200, height: 80, y: -5 }"#,
+    ] {
+        let Err(error) = ScriptExtension::from_source(&format!("fn info() {{ {info} }}")) else {
+            panic!("{info} was accepted");
+        };
+
+        assert!(error.to_string().contains("must be between 0 and"), "{info}: {error}");
+    }
+}
@@ -0,0 +1,164 @@
+use omniverlay_core::{
+    extensions::{
+        package::ExtensionPackage,
+        signing::{generate_key, PackageSignature, SigningPolicy, TrustStatus, TrustedKeys, TRUSTED_KEYS_FILE_NAME},
+    },
+    settings::SETTINGS_FILE_NAME,
+};
+
+const CHECKSUMS: &[u8] = b"9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  clock.rhai\n";
+
+fn signature_of(checksums: &[u8], key: &ed25519_dalek::SigningKey) -> Vec<u8> {
+    serde_json::to_vec(&PackageSignature::sign(checksums, key)).unwrap()
+}
+
+fn public_key(key: &ed25519_dalek::SigningKey) -> String {
+    hex::encode(key.verifying_key().as_bytes())
+}
+
+#[test]
+fn parse_skips_comments_and_names_keys() {
+    let team = generate_key();
+    let other = generate_key();
+
+    let keys = TrustedKeys::parse(&format!(
+        "# Trusted keys\n\n{}  Team tools\n   {}\n",
+        public_key(&team),
+        public_key(&other)
+    ))
+    .unwrap();
+
+    assert_eq!(
+        keys.verify(CHECKSUMS, Some(&signature_of(CHECKSUMS, &team))),
+        TrustStatus::Trusted {
+            signer: "Team tools".to_string()
+        }
+    );
+    assert_eq!(
+        keys.verify(CHECKSUMS, Some(&signature_of(CHECKSUMS, &other))),
+        TrustStatus::Trusted {
+            signer: public_key(&other)
+        }
+    );
+}
+
+#[test]
+fn parse_reports_the_bad_line() {
+    let team = generate_key();
+
+    let error = TrustedKeys::parse(&format!("{}  Team\nnot-a-key  Someone\n", public_key(&team))).unwrap_err();
+    assert!(error.starts_with("line 2:"), "{error}");
+
+    assert!(TrustedKeys::parse("abcd").is_err());
+}
+
+#[test]
+fn unsigned_is_untrusted() {
+    assert_eq!(
+        TrustedKeys::default().verify(CHECKSUMS, None),
+        TrustStatus::Untrusted { key: None }
+    );
+}
+
+#[test]
+fn unknown_key_is_untrusted() {
+    let key = generate_key();
+
+    assert_eq!(
+        TrustedKeys::default().verify(CHECKSUMS, Some(&signature_of(CHECKSUMS, &key))),
+        TrustStatus::Untrusted {
+            key: Some(public_key(&key))
+        }
+    );
+}
+
+#[test]
+fn modified_checksums_are_invalid() {
+    let key = generate_key();
+
+    let mut keys = TrustedKeys::default();
+    keys.add(key.verifying_key(), "Team");
+
+    let signature = signature_of(CHECKSUMS, &key);
+    let status = keys.verify(b"00  clock.rhai\n", Some(&signature));
+
+    assert!(matches!(status, TrustStatus::Invalid { .. }), "{status:?}");
+}
+
+#[test]
+fn malformed_signature_is_invalid() {
+    let keys = TrustedKeys::default();
+
+    assert!(matches!(keys.verify(CHECKSUMS, Some(b"{")), TrustStatus::Invalid { .. }));
+
+    let key = generate_key();
+    let signature = serde_json::json!({ "key": public_key(&key), "signature": "abcd" });
+    let status = keys.verify(CHECKSUMS, Some(signature.to_string().as_bytes()));
+
+    assert!(matches!(status, TrustStatus::Invalid { .. }), "{status:?}");
+}
+
+#[test]
+fn signed_package_is_trusted() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("clock");
+
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("extension.toml"),
+        "id = \"dev.test.clock\"\nname = \"Clock\"\nversion = \"1.0.0\"\n",
+    )
+    .unwrap();
+    std::fs::write(source.join("clock.process.json"), "{ \"command\": \"clock\" }").unwrap();
+
+    let key = generate_key();
+    let path = dir.path().join("clock.omnipkg");
+    ExtensionPackage::pack(&source, &path, Some(&key)).unwrap();
+
+    let package = ExtensionPackage::open(&path).unwrap();
+
+    let mut keys = TrustedKeys::default();
+    assert!(!package.trust(&keys).is_trusted());
+
+    keys.add(key.verifying_key(), "Team");
+    assert_eq!(
+        package.trust(&keys),
+        TrustStatus::Trusted {
+            signer: "Team".to_string()
+        }
+    );
+}
+
+#[test]
+fn unreadable_policy_files_fail_closed() {
+    let home = tempfile::tempdir().unwrap();
+    let omniverlay_dir = home.path().join(".omniverlay");
+    std::fs::create_dir_all(&omniverlay_dir).unwrap();
+
+    // The only test of this file reading the home directory
+    std::env::set_var("HOME", home.path());
+
+    let key = generate_key();
+    std::fs::write(
+        omniverlay_dir.join(TRUSTED_KEYS_FILE_NAME),
+        format!("{} Team\nnot-a-key\n", public_key(&key)),
+    )
+    .unwrap();
+    std::fs::write(omniverlay_dir.join(SETTINGS_FILE_NAME), "{ \"require_signed_packages\": true }").unwrap();
+
+    assert!(SigningPolicy::load().is_err());
+
+    let policy = SigningPolicy::load_or_default();
+    assert!(policy.require_signed);
+    assert!(!policy.trusted_keys.verify(CHECKSUMS, Some(&signature_of(CHECKSUMS, &key))).is_trusted());
+
+    // Corrupt settings must not turn off refusing unsigned packages
+    std::fs::write(omniverlay_dir.join(SETTINGS_FILE_NAME), "{ require_signed_packages").unwrap();
+
+    assert!(SigningPolicy::load_or_default().require_signed);
+
+    // Settings the user never saved are the defaults, which allow unsigned packages
+    std::fs::remove_file(omniverlay_dir.join(SETTINGS_FILE_NAME)).unwrap();
+
+    assert!(!SigningPolicy::load_or_default().require_signed);
+}
@@ -0,0 +1,213 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    extensions::{data::DataRetention, Extension, ExtensionInfo, ExtensionLayout, ExtensionState, ExtensionStatus},
+    get_omniverlay,
+};
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+/// Tells whether it is enabled, and whether it was dropped through `alive`
+struct Tracked {
+    info: Arc<Mutex<ExtensionInfo>>,
+    enabled: Arc<AtomicBool>,
+    _alive: Arc<()>,
+}
+
+#[async_trait]
+impl Extension for Tracked {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        self.enabled.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
+        Ok(self.info.clone())
+    }
+}
+
+/// Profiles and layouts are saved into the home directory, a temporary one for this file
+fn set_home() {
+    static HOME: OnceLock<TempDir> = OnceLock::new();
+
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        std::env::set_var("HOME", home.path());
+
+        home
+    });
+}
+
+/// Register `name` enabled, with an entry in the current profile and layout. Returns whether it
+/// is enabled and a reference kept by the extension.
+async fn register_enabled(name: &str) -> (Arc<AtomicBool>, Arc<()>) {
+    set_home();
+
+    let enabled = Arc::new(AtomicBool::new(false));
+    let alive = Arc::new(());
+
+    let omniverlay = get_omniverlay();
+    let omniverlay = omniverlay.read().await;
+
+    omniverlay
+        .get_extension_manager()
+        .await
+        .write()
+        .await
+        .register_extension(Tracked {
+            info: Arc::new(Mutex::new(ExtensionInfo {
+                name: name.to_string(),
+                ..Default::default()
+            })),
+            enabled: enabled.clone(),
+            _alive: alive.clone(),
+        })
+        .await
+        .unwrap();
+
+    let profile_manager = omniverlay.get_profile_manager().await;
+    let layout_manager = omniverlay.get_layout_manager().await;
+
+    {
+        let profile = profile_manager.read().await.get_current().await.unwrap();
+        let layout = layout_manager.read().await.get_current().await.unwrap();
+
+        profile.write().await.extensions.insert(
+            name.to_string(),
+            ExtensionState {
+                is_enabled: true,
+                ..Default::default()
+            },
+        );
+        layout.write().await.extensions.insert(
+            name.to_string(),
+            ExtensionLayout {
+                width: 200,
+                height: 100,
+                x: 0,
+                y: 0,
+            },
+        );
+    }
+
+    // Enables it
+    profile_manager.read().await.save_data().await.unwrap();
+
+    let extensions = omniverlay
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .list_extensions()
+        .await
+        .unwrap();
+    let info = extensions.into_iter().find(|info| info.name == name).unwrap();
+
+    assert_eq!(info.status, ExtensionStatus::Running);
+    assert!(enabled.load(Ordering::SeqCst));
+
+    (enabled, alive)
+}
+
+/// Whether the current profile and layout still have an entry for `name`
+async fn has_entries(name: &str) -> (bool, bool) {
+    let omniverlay = get_omniverlay();
+    let omniverlay = omniverlay.read().await;
+
+    let profile = omniverlay
+        .get_profile_manager()
+        .await
+        .read()
+        .await
+        .get_current()
+        .await
+        .unwrap();
+    let layout = omniverlay
+        .get_layout_manager()
+        .await
+        .read()
+        .await
+        .get_current()
+        .await
+        .unwrap();
+
+    let in_profile = profile.read().await.extensions.contains_key(name);
+    let in_layout = layout.read().await.extensions.contains_key(name);
+
+    (in_profile, in_layout)
+}
+
+async fn unregister(name: &str, retention: DataRetention) {
+    let omniverlay = get_omniverlay();
+    let omniverlay = omniverlay.read().await;
+
+    omniverlay.unregister_extension(name, retention).await.unwrap();
+
+    let extension_manager = omniverlay.get_extension_manager().await;
+    let extension_manager = extension_manager.read().await;
+
+    assert!(extension_manager.get_extension_by_name(name).is_err());
+    assert!(!extension_manager
+        .list_extensions()
+        .await
+        .unwrap()
+        .iter()
+        .any(|info| info.name == name));
+}
+
+/// Whether the last reference to `alive` other than this one goes away within a few seconds
+async fn dropped(alive: &Arc<()>) -> bool {
+    for _ in 0..100 {
+        if Arc::strong_count(alive) == 1 {
+            return true;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    false
+}
+
+#[tokio::test]
+async fn unregistered_extensions_are_disabled_and_dropped() {
+    let (enabled, alive) = register_enabled("unregistered").await;
+
+    unregister("unregistered", DataRetention::Keep).await;
+
+    assert!(!enabled.load(Ordering::SeqCst));
+    assert!(dropped(&alive).await);
+}
+
+#[tokio::test]
+async fn prune_removes_the_profile_and_layout_entries() {
+    let (_, alive) = register_enabled("pruned").await;
+    assert_eq!(has_entries("pruned").await, (true, true));
+
+    unregister("pruned", DataRetention::Prune).await;
+
+    assert_eq!(has_entries("pruned").await, (false, false));
+    assert!(dropped(&alive).await);
+}
+
+#[tokio::test]
+async fn keep_leaves_the_profile_and_layout_entries() {
+    let (_, alive) = register_enabled("kept").await;
+
+    unregister("kept", DataRetention::Keep).await;
+
+    assert_eq!(has_entries("kept").await, (true, true));
+    assert!(dropped(&alive).await);
+}
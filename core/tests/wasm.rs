@@ -0,0 +1,64 @@
+#![cfg(feature = "wasm")]
+
+use std::time::Duration;
+
+use omniverlay_core::extensions::{
+    wasm::{WasmExtension, WasmLimits},
+    Extension, ExtensionManager,
+};
+use serde_json::json;
+
+mod common;
+
+use common::published;
+
+const INFO: &str = r#"{\"name\":\"ticker\",\"state\":{\"is_enabled\":false}}"#;
+const PAYLOAD: &str = r#"{\"tick\":true}"#;
+
+/// A guest publishing on every tick, without its tick exports when `ticks` is false
+fn guest(ticks: bool) -> String {
+    let tick = match ticks {
+        true => {
+            r#"
+            (func (export "omniverlay_tick_interval") (result i32) (i32.const 20))
+            (func (export "omniverlay_tick") (result i32)
+                (call $publish (i32.const 256) (i32.const 13))
+                (i32.const 0))
+            "#
+        }
+        false => "",
+    };
+
+    format!(
+        r#"(module
+            (import "env" "omniverlay_publish" (func $publish (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{INFO}")
+            (data (i32.const 256) "{PAYLOAD}")
+            (func (export "omniverlay_alloc") (param i32) (result i32) (i32.const 512))
+            (func (export "omniverlay_info") (result i64) (i64.const 46))
+            (func (export "omniverlay_enable") (result i32) (i32.const 0))
+            (func (export "omniverlay_disable") (result i32) (i32.const 0))
+            {tick}
+        )"#
+    )
+}
+
+#[tokio::test]
+async fn guests_tick_when_they_export_it() {
+    let extension = WasmExtension::from_bytes(guest(true).as_bytes(), WasmLimits::default()).unwrap();
+    assert_eq!(extension.tick_interval(), Some(Duration::from_millis(20)));
+
+    let mut manager = ExtensionManager::new();
+    manager.register_extension(extension).await.unwrap();
+    manager.enable_extension("ticker").await.unwrap();
+
+    assert_eq!(published(&manager, "ticker").await, json!({ "tick": true }));
+}
+
+#[test]
+fn guests_without_tick_exports_do_not_tick() {
+    let extension = WasmExtension::from_bytes(guest(false).as_bytes(), WasmLimits::default()).unwrap();
+
+    assert_eq!(extension.tick_interval(), None);
+}
@@ -1,90 +1,44 @@
-use std::{sync::Arc, thread};
+use std::{sync::Arc, time::Duration};
 
-use log::info;
 use omniverlay_core::{
     errors::OmniverlayResult,
     extensions::{
-        config::{
-            ConfigCategory, ConfigCategoryBuilder, ConfigEnum, ConfigValue, ConfigValueType,
-            ExtensionConfigBuilder,
-        },
-        Extension, ExtensionGeometry, ExtensionInfo, ExtensionLayout, ExtensionState,
+        capabilities::Capability,
+        config::OmniverlayConfig,
+        host::ExtensionHost,
+        manifest::ExtensionManifest,
+        Extension, ExtensionInfo, ExtensionLayout, ExtensionState,
     },
+    omniverlay_extension,
 };
 use serde::Serialize;
-use sysinfo::System;
-use tauri::Manager;
+use sysinfo::{System, MINIMUM_CPU_UPDATE_INTERVAL};
 use tokio::sync::Mutex;
 
 pub struct PerformanceExtension {
     info: Arc<Mutex<ExtensionInfo>>,
+    config: PerformanceConfig,
     system: System,
+    host: Option<ExtensionHost>,
+}
+
+#[derive(Debug, Clone, PartialEq, OmniverlayConfig)]
+#[config(category = "General")]
+pub struct PerformanceConfig {
+    /// Applies the next time the extension is enabled
+    #[config(description = "Time between two measures, in milliseconds", default = 1_000u64)]
+    pub refresh_interval_ms: u64,
 }
 
 impl PerformanceExtension {
     pub fn new() -> Self {
-        let config = ExtensionConfigBuilder::new()
-            .add_category(
-                ConfigCategoryBuilder::new("General".to_string())
-                    .add_value(
-                        "first_name".to_string(),
-                        ConfigValue::new(
-                            "The first name of the person".to_string(),
-                            ConfigValueType::String("Jhon".to_string()),
-                        ),
-                    )
-                    .add_value(
-                        "last_name".to_string(),
-                        ConfigValue::new(
-                            "The last name of the person".to_string(),
-                            ConfigValueType::String("Doe".to_string()),
-                        ),
-                    )
-                    .add_value(
-                        "age".to_string(),
-                        ConfigValue::new(
-                            "The age of the person".to_string(),
-                            ConfigValueType::Int(30),
-                        ),
-                    )
-                    .add_value(
-                        "active".to_string(),
-                        ConfigValue::new(
-                            "Is the person active".to_string(),
-                            ConfigValueType::Bool(true),
-                        ),
-                    )
-                    .build(),
-            )
-            .add_category(
-                ConfigCategoryBuilder::new("Job".to_string())
-                    .add_value(
-                        "Grade".to_string(),
-                        ConfigValue::new(
-                            "The grade of the person".to_string(),
-                            ConfigValueType::Enum(ConfigEnum::new(
-                                "grades".to_string(),
-                                "A".to_string(),
-                                vec![
-                                    "A".to_string(),
-                                    "B".to_string(),
-                                    "C".to_string(),
-                                    "D".to_string(),
-                                    "E".to_string(),
-                                ],
-                            )),
-                        ),
-                    )
-                    .build(),
-            )
-            .build();
-
         Self {
             info: Arc::new(Mutex::new(ExtensionInfo {
                 name: "Performance".to_string(),
                 state: ExtensionState {
                     is_enabled: true,
-                    config: Some(config),
+                    config: Some(PerformanceConfig::default_config().expect("Invalid Performance config")),
+                    ..Default::default()
                 },
                 layout: Some(ExtensionLayout {
                     width: 200,
@@ -92,8 +46,15 @@ impl PerformanceExtension {
                     x: 0,
                     y: 0,
                 }),
+                manifest: Some(
+                    ExtensionManifest::from_toml(include_str!("../extension.toml"))
+                        .expect("Invalid Performance manifest"),
+                ),
+                ..Default::default()
             })),
+            config: PerformanceConfig::default_values(),
             system: System::new(),
+            host: None,
         }
     }
 }
@@ -103,29 +64,63 @@ struct PerformancePayload {
     cpu_usage: f32,
 }
 
+#[omniverlay_extension]
 impl Extension for PerformanceExtension {
-    fn enable(&mut self) -> OmniverlayResult<()> {
+    async fn enable(&mut self) -> OmniverlayResult<()> {
+        if let Some(host) = &self.host {
+            host.require(&Capability::SystemInfo)?;
+        }
+
+        // The first measure is only a baseline, CPU usage is computed between two refreshes
+        self.system.refresh_cpu_all();
+
+        Ok(())
+    }
+
+    async fn disable(&mut self) -> OmniverlayResult<()> {
+        Ok(())
+    }
+
+    fn set_host(&mut self, host: ExtensionHost) {
+        self.host = Some(host);
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        // CPU usage measured over a shorter time is not accurate
+        Some(Duration::from_millis(self.config.refresh_interval_ms).max(MINIMUM_CPU_UPDATE_INTERVAL))
+    }
+
+    async fn on_tick(&mut self) -> OmniverlayResult<()> {
         self.system.refresh_cpu_all();
-        let cpu_usage = self.system.global_cpu_usage();
-        // let app = TAURI_APP_HANDLE.get().unwrap().clone();
-
-        // thread::spawn(move || loop {
-        //     app.emit_all(
-        //         "Performance://performance_update",
-        //         PerformancePayload { cpu_usage },
-        //     )
-        //     .unwrap();
-        //     thread::sleep(std::time::Duration::from_millis(1000));
-        // });
+
+        let payload = PerformancePayload {
+            cpu_usage: self.system.global_cpu_usage(),
+        };
+
+        if let Some(host) = &self.host {
+            host.publish(&payload)?;
+        }
 
         Ok(())
     }
 
-    fn disable(&mut self) -> OmniverlayResult<()> {
+    async fn update_state(&mut self, mut new_state: ExtensionState) -> OmniverlayResult<()> {
+        if let Some(config) = &new_state.config {
+            self.config = PerformanceConfig::from_config(config)?;
+
+            // Profiles saved with an older config keep values that no longer exist, they are dropped
+            new_state.config = Some(self.config.to_config()?);
+        }
+
+        self.info.lock().await.state = new_state;
+
         Ok(())
     }
 
-    fn get_extension_info(&self) -> OmniverlayResult<Arc<Mutex<ExtensionInfo>>> {
-        Ok(self.info.clone())
+    async fn preview_payloads(&mut self) -> OmniverlayResult<Vec<serde_json::Value>> {
+        [12.5, 58.0, 100.0]
+            .into_iter()
+            .map(|cpu_usage| Ok(serde_json::to_value(PerformancePayload { cpu_usage })?))
+            .collect()
     }
 }
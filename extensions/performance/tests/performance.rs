@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use omniverlay_core::{
+    errors::OmniverlayError,
+    extensions::{
+        config::{ConfigValue, ConfigValueType, OmniverlayConfig},
+        Extension, ExtensionState, ExtensionStatus,
+    },
+};
+use omniverlay_test::ExtensionHarness;
+use performance::{PerformanceConfig, PerformanceExtension};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct PerformancePayload {
+    cpu_usage: f32,
+}
+
+#[tokio::test]
+async fn publishes_cpu_usage_every_second() {
+    let mut harness = ExtensionHarness::new(PerformanceExtension::new()).await.unwrap();
+
+    harness.enable().await.unwrap();
+    assert_eq!(harness.info().await.status, ExtensionStatus::Running);
+
+    harness.advance(Duration::from_millis(3_500)).await.unwrap();
+
+    let published = harness.published::<PerformancePayload>().unwrap();
+
+    assert_eq!(published.len(), 3);
+    assert!(published
+        .iter()
+        .all(|payload| (0.0..=100.0).contains(&payload.cpu_usage)));
+
+    harness.disable().await.unwrap();
+    harness.advance(Duration::from_secs(3)).await.unwrap();
+
+    assert!(harness.published_values().is_empty());
+}
+
+#[tokio::test]
+async fn tick_publishes_right_away() {
+    let mut harness = ExtensionHarness::new(PerformanceExtension::new()).await.unwrap();
+
+    harness.enable().await.unwrap();
+    harness.tick().await.unwrap();
+
+    assert!(harness.last_published::<PerformancePayload>().unwrap().is_some());
+}
+
+#[tokio::test]
+async fn needs_system_info() {
+    let mut harness = ExtensionHarness::new(PerformanceExtension::new()).await.unwrap();
+
+    harness.grant(Vec::new()).await.unwrap();
+
+    let error = harness.enable().await.unwrap_err();
+
+    assert!(matches!(error, OmniverlayError::PermissionDenied(..)), "{error}");
+    assert!(matches!(harness.info().await.status, ExtensionStatus::Failed { .. }));
+}
+
+#[tokio::test]
+async fn previews_without_being_enabled() {
+    let mut harness = ExtensionHarness::new(PerformanceExtension::new()).await.unwrap();
+
+    let preview = harness.preview::<PerformancePayload>().await.unwrap();
+
+    assert_eq!(
+        preview.iter().map(|payload| payload.cpu_usage).collect::<Vec<_>>(),
+        [12.5, 58.0, 100.0]
+    );
+    assert!(harness.published_values().is_empty());
+}
+
+#[tokio::test]
+async fn reads_its_config_and_drops_old_values() {
+    let mut extension = PerformanceExtension::new();
+
+    // Profiles saved before the config had a refresh interval
+    let mut config = PerformanceConfig::default_config().unwrap();
+    config.set_value("General", "first_name", ConfigValue::new(String::new(), ConfigValueType::String("Jhon".into())));
+    config.set_value("General", "refresh_interval_ms", ConfigValue::new(String::new(), ConfigValueType::Int(500)));
+
+    let state = ExtensionState {
+        is_enabled: true,
+        config: Some(config),
+        ..Default::default()
+    };
+
+    extension.update_state(state).await.unwrap();
+
+    assert_eq!(extension.tick_interval(), Some(Duration::from_millis(500)));
+
+    let info = extension.get_extension_info().unwrap();
+    let saved = info.lock().await.state.config.clone().unwrap();
+
+    assert!(saved.get_value("first_name").is_none());
+    assert_eq!(
+        PerformanceConfig::from_config(&saved).unwrap(),
+        PerformanceConfig { refresh_interval_ms: 500 }
+    );
+}
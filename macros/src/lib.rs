@@ -0,0 +1,291 @@
+//! Procedural macros of Omniverlay, re-exported by `omniverlay_core`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Expr, ExprArray, Fields, Ident, ImplItem, ItemImpl,
+    LitStr, Path,
+};
+
+/// Derive `omniverlay_core::extensions::config::OmniverlayConfig` for a struct with named fields.
+///
+/// ```ignore
+/// #[derive(OmniverlayConfig)]
+/// #[config(category = "General")]
+/// struct ClockConfig {
+///     #[config(description = "Time zone to display", default = "UTC")]
+///     time_zone: String,
+///     #[config(category = "Style", description = "Format", default = "24h", choices = ["12h", "24h"])]
+///     format: String,
+/// }
+/// ```
+///
+/// Field attributes:
+/// - `category`: category of the value, defaults to the one of the struct or `"General"`
+/// - `name`: key of the value, defaults to the field name
+/// - `description`: shown in the studio
+/// - `default`: any expression converted into the field type with `Into`, `Default` if omitted
+/// - `choices`: displays the value as a list of choices, the field must implement `Display` and `FromStr`
+/// - `enum_name`: name of the choices, defaults to the key
+#[proc_macro_derive(OmniverlayConfig, attributes(config))]
+pub fn derive_omniverlay_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Register an `impl Extension` block so the host finds it with
+/// `ExtensionManager::register_compiled_extensions`.
+///
+/// ```ignore
+/// #[omniverlay_extension]
+/// impl Extension for ClockExtension {
+///     async fn enable(&mut self) -> OmniverlayResult<()> { Ok(()) }
+///     async fn disable(&mut self) -> OmniverlayResult<()> { Ok(()) }
+/// }
+/// ```
+///
+/// `get_extension_info` returns the `info` field unless the block defines it, and `#[async_trait]`
+/// is added when missing. Every instance is built with `new()`. Both can be changed:
+/// `#[omniverlay_extension(constructor = ClockExtension::with_defaults, info = state)]`.
+#[proc_macro_attribute]
+pub fn omniverlay_extension(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut constructor: Option<Path> = None;
+    let mut info: Option<Ident> = None;
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("constructor") {
+            constructor = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("info") {
+            info = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("expected `constructor` or `info`"));
+        }
+
+        Ok(())
+    });
+
+    parse_macro_input!(args with parser);
+
+    let item = parse_macro_input!(input as ItemImpl);
+
+    match expand_extension(item, constructor, info) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_extension(mut item: ItemImpl, constructor: Option<Path>, info: Option<Ident>) -> syn::Result<TokenStream2> {
+    if item.trait_.is_none() {
+        return Err(syn::Error::new_spanned(&item.self_ty, "#[omniverlay_extension] goes on an `impl Extension for ...` block"));
+    }
+
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "#[omniverlay_extension] cannot register a generic extension"));
+    }
+
+    let self_ty = item.self_ty.clone();
+    let info = info.unwrap_or_else(|| Ident::new("info", proc_macro2::Span::call_site()));
+
+    let defines_info = item
+        .items
+        .iter()
+        .any(|item| matches!(item, ImplItem::Fn(function) if function.sig.ident == "get_extension_info"));
+
+    if !defines_info {
+        item.items.push(parse_quote! {
+            fn get_extension_info(
+                &self,
+            ) -> ::omniverlay_core::errors::OmniverlayResult<
+                ::std::sync::Arc<::omniverlay_core::__private::tokio::sync::Mutex<::omniverlay_core::extensions::ExtensionInfo>>,
+            > {
+                ::core::result::Result::Ok(self.#info.clone())
+            }
+        });
+    }
+
+    let has_async_trait = item
+        .attrs
+        .iter()
+        .any(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == "async_trait"));
+
+    if !has_async_trait {
+        item.attrs.push(parse_quote!(#[::omniverlay_core::__private::async_trait]));
+    }
+
+    let constructor = match constructor {
+        Some(constructor) => quote!(#constructor),
+        None => quote!(<#self_ty>::new),
+    };
+
+    let type_name = quote!(#self_ty).to_string().replace(' ', "");
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            fn factory() -> ::std::sync::Arc<dyn ::omniverlay_core::extensions::instances::ExtensionFactory> {
+                ::std::sync::Arc::new(|| -> ::omniverlay_core::errors::OmniverlayResult<#self_ty> {
+                    ::core::result::Result::Ok(#constructor())
+                })
+            }
+
+            ::omniverlay_core::__private::inventory::submit! {
+                ::omniverlay_core::extensions::registry::CompiledExtension {
+                    type_name: #type_name,
+                    factory,
+                }
+            }
+        };
+    })
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    category: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    default: Option<Expr>,
+    choices: Option<ExprArray>,
+    enum_name: Option<String>,
+}
+
+fn parse_options(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("config")) {
+        attr.parse_nested_meta(|meta| {
+            let string = |meta: &syn::meta::ParseNestedMeta| -> syn::Result<String> {
+                Ok(meta.value()?.parse::<LitStr>()?.value())
+            };
+
+            if meta.path.is_ident("category") {
+                options.category = Some(string(&meta)?);
+            } else if meta.path.is_ident("name") {
+                options.name = Some(string(&meta)?);
+            } else if meta.path.is_ident("description") {
+                options.description = Some(string(&meta)?);
+            } else if meta.path.is_ident("default") {
+                options.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("choices") {
+                options.choices = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("enum_name") {
+                options.enum_name = Some(string(&meta)?);
+            } else {
+                return Err(meta.error("unknown config attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(ident, "OmniverlayConfig needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "OmniverlayConfig can only be derived for structs")),
+    };
+
+    let struct_options = parse_options(&input.attrs)?;
+    let default_category = struct_options.category.unwrap_or_else(|| "General".to_string());
+
+    let config = quote!(::omniverlay_core::extensions::config);
+
+    let mut defaults = Vec::new();
+    let mut to_values = Vec::new();
+    let mut from_values = Vec::new();
+
+    for field in fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let options = parse_options(&field.attrs)?;
+
+        let category = options.category.unwrap_or_else(|| default_category.clone());
+        let name = options.name.unwrap_or_else(|| field_ident.to_string());
+        let description = options.description.unwrap_or_default();
+
+        defaults.push(match &options.default {
+            Some(default) => quote!(#field_ident: ::core::convert::Into::into(#default)),
+            None => quote!(#field_ident: ::core::default::Default::default()),
+        });
+
+        let (to_value, from_value) = match &options.choices {
+            Some(choices) => {
+                let enum_name = options.enum_name.clone().unwrap_or_else(|| name.clone());
+                let choices = choices.elems.iter();
+
+                (
+                    quote! {
+                        #config::ConfigValueType::Enum(#config::ConfigEnum::new(
+                            #enum_name.to_string(),
+                            ::std::string::ToString::to_string(&self.#field_ident),
+                            ::std::vec![#(::std::string::ToString::to_string(&#choices)),*],
+                        ))
+                    },
+                    quote! {
+                        match &value.value {
+                            #config::ConfigValueType::Enum(choice) => ::std::str::FromStr::from_str(&choice.current).ok(),
+                            _ => ::core::option::Option::None,
+                        }
+                    },
+                )
+            }
+            None => (
+                quote!(#config::ConfigField::to_config_value(&self.#field_ident)?),
+                quote!(#config::ConfigField::from_config_value(&value.value)),
+            ),
+        };
+
+        to_values.push(quote! {
+            config.set_value(#category, #name, #config::ConfigValue::new(#description.to_string(), #to_value));
+        });
+
+        from_values.push(quote! {
+            #field_ident: match config.get_category(#category).and_then(|category| category.get_value(#name)) {
+                ::core::option::Option::Some(value) => (#from_value).ok_or_else(|| {
+                    ::omniverlay_core::errors::OmniverlayError::InvalidConfigValue(
+                        ::std::format!("{}.{}", #category, #name),
+                    )
+                })?,
+                ::core::option::Option::None => defaults.#field_ident,
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics #config::OmniverlayConfig for #ident #type_generics #where_clause {
+            fn default_values() -> Self {
+                Self {
+                    #(#defaults,)*
+                }
+            }
+
+            fn to_config(&self) -> ::omniverlay_core::errors::OmniverlayResult<#config::ExtensionConfig> {
+                let mut config = #config::ExtensionConfig { categories: ::std::vec::Vec::new() };
+
+                #(#to_values)*
+
+                ::core::result::Result::Ok(config)
+            }
+
+            fn from_config(config: &#config::ExtensionConfig) -> ::omniverlay_core::errors::OmniverlayResult<Self> {
+                let defaults = <Self as #config::OmniverlayConfig>::default_values();
+
+                ::core::result::Result::Ok(Self {
+                    #(#from_values,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,77 @@
+//! Events of the harnessed extensions, captured from the global `EVENT_HANDLER`.
+//!
+//! The handler is shared by every harness of the test binary, so events naming an extension go
+//! to the latest harness of that extension.
+
+use std::sync::{Arc, Mutex, Weak};
+
+use log::warn;
+use omniverlay_core::event::{OmniverlayEvent, OmniverlayEventType, EVENT_HANDLER};
+use once_cell::sync::Lazy;
+use tokio::sync::RwLock;
+
+type Events = Arc<Mutex<Vec<OmniverlayEventType>>>;
+
+/// Events of a harness, with the name of its extension
+type Sink = (String, Weak<Mutex<Vec<OmniverlayEventType>>>);
+
+static SINKS: Lazy<Mutex<Vec<Sink>>> = Lazy::new(Default::default);
+
+/// Whether `dispatch` is the event handler, the app may have installed its own before
+static INSTALLED: Lazy<bool> = Lazy::new(|| EVENT_HANDLER.set(RwLock::new(Some(Box::new(dispatch)))).is_ok());
+
+pub(crate) struct EventCapture {
+    events: Events,
+}
+
+impl EventCapture {
+    pub(crate) fn new(extension_name: &str) -> Self {
+        if !*INSTALLED {
+            warn!("Another event handler is installed, events of {} are not captured", extension_name);
+        }
+
+        let events = Events::default();
+
+        SINKS
+            .lock()
+            .unwrap()
+            .push((extension_name.to_string(), Arc::downgrade(&events)));
+
+        Self { events }
+    }
+
+    pub(crate) fn take(&self) -> Vec<OmniverlayEventType> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+fn dispatch(event: OmniverlayEvent) {
+    let mut sinks = SINKS.lock().unwrap();
+
+    sinks.retain(|(_, sink)| sink.strong_count() > 0);
+
+    let extension_name = match &event.event_type {
+        OmniverlayEventType::PublishExtensionData(name, _)
+        | OmniverlayEventType::ExtensionGaveUp(name, _)
+        | OmniverlayEventType::ExtensionOverBudget(name, _) => name.clone(),
+        OmniverlayEventType::UpdateExtensionData => {
+            for (_, sink) in sinks.iter() {
+                if let Some(sink) = sink.upgrade() {
+                    sink.lock().unwrap().push(OmniverlayEventType::UpdateExtensionData);
+                }
+            }
+
+            return;
+        }
+    };
+
+    let sink = sinks
+        .iter()
+        .rev()
+        .find(|(name, _)| *name == extension_name)
+        .and_then(|(_, sink)| sink.upgrade());
+
+    if let Some(sink) = sink {
+        sink.lock().unwrap().push(event.event_type);
+    }
+}
@@ -0,0 +1,276 @@
+//! Run an `Extension` in a test without the app.
+//!
+//! `ExtensionHarness` plays the part of the `ExtensionManager` for a single extension: it gives it
+//! a host whose storage lives in a temporary directory, drives its lifecycle and its ticks from a
+//! clock the test advances by hand, and captures what it publishes.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn publishes_usage() -> OmniverlayResult<()> {
+//!     let mut harness = ExtensionHarness::new(PerformanceExtension::new()).await?;
+//!
+//!     harness.enable().await?;
+//!     harness.advance(Duration::from_secs(3)).await?;
+//!
+//!     assert_eq!(harness.published::<PerformanceData>()?.len(), 3);
+//!
+//!     harness.disable().await
+//! }
+//! ```
+//!
+//! Every capability the extension declares is granted, use `grant` to test it with less.
+
+mod events;
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use events::EventCapture;
+use omniverlay_core::{
+    errors::OmniverlayResult,
+    event::OmniverlayEventType,
+    extensions::{
+        capabilities::Capability,
+        channel::DataSubscription,
+        config::ExtensionConfig,
+        host::{ExtensionHost, StandaloneGrants},
+        storage::ExtensionStorage,
+        Extension, ExtensionInfo, ExtensionStatus,
+    },
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+/// Shortest tick interval, the same as the core scheduler
+const MIN_TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct ExtensionHarness<T: Extension> {
+    extension: T,
+    info: Arc<Mutex<ExtensionInfo>>,
+    extension_name: String,
+    host: ExtensionHost,
+    grants: StandaloneGrants,
+    data_dir: TempDir,
+    published: DataSubscription,
+    events: EventCapture,
+    now: Duration,
+    next_tick: Option<Duration>,
+}
+
+impl<T: Extension> ExtensionHarness<T> {
+    pub async fn new(mut extension: T) -> OmniverlayResult<Self> {
+        let data_dir = tempfile::tempdir()?;
+        let info = extension.get_extension_info()?;
+
+        let (extension_name, declared) = {
+            let mut info = info.lock().await;
+
+            if info.extension_type.is_empty() {
+                info.extension_type = info.name.clone();
+            }
+
+            if info.capabilities.is_empty() {
+                if let Some(manifest) = &info.manifest {
+                    info.capabilities = manifest.capabilities.clone();
+                }
+            }
+
+            info.state.granted_capabilities = info.capabilities.clone();
+
+            (info.name.clone(), info.capabilities.clone())
+        };
+
+        let storage = ExtensionStorage::in_dir(data_dir.path().join("storage"));
+        let (host, grants) = ExtensionHost::standalone(info.clone(), &extension_name, storage, &declared);
+
+        extension.set_host(host.clone());
+
+        Ok(Self {
+            published: host.subscribe(&extension_name),
+            events: EventCapture::new(&extension_name),
+            extension,
+            info,
+            extension_name,
+            host,
+            grants,
+            data_dir,
+            now: Duration::ZERO,
+            next_tick: None,
+        })
+    }
+
+    pub fn extension(&self) -> &T {
+        &self.extension
+    }
+
+    pub fn extension_mut(&mut self) -> &mut T {
+        &mut self.extension
+    }
+
+    pub async fn info(&self) -> ExtensionInfo {
+        self.info.lock().await.clone()
+    }
+
+    /// Host given to the extension, to act as another extension would
+    pub fn host(&self) -> &ExtensionHost {
+        &self.host
+    }
+
+    pub fn storage(&self) -> &ExtensionStorage {
+        self.host.storage()
+    }
+
+    /// Temporary directory holding the storage, deleted with the harness
+    pub fn data_dir(&self) -> &Path {
+        self.data_dir.path()
+    }
+
+    pub async fn enable(&mut self) -> OmniverlayResult<()> {
+        let mut state = self.info().await.state;
+        state.is_enabled = true;
+
+        self.extension.update_state(state).await?;
+        self.set_status(ExtensionStatus::Starting).await;
+
+        if let Err(e) = self.extension.enable().await {
+            self.set_status(ExtensionStatus::Failed { error: e.to_string() }).await;
+
+            return Err(e);
+        }
+
+        self.set_status(ExtensionStatus::Running).await;
+        self.next_tick = self.tick_interval().map(|interval| self.now + interval);
+
+        Ok(())
+    }
+
+    pub async fn disable(&mut self) -> OmniverlayResult<()> {
+        let mut state = self.info().await.state;
+        state.is_enabled = false;
+
+        self.extension.update_state(state).await?;
+        self.set_status(ExtensionStatus::Stopping).await;
+        self.next_tick = None;
+
+        if let Err(e) = self.extension.disable().await {
+            self.set_status(ExtensionStatus::Failed { error: e.to_string() }).await;
+
+            return Err(e);
+        }
+
+        self.set_status(ExtensionStatus::Stopped).await;
+
+        Ok(())
+    }
+
+    /// Give the extension a new config, as the studio does when the user saves it
+    pub async fn configure(&mut self, config: ExtensionConfig) -> OmniverlayResult<()> {
+        let mut state = self.info().await.state;
+        state.config = Some(config);
+
+        self.extension.update_state(state).await
+    }
+
+    /// Replace the granted capabilities, only the declared ones can be granted
+    pub async fn grant(&mut self, capabilities: Vec<Capability>) -> OmniverlayResult<()> {
+        let mut info = self.info().await;
+
+        self.grants.grant(&capabilities);
+
+        info.state.granted_capabilities = capabilities;
+
+        self.extension.update_state(info.state).await
+    }
+
+    /// Time elapsed on the clock of the harness
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Move the clock forward, running every tick that falls due on the way. Stops at the first
+    /// tick that fails.
+    pub async fn advance(&mut self, duration: Duration) -> OmniverlayResult<()> {
+        let until = self.now + duration;
+
+        while let Some(due) = self.next_tick.filter(|due| *due <= until) {
+            self.now = due;
+            self.next_tick = self.tick_interval().map(|interval| due + interval);
+
+            self.extension.on_tick().await?;
+        }
+
+        self.now = until;
+
+        Ok(())
+    }
+
+    /// Run a tick right away, without moving the clock
+    pub async fn tick(&mut self) -> OmniverlayResult<()> {
+        self.extension.on_tick().await
+    }
+
+    /// Payloads published since the last call, oldest first. Only the last 16 are kept.
+    pub fn published_values(&mut self) -> Vec<Value> {
+        std::iter::from_fn(|| self.published.try_recv_value()).collect()
+    }
+
+    pub fn published<P: DeserializeOwned>(&mut self) -> OmniverlayResult<Vec<P>> {
+        self.published_values()
+            .into_iter()
+            .map(|payload| Ok(serde_json::from_value(payload)?))
+            .collect()
+    }
+
+    /// Last payload published, the one the overlay shows
+    pub fn last_published<P: DeserializeOwned>(&self) -> OmniverlayResult<Option<P>> {
+        match self.host.last_data(&self.extension_name) {
+            Some(payload) => Ok(Some(serde_json::from_value(payload)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Panics unless the last payload published is `expected`
+    #[track_caller]
+    pub fn assert_published<P: Serialize>(&self, expected: &P) {
+        let expected = serde_json::to_value(expected).expect("expected payload should serialize");
+
+        match self.host.last_data(&self.extension_name) {
+            Some(payload) => assert_eq!(payload, expected, "extension {} published another payload", self.extension_name),
+            None => panic!("extension {} published nothing, expected {}", self.extension_name, expected),
+        }
+    }
+
+    /// Payloads of `Extension::preview_payloads`, works while the extension is disabled
+    pub async fn preview<P: DeserializeOwned>(&mut self) -> OmniverlayResult<Vec<P>> {
+        self.extension
+            .preview_payloads()
+            .await?
+            .into_iter()
+            .map(|payload| Ok(serde_json::from_value(payload)?))
+            .collect()
+    }
+
+    /// Call a handler registered with `ExtensionHost::handle`, the extension must be enabled
+    pub async fn request<P: Serialize, R: DeserializeOwned>(&self, method: &str, params: &P) -> OmniverlayResult<R> {
+        self.host.request(&self.extension_name, method, params).await
+    }
+
+    /// Events sent by the extension since the last call. Data events are sent from a task and at
+    /// most every 50 ms, prefer `published` to check the data.
+    pub async fn take_events(&self) -> Vec<OmniverlayEventType> {
+        tokio::task::yield_now().await;
+
+        self.events.take()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.extension
+            .tick_interval()
+            .map(|interval| interval.max(MIN_TICK_INTERVAL))
+    }
+
+    async fn set_status(&self, status: ExtensionStatus) {
+        self.info.lock().await.status = status;
+    }
+}
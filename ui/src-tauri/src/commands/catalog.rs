@@ -0,0 +1,33 @@
+use log::info;
+use omniverlay_core::{
+    extensions::catalog::{AvailableExtension, ExtensionCatalog},
+    get_omniverlay,
+};
+
+use super::packages::PackageInstall;
+
+/// Extensions of the catalogs matching `query`, all of them when it is empty
+#[tauri::command]
+pub async fn search_catalog(query: String) -> Result<Vec<AvailableExtension>, String> {
+    Ok(ExtensionCatalog::load()?.search(&query).await?)
+}
+
+#[tauri::command]
+pub async fn check_catalog_updates() -> Result<Vec<AvailableExtension>, String> {
+    Ok(ExtensionCatalog::load()?.updates().await?)
+}
+
+/// Install or update the extension `id` to the newest version of the catalogs
+#[tauri::command]
+pub async fn install_from_catalog(id: String) -> Result<PackageInstall, String> {
+    info!("Invoked install_from_catalog with id: {}", id);
+
+    let available = ExtensionCatalog::load()?.find(&id).await?;
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    let (package, outcome) = omniverlay_guard.install_catalog_entry(&available.entry).await?;
+
+    Ok(PackageInstall { package, outcome })
+}
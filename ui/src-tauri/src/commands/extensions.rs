@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use log::info;
 use omniverlay_core::{
-    errors::OmniverlayError, event::OmniverlayEventType, extensions::{ExtensionLayout, ExtensionState}, get_omniverlay, invoke_event
+    errors::OmniverlayError, event::OmniverlayEventType, extensions::{data::DataRetention, usage::UsageReport, ExtensionLayout, ExtensionState}, get_omniverlay, invoke_event
 };
 
 #[tauri::command]
@@ -23,6 +23,70 @@ pub async fn list_extensions() -> Result<serde_json::Value, String> {
     Ok(json)
 }
 
+/// Last payload published by an extension, `null` if it did not publish anything yet
+#[tauri::command]
+pub async fn get_extension_data(name: String) -> Result<Option<serde_json::Value>, String> {
+    let omniverlay = get_omniverlay();
+    let guard = omniverlay.read().await;
+
+    let data = guard
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .get_extension_data(&name)?;
+
+    Ok(data)
+}
+
+/// Sample payloads of an extension for the layout editor, empty if it has none
+#[tauri::command]
+pub async fn get_extension_preview(name: String) -> Result<Vec<serde_json::Value>, String> {
+    let omniverlay = get_omniverlay();
+    let guard = omniverlay.read().await;
+
+    let payloads = guard
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .preview_payloads(&name)
+        .await?;
+
+    Ok(payloads)
+}
+
+#[tauri::command]
+pub async fn get_extensions_data() -> Result<HashMap<String, serde_json::Value>, String> {
+    let omniverlay = get_omniverlay();
+    let guard = omniverlay.read().await;
+
+    let data = guard
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .get_extensions_data();
+
+    Ok(data)
+}
+
+/// Time spent in hooks and publish rate of every extension instance
+#[tauri::command]
+pub async fn get_extensions_usage() -> Result<HashMap<String, UsageReport>, String> {
+    let omniverlay = get_omniverlay();
+    let guard = omniverlay.read().await;
+
+    let usage = guard
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .usage();
+
+    Ok(usage)
+}
+
 #[tauri::command]
 pub async fn update_extensions_state(
     states: HashMap<String, ExtensionState>,
@@ -51,6 +115,27 @@ pub async fn update_extensions_state(
     Ok(())
 }
 
+/// Start an extension again after it was disabled over its budget, saving the profile does not
+#[tauri::command]
+pub async fn enable_extension(name: String) -> Result<(), String> {
+    info!("Invoked enable_extension with name: {}", name);
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    omniverlay_guard
+        .get_extension_manager()
+        .await
+        .read()
+        .await
+        .enable_extension(&name)
+        .await?;
+
+    invoke_event!(OmniverlayEventType::UpdateExtensionData);
+
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn update_extensions_layout(layouts: HashMap<String, ExtensionLayout>) -> Result<(), String> {
     info!("Invoked update_extension_layout with layouts: {:?}", layouts);
@@ -76,4 +161,100 @@ pub async fn update_extensions_layout(layouts: HashMap<String, ExtensionLayout>)
     layout_manager_guard.save_data().await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Add an instance of an extension to the current profile and layout, returns its name
+#[tauri::command]
+pub async fn add_extension_instance(extension_type: String) -> Result<String, String> {
+    info!("Invoked add_extension_instance with extension_type: {}", extension_type);
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    let info = {
+        let extension_manager = omniverlay_guard.get_extension_manager().await;
+        let mut extension_manager_guard = extension_manager.write().await;
+
+        let name = extension_manager_guard.create_extension_instance(&extension_type).await?;
+
+        let extension = extension_manager_guard.get_extension_by_name(&name)?;
+        let info = extension.lock().await.get_extension_info()?;
+        let info = info.lock().await.clone();
+
+        info
+    };
+
+    let profile_manager = omniverlay_guard.get_profile_manager().await;
+    let profile_manager_guard = profile_manager.read().await;
+
+    profile_manager_guard
+        .get_current()
+        .await?
+        .write()
+        .await
+        .extensions
+        .insert(info.name.clone(), info.state.clone());
+
+    profile_manager_guard.save_data().await?;
+
+    if let Some(layout) = info.layout {
+        let layout_manager = omniverlay_guard.get_layout_manager().await;
+        let layout_manager_guard = layout_manager.read().await;
+
+        layout_manager_guard
+            .get_current()
+            .await?
+            .write()
+            .await
+            .extensions
+            .insert(info.name.clone(), layout);
+
+        layout_manager_guard.save_data().await?;
+    }
+
+    Ok(info.name)
+}
+
+/// Remove an instance of an extension from the current profile and layout
+#[tauri::command]
+pub async fn remove_extension_instance(name: String) -> Result<(), String> {
+    info!("Invoked remove_extension_instance with name: {}", name);
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    omniverlay_guard
+        .get_extension_manager()
+        .await
+        .write()
+        .await
+        .remove_extension_instance(&name)
+        .await?;
+
+    let profile_manager = omniverlay_guard.get_profile_manager().await;
+    let profile_manager_guard = profile_manager.read().await;
+
+    profile_manager_guard.get_current().await?.write().await.extensions.remove(&name);
+    profile_manager_guard.save_data().await?;
+
+    let layout_manager = omniverlay_guard.get_layout_manager().await;
+    let layout_manager_guard = layout_manager.read().await;
+
+    layout_manager_guard.get_current().await?.write().await.extensions.remove(&name);
+    layout_manager_guard.save_data().await?;
+
+    Ok(())
+}
+
+/// Unregister an extension with all its instances, pruning or keeping them in the current profile and layout
+#[tauri::command]
+pub async fn unregister_extension(extension_type: String, retention: DataRetention) -> Result<(), String> {
+    info!("Invoked unregister_extension with extension_type: {} and retention: {:?}", extension_type, retention);
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    omniverlay_guard.unregister_extension(&extension_type, retention).await?;
+
+    Ok(())
+}
@@ -1,2 +1,5 @@
+pub mod catalog;
 pub mod extensions;
-pub mod native;
\ No newline at end of file
+pub mod native;
+pub mod packages;
+pub mod settings;
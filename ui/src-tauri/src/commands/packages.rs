@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use log::info;
+use omniverlay_core::{
+    extensions::{
+        installer::{InstallOutcome, InstalledPackage, PackageInstaller},
+        package::ExtensionPackage,
+        signing::TrustStatus,
+    },
+    get_omniverlay,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct PackageInstall {
+    pub package: InstalledPackage,
+    pub outcome: InstallOutcome,
+}
+
+#[derive(Serialize)]
+pub struct PackageCheck {
+    outcome: InstallOutcome,
+    trust: TrustStatus,
+}
+
+/// What installing a package would do without installing it, fails if the package is invalid or
+/// refused by the signing policy
+#[tauri::command]
+pub async fn check_extension_package(path: PathBuf) -> Result<PackageCheck, String> {
+    let package = ExtensionPackage::open(&path)?;
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    let extension_manager = omniverlay_guard.get_extension_manager().await;
+    let trust = extension_manager.read().await.signing_policy().admit_package(&path, &package)?;
+
+    Ok(PackageCheck {
+        outcome: PackageInstaller::new()?.plan(&package, true)?,
+        trust,
+    })
+}
+
+#[tauri::command]
+pub async fn install_extension_package(path: PathBuf, allow_downgrade: bool) -> Result<PackageInstall, String> {
+    info!("Invoked install_extension_package with path: {} and allow_downgrade: {}", path.display(), allow_downgrade);
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    let (package, outcome) = omniverlay_guard.install_package(&path, allow_downgrade).await?;
+
+    Ok(PackageInstall { package, outcome })
+}
+
+#[tauri::command]
+pub async fn list_installed_packages() -> Result<Vec<InstalledPackage>, String> {
+    Ok(PackageInstaller::new()?.list_installed()?)
+}
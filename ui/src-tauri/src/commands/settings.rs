@@ -0,0 +1,24 @@
+use log::info;
+use omniverlay_core::{extensions::signing::SigningPolicy, get_omniverlay, settings::OmniverlaySettings};
+
+#[tauri::command]
+pub async fn get_settings() -> Result<OmniverlaySettings, String> {
+    Ok(OmniverlaySettings::load()?)
+}
+
+/// Save the settings, they apply to the extensions loaded from now on
+#[tauri::command]
+pub async fn update_settings(settings: OmniverlaySettings) -> Result<(), String> {
+    info!("Invoked update_settings with settings: {:?}", settings);
+
+    settings.save()?;
+
+    let omniverlay = get_omniverlay();
+    let omniverlay_guard = omniverlay.read().await;
+
+    let extension_manager = omniverlay_guard.get_extension_manager().await;
+
+    extension_manager.write().await.set_signing_policy(SigningPolicy::load_or_default());
+
+    Ok(())
+}
@@ -6,11 +6,16 @@ mod utils;
 
 use std::sync::Arc;
 
-use omniverlay_core::{errors::OmniverlayError, event::{OmniverlayEvent, OmniverlayEventHandler, OmniverlayEventType, EVENT_HANDLER}, get_omniverlay};
-use performance::PerformanceExtension;
+use omniverlay_core::{errors::OmniverlayError, event::{OmniverlayEvent, OmniverlayEventHandler, OmniverlayEventType, EVENT_HANDLER}, extensions::{assets::ASSETS_SCHEME, channel::data_event_name, signing::SigningPolicy}, get_omniverlay, utils::fs::get_extensions_dir};
+#[cfg(debug_assertions)]
+use omniverlay_core::extensions::reload;
 use tauri::{AppHandle, WindowBuilder, Manager};
 use tokio::sync::RwLock;
-use utils::tray::{self, create_system_tray, on_system_tray_event};
+use utils::{assets::handle_asset_request, tray::{self, create_system_tray, on_system_tray_event}};
+
+// Links the compiled-in extensions so they register themselves
+#[cfg(feature = "performance")]
+use performance as _;
 
 const OVERLAY_SIZE: tauri::Size = tauri::Size::Physical(tauri::PhysicalSize {
     width: 0,
@@ -25,16 +30,32 @@ const STUDIO_SIZE: tauri::Size = tauri::Size::Physical(tauri::PhysicalSize {
 #[tauri::command]
 async fn bootstrap_backend(app: AppHandle) -> Result<(), String> {
     let app_handle: Arc<AppHandle> = Arc::new(app);
+    let assets_handle = Arc::clone(&app_handle);
 
     async fn handle_event(app_handle: Arc<AppHandle>, event: OmniverlayEvent) {
         let app_handle = Arc::clone(&app_handle);
 
-        match event.event_type {
+        let emitted = match event.event_type {
             OmniverlayEventType::UpdateExtensionData => {
-                app_handle.clone().emit_all("Omniverlay://refresh_extensions_data", true).unwrap();
+                let emitted = app_handle.emit_all("Omniverlay://refresh_extensions_data", true);
 
                 tray::update_system_tray(&app_handle.clone().app_handle()).await;
+
+                emitted
+            },
+            OmniverlayEventType::PublishExtensionData(name, data) => {
+                app_handle.emit_all(&data_event_name(&name), data)
             },
+            OmniverlayEventType::ExtensionGaveUp(name, error) => {
+                app_handle.emit_all("Omniverlay://extension_gave_up", serde_json::json!({ "name": name, "error": error }))
+            },
+            OmniverlayEventType::ExtensionOverBudget(name, reason) => {
+                app_handle.emit_all("Omniverlay://extension_over_budget", serde_json::json!({ "name": name, "reason": reason }))
+            },
+        };
+
+        if let Err(e) = emitted {
+            log::error!("Failed to emit event to the frontend: {}", e);
         }
     }
 
@@ -51,9 +72,25 @@ async fn bootstrap_backend(app: AppHandle) -> Result<(), String> {
 
     // Register extension (Write lock)
     {
-        guard.get_extension_manager().await.write().await.register_extension(PerformanceExtension::new()).await?;
+        let extension_manager = guard.get_extension_manager().await;
+        let mut extension_manager_guard = extension_manager.write().await;
+
+        extension_manager_guard.register_compiled_extensions().await?;
+
+        // Before loading anything the user's policy may refuse
+        extension_manager_guard.set_signing_policy(SigningPolicy::load_or_default());
+
+        // Load compiled extensions dropped in ~/.omniverlay/extensions
+        extension_manager_guard.load_extensions_from_dir(&get_extensions_dir()?).await?;
+
+        // Served by the omniverlay-ext protocol
+        assets_handle.manage(extension_manager_guard.assets());
     }
 
+    // Reload extensions as they are rebuilt while developing them
+    #[cfg(debug_assertions)]
+    reload::watch(guard.get_extension_manager().await).await?;
+
     guard.startup().await.map_err(|_| OmniverlayError::BackendInitialization("Failed to start Omniverlay".to_string()))?;
 
     //invoke_event!(guard, EventType::UpdateExtensions("AAaaaaa".to_string()));
@@ -110,6 +147,7 @@ async fn main() {
 
             Ok(())
         })
+        .register_uri_scheme_protocol(ASSETS_SCHEME, handle_asset_request)
         .system_tray(create_system_tray().await)
         .on_system_tray_event(|app_handle, event| {
             let app_handle = app_handle.clone();
@@ -124,8 +162,24 @@ async fn main() {
             bootstrap_backend,
             commands::native::open_url,
             commands::extensions::list_extensions,
+            commands::extensions::get_extension_data,
+            commands::extensions::get_extensions_data,
+            commands::extensions::get_extension_preview,
+            commands::extensions::get_extensions_usage,
             commands::extensions::update_extensions_state,
-            commands::extensions::update_extensions_layout
+            commands::extensions::enable_extension,
+            commands::extensions::update_extensions_layout,
+            commands::extensions::add_extension_instance,
+            commands::extensions::remove_extension_instance,
+            commands::extensions::unregister_extension,
+            commands::packages::check_extension_package,
+            commands::packages::install_extension_package,
+            commands::packages::list_installed_packages,
+            commands::catalog::search_catalog,
+            commands::catalog::check_catalog_updates,
+            commands::catalog::install_from_catalog,
+            commands::settings::get_settings,
+            commands::settings::update_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
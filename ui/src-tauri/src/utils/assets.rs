@@ -0,0 +1,18 @@
+use omniverlay_core::extensions::assets::AssetRegistry;
+use tauri::{
+    http::{Request, Response, ResponseBuilder},
+    AppHandle, Manager,
+};
+
+/// Serve the `omniverlay-ext` protocol from the assets of the extensions, see
+/// `omniverlay_core::extensions::assets`. Nothing is served until the backend is bootstrapped.
+pub fn handle_asset_request(app: &AppHandle, request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+    let asset = app
+        .try_state::<AssetRegistry>()
+        .and_then(|assets| assets.load(request.uri()));
+
+    match asset {
+        Some(asset) => ResponseBuilder::new().mimetype(asset.mime_type).body(asset.contents),
+        None => ResponseBuilder::new().status(404).body(Vec::new()),
+    }
+}
@@ -1 +1,2 @@
+pub mod assets;
 pub mod tray;
\ No newline at end of file
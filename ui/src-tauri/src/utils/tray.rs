@@ -137,6 +137,18 @@ pub async fn on_system_tray_event(app_handle: &AppHandle, event: SystemTrayEvent
             } else {
                 match id.as_str() {
                     "quit" => {
+                        {
+                            let omniverlay = get_omniverlay();
+                            let omniverlay_guard = omniverlay.read().await;
+
+                            let extension_manager = omniverlay_guard.get_extension_manager().await;
+                            let extension_manager_guard = extension_manager.read().await;
+
+                            if let Err(e) = extension_manager_guard.disable_all().await {
+                                log::error!("Failed to disable extensions: {}", e);
+                            }
+                        }
+
                         app_handle.exit(0);
                     }
                     "add_profile" => {
@@ -0,0 +1,44 @@
+import type { Capability, ExtensionInfo } from "$lib/extensions/types";
+
+export const capabilityLabel = (capability: Capability): string => {
+    switch (capability.type) {
+        case "filesystem":
+            return `${capability.write ? "Write" : "Read"} access to ${capability.path}`;
+        case "process-list":
+            return "Process listing";
+        case "system-info":
+            return "System information";
+        case "network":
+            return "Network access";
+        case "spawn-process":
+            return "Starting programs";
+    }
+};
+
+const sameCapability = (a: Capability, b: Capability) =>
+    JSON.stringify(a) === JSON.stringify(b);
+
+export const isGranted = (extension: ExtensionInfo, capability: Capability) =>
+    (extension.state.granted_capabilities ?? []).some((granted) =>
+        sameCapability(granted, capability),
+    );
+
+export const setGranted = (
+    extension: ExtensionInfo,
+    capability: Capability,
+    granted: boolean,
+) => {
+    const others = (extension.state.granted_capabilities ?? []).filter(
+        (other) => !sameCapability(other, capability),
+    );
+
+    extension.state.granted_capabilities = granted
+        ? [...others, capability]
+        : others;
+};
+
+/** Capabilities the extension declares but the user did not grant yet */
+export const missingCapabilities = (extension: ExtensionInfo) =>
+    (extension.capabilities ?? []).filter(
+        (capability) => !isGranted(extension, capability),
+    );
@@ -1,4 +1,12 @@
-import type { ExtensionInfo } from "$lib/extensions/types";
+import type {
+    AvailableExtension,
+    ExtensionInfo,
+    InstallOutcome,
+    InstalledPackage,
+    OmniverlaySettings,
+    PackageCheck,
+    UsageReport,
+} from "$lib/extensions/types";
 import { invoke } from "@tauri-apps/api";
 import type { SvelteComponent } from "svelte";
 
@@ -6,6 +14,93 @@ export const getExtensions = async (): Promise<ExtensionInfo[]> => {
     return await invoke("list_extensions");
 }
 
+/** Event carrying the payloads of an extension, see `channel::data_event_name`. Tauri only
+ *  accepts alphanumerics and `-/:_` in event names, other bytes are written as `:` and two hex
+ *  digits. */
+export const extensionDataEvent = (extension: string): string => {
+    const encoded = Array.from(new TextEncoder().encode(extension), (byte) => {
+        const char = String.fromCharCode(byte);
+
+        return /[A-Za-z0-9_-]/.test(char) ? char : `:${byte.toString(16).toUpperCase().padStart(2, "0")}`;
+    }).join("");
+
+    return `Extension://${encoded}/data`;
+}
+
+export const getExtensionData = async <T>(extension: string): Promise<T | null> => {
+    return await invoke("get_extension_data", { name: extension });
+}
+
+/** Sample payloads for the layout editor, see `Extension::preview_payloads` */
+export const getExtensionPreview = async <T>(extension: string): Promise<T[]> => {
+    return await invoke("get_extension_preview", { name: extension });
+}
+
+export const getExtensionsUsage = async (): Promise<Record<string, UsageReport>> => {
+    return await invoke("get_extensions_usage");
+}
+
+export const addExtensionInstance = async (extensionType: string): Promise<string> => {
+    return await invoke("add_extension_instance", { extensionType });
+}
+
+export const removeExtensionInstance = async (name: string): Promise<void> => {
+    return await invoke("remove_extension_instance", { name });
+}
+
+export type DataRetention = "prune" | "keep";
+
+export const unregisterExtension = async (extensionType: string, retention: DataRetention): Promise<void> => {
+    return await invoke("unregister_extension", { extensionType, retention });
+}
+
+export const checkExtensionPackage = async (path: string): Promise<PackageCheck> => {
+    return await invoke("check_extension_package", { path });
+}
+
+export const installExtensionPackage = async (
+    path: string,
+    allowDowngrade: boolean,
+): Promise<{ package: InstalledPackage; outcome: InstallOutcome }> => {
+    return await invoke("install_extension_package", { path, allowDowngrade });
+}
+
+export const listInstalledPackages = async (): Promise<InstalledPackage[]> => {
+    return await invoke("list_installed_packages");
+}
+
+export const searchCatalog = async (query: string): Promise<AvailableExtension[]> => {
+    return await invoke("search_catalog", { query });
+}
+
+export const checkCatalogUpdates = async (): Promise<AvailableExtension[]> => {
+    return await invoke("check_catalog_updates");
+}
+
+export const installFromCatalog = async (
+    id: string,
+): Promise<{ package: InstalledPackage; outcome: InstallOutcome }> => {
+    return await invoke("install_from_catalog", { id });
+}
+
+export const getSettings = async (): Promise<OmniverlaySettings> => {
+    return await invoke("get_settings");
+}
+
+export const updateSettings = async (settings: OmniverlaySettings): Promise<void> => {
+    return await invoke("update_settings", { settings });
+}
+
+/** Page of the frontend an extension ships, see `ExtensionFrame`. Windows webviews reach custom
+ *  protocols through https. */
+export const extensionAssetsUrl = (info: ExtensionInfo): string => {
+    const base = navigator.userAgent.includes("Windows")
+        ? "https://omniverlay-ext.localhost"
+        : "omniverlay-ext://localhost";
+
+    return `${base}/${encodeURIComponent(info.extension_type)}/index.html?instance=${encodeURIComponent(info.name)}`;
+}
+
 export const loadExtension = async (extension: string): Promise<SvelteComponent<any> | null> => {
     try {
         const module = await import(`$lib/extensions/components/${extension}.svelte`);
@@ -1,12 +1,126 @@
 export interface ExtensionInfo {
+    /** Name of the instance, `<extension>#<n>` for every instance but the first */
     name: string;
+    /** Name of the extension this is an instance of, also the name of its component */
+    extension_type: string;
     state: ExtensionState;
     layout?: ExtensionLayout;
+    manifest?: ExtensionManifest;
+    status: ExtensionStatus;
+    capabilities?: Capability[];
+    /** Whether its files are signed by a trusted key, absent when compiled into the app */
+    trust?: TrustStatus;
+    /** Whether it ships its own frontend, shown by `ExtensionFrame` */
+    has_assets?: boolean;
+}
+
+export type TrustStatus =
+    | { trust: "trusted"; signer: string }
+    | { trust: "untrusted"; key?: string }
+    | { trust: "invalid"; error: string };
+
+export type Capability =
+    | { type: "filesystem"; path: string; write: boolean }
+    | { type: "process-list" }
+    | { type: "system-info" }
+    | { type: "network" }
+    | { type: "spawn-process" };
+
+export type ExtensionStatus =
+    | "Stopped"
+    | "Starting"
+    | "Running"
+    | "Stopping"
+    | { Failed: { error: string } }
+    | { OverBudget: { reason: string } };
+
+export interface ExtensionManifest {
+    id: string;
+    name: string;
+    version: string;
+    author?: string;
+    description?: string;
+    homepage?: string;
+    icon?: string;
+    tags: string[];
+    min_host_version?: string;
+    dependencies?: Record<string, string>;
+    restart?: RestartPolicy;
+    budget?: ResourceBudget;
+    capabilities?: Capability[];
+    /** Sample payloads for the layout editor */
+    preview?: unknown[];
+}
+
+export type RestartPolicy =
+    | { policy: "never" }
+    | { policy: "on-failure"; initial_backoff_ms: number; max_backoff_ms: number }
+    | {
+          policy: "max-retries";
+          max_retries: number;
+          initial_backoff_ms: number;
+          max_backoff_ms: number;
+      };
+
+export interface ResourceBudget {
+    max_cpu_percent: number;
+    max_publish_rate: number;
+}
+
+/** Totals since the extension was registered, rates over the last few seconds */
+export interface UsageReport {
+    hook_calls: number;
+    wall_time_ms: number;
+    cpu_time_ms: number;
+    published: number;
+    cpu_percent: number;
+    publish_rate: number;
+    budget?: ResourceBudget;
+}
+
+export type InstallOutcome =
+    | { kind: "installed" }
+    | { kind: "upgraded"; from: string }
+    | { kind: "downgraded"; from: string }
+    | { kind: "reinstalled" };
+
+/** What installing a package would do, and who signed it */
+export interface PackageCheck {
+    outcome: InstallOutcome;
+    trust: TrustStatus;
+}
+
+/** Package listed in a catalog */
+export interface CatalogEntry {
+    id: string;
+    name: string;
+    version: string;
+    description?: string;
+    author?: string;
+    tags: string[];
+    min_host_version?: string;
+    url: string;
+    sha256: string;
+}
+
+/** Newest version of an extension in the catalogs */
+export interface AvailableExtension {
+    entry: CatalogEntry;
+    catalog: string;
+    installed?: string;
+    update_available: boolean;
+}
+
+/** Extension directory of `~/.omniverlay/extensions` */
+export interface InstalledPackage {
+    manifest: ExtensionManifest;
+    dir: string;
 }
 
 export interface ExtensionState {
     is_enabled: boolean;
     config: ExtensionConfig;
+    granted_capabilities?: Capability[];
 }
 
 export interface ExtensionConfig {
@@ -37,4 +151,11 @@ export interface ExtensionLayout {
     height: number;
     x: number;
     y: number;
-}
\ No newline at end of file
+}
+
+/** `~/.omniverlay/settings.json` */
+export interface OmniverlaySettings {
+    require_signed_packages: boolean;
+    /** Urls of the extension catalogs, `file://` or `http://` */
+    catalogs: string[];
+}